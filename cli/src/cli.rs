@@ -102,7 +102,14 @@ pub fn flag_list(input: &str) -> Vec<FlagName<'a>> {
     just("-")
         .ignore_then(choice((
             just("-")
-                .ignore_then(ident)
+                .ignore_then(
+                    filter(
+                        |ch: &char| ch.is_ascii_alphanumeric() || *ch == '-' || *ch == '_',
+                        Expectation::LongFlag,
+                    )
+                    .repeated()
+                    .slice(),
+                )
                 .map(|name| vec![FlagName::Long(name)]),
             slice(filter(|ch| *ch != ' ', Expectation::ShortFlag))
                 .map(FlagName::Short)
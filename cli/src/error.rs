@@ -64,6 +64,17 @@ pub enum ParseError {
         error: ParseIntError,
     },
 
+    #[error("invalid value for {flag}: {actual:?}")]
+    #[diagnostic(code(cli::invalid_value))]
+    InvalidValue {
+        flag: String,
+        actual: String,
+        #[label = "here"]
+        at: SourceSpan,
+        #[help]
+        help: Option<Cow<'static, str>>,
+    },
+
     #[error("{label}; last token was {last_token:?}")]
     Text {
         #[label = "here"]
@@ -93,6 +104,10 @@ pub enum Expectation {
     Digit(u32),
     #[error("a short flag character (anything but a whitespace)")]
     ShortFlag,
+    #[error("a long flag character (a letter, a digit, `-` or `_`)")]
+    LongFlag,
+    #[error("a flag value (anything but a whitespace)")]
+    FlagValue,
 }
 
 impl<'a> aott::error::Error<&'a str> for ParseError {
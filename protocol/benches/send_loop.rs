@@ -0,0 +1,132 @@
+//! Compares the batched [`PlayerNet`] send task with writing every packet on its own,
+//! like the send task used to.
+//!
+//! Run with `cargo bench -p oxcr_protocol --bench send_loop`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use oxcr_protocol::{
+    bytes::Bytes,
+    model::packets::{PluginMessage, SerializedPacket},
+    net::NetConfig,
+    ser::{Identifier, Serialize},
+    PlayerNet,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    runtime::Runtime,
+};
+use tokio_util::sync::CancellationToken;
+
+/// Roughly the size of a chunk packet for a mostly empty chunk.
+const PACKET_SIZE: usize = 2048;
+
+fn packets(count: usize) -> Vec<PluginMessage> {
+    (0..count)
+        .map(|i| PluginMessage {
+            channel: Identifier::MINECRAFT_BRAND,
+            data: Bytes::from(vec![i as u8; PACKET_SIZE]),
+        })
+        .collect()
+}
+
+fn wire_length(packets: &[PluginMessage]) -> usize {
+    packets
+        .iter()
+        .map(|packet| {
+            SerializedPacket::new_ref(packet)
+                .and_then(|spack| spack.serialize())
+                .expect("failed to serialize packet")
+                .len()
+        })
+        .sum()
+}
+
+async fn connected() -> (TcpStream, TcpStream) {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap())
+        .await
+        .unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+    (server, client)
+}
+
+/// The send loop before batching: one serialization and one `write_all` per packet.
+async fn per_packet_loop() -> (flume::Sender<SerializedPacket>, TcpStream) {
+    let (server, client) = connected().await;
+    let (_read, mut write) = server.into_split();
+    let (send, recv) = flume::unbounded::<SerializedPacket>();
+
+    tokio::spawn(async move {
+        while let Ok(packet) = recv.recv_async().await {
+            let data = packet.serialize().unwrap();
+            write.write_all(&data).await.unwrap();
+        }
+    });
+
+    (send, client)
+}
+
+async fn player_net() -> (PlayerNet, TcpStream) {
+    let (server, client) = connected().await;
+    let (read, write) = server.into_split();
+    let net = PlayerNet::new(
+        read,
+        write,
+        CancellationToken::new(),
+        None,
+        NetConfig::default(),
+    );
+
+    (net, client)
+}
+
+fn send_loop(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("send_loop");
+
+    for count in [64, 512] {
+        let packets = packets(count);
+        let total = wire_length(&packets);
+        let mut sink = vec![0u8; total];
+        group.throughput(Throughput::Bytes(total as u64));
+
+        let (send, mut client) = runtime.block_on(per_packet_loop());
+        group.bench_with_input(
+            BenchmarkId::new("per_packet", count),
+            &packets,
+            |b, packets| {
+                b.iter(|| {
+                    runtime.block_on(async {
+                        for packet in packets {
+                            send.send(SerializedPacket::new_ref(packet).unwrap())
+                                .unwrap();
+                        }
+                        client.read_exact(&mut sink).await.unwrap();
+                    })
+                })
+            },
+        );
+
+        let (net, mut client) = runtime.block_on(player_net());
+        group.bench_with_input(
+            BenchmarkId::new("batched", count),
+            &packets,
+            |b, packets| {
+                b.iter(|| {
+                    runtime.block_on(async {
+                        for packet in packets {
+                            net.send_packet(packet.clone()).await.unwrap();
+                        }
+                        client.read_exact(&mut sink).await.unwrap();
+                    })
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, send_loop);
+criterion_main!(benches);
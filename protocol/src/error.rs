@@ -2,6 +2,7 @@ use std::{str::Utf8Error, string::FromUtf8Error};
 
 use crate::{
     model::packets::SerializedPacket,
+    net::QueuedPacket,
     ser::{SerializationError, WithSource},
 };
 use miette::Diagnostic;
@@ -28,7 +29,7 @@ pub enum Error {
     InvalidPacketId(i32),
     #[error("Packet send error: {_0:?}")]
     #[diagnostic(code(flume::error::send))]
-    Send(#[from] flume::SendError<(bool, SerializedPacket)>),
+    Send(#[from] flume::SendError<QueuedPacket>),
     #[error("Packet send error: {_0:?}")]
     #[diagnostic(code(flume::error::send))]
     SendSingle(#[from] flume::SendError<SerializedPacket>),
@@ -60,6 +61,15 @@ pub enum Error {
     #[error("Invalid bit flags encountered")]
     #[diagnostic(code(protocol::error::invalid_bit_flags))]
     InvalidBitFlags,
+    #[error("The {queue} queue is full (capacity: {capacity} packets)")]
+    #[diagnostic(
+        code(protocol::error::queue_full),
+        help("the other side can't keep up; consider raising the queue capacity or changing its policy in NetConfig")
+    )]
+    QueueFull {
+        queue: &'static str,
+        capacity: usize,
+    },
     #[error("Connection ended")]
     #[diagnostic(code(protocol::error::connection_reset))]
     ConnectionEnded,
@@ -19,8 +19,11 @@ pub mod executor;
 pub mod logging;
 pub mod model;
 pub mod nbt;
+pub mod net;
 pub mod nsfr;
 pub mod ser;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub use aott;
 pub use bytes;
 use bytes::BytesMut;
@@ -128,29 +131,32 @@ use bevy::{app::ScheduleRunnerPlugin, prelude::*, time::TimePlugin};
 use error::Result;
 use ser::*;
 use std::{
+    collections::VecDeque,
     fmt::Debug,
     net::SocketAddr,
     ops::{Deref, DerefMut},
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     time::Duration,
 };
 
 use executor::*;
+use net::*;
 
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::tcp::{OwnedReadHalf, OwnedWriteHalf},
     select,
-    sync::RwLock,
+    sync::{Notify, RwLock},
 };
 
 use crate::{
     model::{
         packets::{
-            Packet, PacketContext, PluginMessage, SerializedPacket, SerializedPacketCompressed,
+            Packet, PacketCompressor, PacketContext, PluginMessage, SerializedPacket,
+            SerializedPacketCompressed,
         },
         State,
     },
@@ -159,7 +165,7 @@ use crate::{
 
 #[derive(Debug)]
 pub struct PlayerNet {
-    pub send: flume::Sender<(bool, SerializedPacket)>,
+    pub send: flume::Sender<QueuedPacket>,
     pub recv: flume::Receiver<SerializedPacket>,
     pub peer_addr: SocketAddr,
     pub local_addr: SocketAddr,
@@ -167,6 +173,11 @@ pub struct PlayerNet {
     pub compression: Option<usize>,
     pub compressing: Arc<AtomicBool>,
     pub cancellator: CancellationToken,
+    pub config: NetConfig,
+    pub metrics: Arc<NetMetrics>,
+    flush: Arc<Notify>,
+    /// Packets that didn't fit into the send queue but mustn't be dropped, in the order they're sent in.
+    overflow: Mutex<VecDeque<QueuedPacket>>,
 }
 
 #[derive(Component, Deref, Debug)]
@@ -182,28 +193,85 @@ impl PlayerNet {
         mut write: OwnedWriteHalf,
         cancellator: CancellationToken,
         compression: Option<usize>,
+        config: NetConfig,
     ) -> Self {
         let peer_addr = read.peer_addr().expect("no peer address");
         let local_addr = read.local_addr().expect("no local address");
 
-        let (s_recv, recv) = flume::unbounded();
-        let (send, r_send) = flume::unbounded();
+        // keep the capacities the queues really have, so that errors report those
+        let config = NetConfig {
+            send_capacity: config.send_capacity.max(1),
+            recv_capacity: config.recv_capacity.max(1),
+            ..config
+        };
+        let (s_recv, recv) = flume::bounded(config.recv_capacity);
+        let (send, r_send) = flume::bounded::<QueuedPacket>(config.send_capacity);
 
         let compressing = Arc::new(AtomicBool::new(false));
+        let metrics = Arc::new(NetMetrics::default());
 
+        let flush = Arc::new(Notify::new());
+
+        let metrics_ = metrics.clone();
+        let flush_ = flush.clone();
         let send_task = tokio::spawn(async move {
+            let metrics = metrics_;
+            let flush = flush_;
+            let mut compressor =
+                compression.map(|threshold| PacketCompressor::new(threshold, config.compression));
+            let mut out = BytesMut::new();
+            let mut in_flight = 0usize;
+
             let Err::<!, _>(e) = async {
                 loop {
-                    let (compres, packet): (bool, SerializedPacket) = r_send.recv_async().await?;
-                    let data = if compression.is_some_and(|_| compres) {
-                        trace!("[send]compressing");
-                        packet.serialize_compressing(compression)?
-                    } else {
-                        trace!("[send]not compressing");
-                        packet.serialize()?
-                    };
-                    trace!(initial_packet=?packet, ?data, "sending packet");
-                    write.write_all(&data).await?;
+                    select! {
+                        biased;
+
+                        () = flush.notified(), if config.flush_on_tick && !out.is_empty() => {
+                            trace!(bytes = out.len(), "[send]flushing at tick boundary");
+                        }
+                        queued = r_send.recv_async() => {
+                            let queued = match queued {
+                                Ok(queued) => queued,
+                                Err(error) => {
+                                    // what was held back for the tick still goes out, like a disconnect message
+                                    write.write_all(&out).await?;
+                                    Err(error)?
+                                }
+                            };
+                            in_flight += encode_queued(queued, compressor.as_mut(), &mut out)?;
+
+                            // coalesce everything that is already waiting into the same write
+                            while out.len() < config.max_batch_bytes {
+                                match r_send.try_recv() {
+                                    Ok(queued) => {
+                                        in_flight += encode_queued(queued, compressor.as_mut(), &mut out)?
+                                    }
+                                    Err(
+                                        flume::TryRecvError::Empty
+                                        | flume::TryRecvError::Disconnected,
+                                    ) => break,
+                                }
+                            }
+
+                            if config.flush_on_tick
+                                && out.len() < config.max_batch_bytes
+                                && !r_send.is_disconnected()
+                            {
+                                continue;
+                            }
+                        }
+                    }
+
+                    trace!(bytes = out.len(), "sending batch");
+                    write.write_all(&out).await?;
+                    metrics
+                        .bytes_sent
+                        .fetch_add(out.len() as u64, Ordering::Relaxed);
+                    metrics
+                        .bytes_in_flight
+                        .fetch_sub(std::mem::take(&mut in_flight), Ordering::SeqCst);
+                    out.clear();
                 }
             }
             .await;
@@ -214,7 +282,9 @@ impl PlayerNet {
         });
 
         let compressing__ = compressing.clone();
+        let metrics_ = metrics.clone();
         let recv_task = tokio::spawn(async move {
+            let metrics = metrics_;
             async {
                 let mut buf = BytesMut::new();
 
@@ -237,7 +307,25 @@ impl PlayerNet {
                         let offset = input.offset;
                         drop((bufslice, input));
                         buf = buf.split_off(offset);
-                        s_recv.send_async(packet).await?;
+                        metrics
+                            .bytes_received
+                            .fetch_add(offset as u64, Ordering::Relaxed);
+
+                        match config.recv_policy {
+                            RecvPolicy::Block => s_recv.send_async(packet).await?,
+                            RecvPolicy::Disconnect => match s_recv.try_send(packet) {
+                                Ok(()) => {}
+                                Err(flume::TrySendError::Full(_)) => {
+                                    return Err(crate::error::Error::QueueFull {
+                                        queue: "receive",
+                                        capacity: config.recv_capacity,
+                                    })
+                                }
+                                Err(flume::TrySendError::Disconnected(packet)) => {
+                                    return Err(flume::SendError(packet).into())
+                                }
+                            },
+                        }
                     } else {
                         if read.read_buf(&mut buf).await? == 0 {
                             if buf.is_empty() {
@@ -285,6 +373,48 @@ impl PlayerNet {
             compression,
             compressing,
             cancellator,
+            config,
+            metrics,
+            flush,
+            overflow: Mutex::default(),
+        }
+    }
+
+    /// Moves packets that overflowed the send queue into it as far as there's room, and asks the send task
+    /// to write out everything it has buffered.
+    /// The latter only has an effect if [`NetConfig::flush_on_tick`] is enabled, otherwise packets are written as soon as the queue runs dry.
+    pub fn flush(&self) {
+        self.drain_overflow(&mut self.overflow.lock().unwrap());
+        self.flush.notify_one();
+    }
+
+    /// Moves packets from the front of the overflow into the send queue until it's full.
+    fn drain_overflow(&self, overflow: &mut VecDeque<QueuedPacket>) {
+        while let Some(queued) = overflow.pop_front() {
+            match self.send.try_send(queued) {
+                Ok(()) => {}
+                Err(flume::TrySendError::Full(queued)) => {
+                    overflow.push_front(queued);
+                    break;
+                }
+                // nothing is going to send them anymore
+                Err(flume::TrySendError::Disconnected(_)) => {
+                    overflow.clear();
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Returns the current queue depths and traffic counters of this connection.
+    pub fn metrics(&self) -> NetMetricsSnapshot {
+        NetMetricsSnapshot {
+            send_queue_depth: self.send.len() + self.overflow.lock().unwrap().len(),
+            recv_queue_depth: self.recv.len(),
+            bytes_in_flight: self.metrics.bytes_in_flight(),
+            bytes_sent: self.metrics.bytes_sent(),
+            bytes_received: self.metrics.bytes_received(),
+            dropped_packets: self.metrics.dropped_packets(),
         }
     }
 
@@ -313,10 +443,138 @@ impl PlayerNet {
         }
         let spack = SerializedPacket::new_ref(&packet)?;
         trace!(?packet, addr=%self.peer_addr, ?spack, "Sending packet");
-        Ok(self
-            .send
-            .send_async((self.compressing.load(Ordering::SeqCst), spack))
-            .await?)
+        self.enqueue(QueuedPacket {
+            compress: self.compressing.load(Ordering::SeqCst),
+            essential: T::ESSENTIAL,
+            packet: spack,
+        })
+        .await
+    }
+
+    /// Writes a packet without waiting, for systems that can't await but need packets sent one after another
+    /// to arrive in that order (which separately spawned tasks don't guarantee).
+    ///
+    /// If the send queue is full, the [`SendPolicy`] decides what happens: packets that aren't dropped wait in order
+    /// until there's room, which later calls and [`flush`](Self::flush) make, and only [`SendPolicy::Disconnect`]
+    /// closes the connection.
+    pub fn queue_packet<T: Packet + Serialize + Debug>(&self, packet: T) -> Result<()> {
+        if self.send.is_disconnected() {
+            trace!(?packet, addr=%self.peer_addr, "queueing packet failed - disconnected");
+            return Err(crate::error::Error::ConnectionEnded);
+        }
+        let spack = SerializedPacket::new_ref(&packet)?;
+        trace!(?packet, addr=%self.peer_addr, ?spack, "Queueing packet");
+
+        let length = spack.length;
+        self.metrics
+            .bytes_in_flight
+            .fetch_add(length, Ordering::SeqCst);
+
+        let queued = QueuedPacket {
+            compress: self.compressing.load(Ordering::SeqCst),
+            essential: T::ESSENTIAL,
+            packet: spack,
+        };
+        let mut overflow = self.overflow.lock().unwrap();
+        self.drain_overflow(&mut overflow);
+        // nothing goes ahead of the packets that are already waiting
+        let sent = if overflow.is_empty() {
+            self.send.try_send(queued)
+        } else {
+            Err(flume::TrySendError::Full(queued))
+        };
+        let result = match sent {
+            Ok(()) => return Ok(()),
+            Err(flume::TrySendError::Disconnected(queued)) => Err(flume::SendError(queued).into()),
+            Err(flume::TrySendError::Full(queued)) => match self.config.send_policy {
+                SendPolicy::DropNonEssential if !queued.essential => {
+                    self.metrics.dropped_packets.fetch_add(1, Ordering::Relaxed);
+                    trace!(packet=?queued.packet, addr=%self.peer_addr, "send queue is full, dropping non-essential packet");
+                    Ok(())
+                }
+                SendPolicy::Block | SendPolicy::DropNonEssential => {
+                    trace!(packet=?queued.packet, addr=%self.peer_addr, "send queue is full, waiting for room");
+                    overflow.push_back(queued);
+                    return Ok(());
+                }
+                SendPolicy::Disconnect => {
+                    self.cancellator.cancel();
+                    Err(crate::error::Error::QueueFull {
+                        queue: "send",
+                        capacity: self.config.send_capacity,
+                    })
+                }
+            },
+        };
+
+        self.metrics
+            .bytes_in_flight
+            .fetch_sub(length, Ordering::SeqCst);
+
+        result
+    }
+
+    /// Like [`queue_packet`](Self::queue_packet), for when there's nothing to do about it failing.
+    /// The connection closes by itself then, so the error is only logged.
+    pub fn queue<T: Packet + Serialize + Debug>(&self, packet: T) {
+        if let Err(error) = self.queue_packet(packet) {
+            debug!(?error, packet = std::any::type_name::<T>(), addr = %self.peer_addr, "failed to queue a packet");
+        }
+    }
+
+    /// Puts a packet into the send queue, applying the configured [`SendPolicy`] if the queue is full.
+    async fn enqueue(&self, queued: QueuedPacket) -> Result<()> {
+        let length = queued.packet.length;
+        // count the bytes before the send task can see the packet, so that it never subtracts first.
+        self.metrics
+            .bytes_in_flight
+            .fetch_add(length, Ordering::SeqCst);
+
+        let result = match self.send.try_send(queued) {
+            Ok(()) => Ok(()),
+            Err(flume::TrySendError::Disconnected(queued)) => Err(flume::SendError(queued).into()),
+            Err(flume::TrySendError::Full(queued)) => match self.config.send_policy {
+                SendPolicy::DropNonEssential if !queued.essential => {
+                    self.metrics.dropped_packets.fetch_add(1, Ordering::Relaxed);
+                    trace!(packet=?queued.packet, addr=%self.peer_addr, "send queue is full, dropping non-essential packet");
+                    self.metrics
+                        .bytes_in_flight
+                        .fetch_sub(length, Ordering::SeqCst);
+                    return Ok(());
+                }
+                SendPolicy::Block | SendPolicy::DropNonEssential => {
+                    // behind the packets that overflowed the queue, if there are any
+                    let waiting = {
+                        let mut overflow = self.overflow.lock().unwrap();
+                        if overflow.is_empty() {
+                            Some(queued)
+                        } else {
+                            overflow.push_back(queued);
+                            None
+                        }
+                    };
+                    match waiting {
+                        Some(queued) => self.send.send_async(queued).await.map_err(Into::into),
+                        None => Ok(()),
+                    }
+                }
+                SendPolicy::Disconnect => {
+                    self.cancellator.cancel();
+                    Err(crate::error::Error::QueueFull {
+                        queue: "send",
+                        capacity: self.config.send_capacity,
+                    })
+                }
+            },
+        };
+
+        if result.is_err() {
+            self.metrics
+                .bytes_in_flight
+                .fetch_sub(length, Ordering::SeqCst);
+        }
+
+        result
     }
 
     /// Sends a plugin message.
@@ -356,15 +614,292 @@ impl PlayerNet {
     }
 }
 
+/// Serializes a queued packet to the end of `out`, returning how many bytes it counted towards [`NetMetrics::bytes_in_flight`].
+fn encode_queued(
+    QueuedPacket {
+        compress, packet, ..
+    }: QueuedPacket,
+    compressor: Option<&mut PacketCompressor>,
+    out: &mut BytesMut,
+) -> Result<usize> {
+    match compressor {
+        Some(compressor) if compress => {
+            trace!("[send]compressing");
+            compressor.write(&packet, out)?
+        }
+        _ => {
+            trace!("[send]not compressing");
+            packet.serialize_to(out)?
+        }
+    }
+    trace!(initial_packet=?packet, "queued packet for sending");
+    Ok(packet.length)
+}
+
+/// Flushes every player's send buffer at the end of the tick.
+fn flush_players(players: Query<&PlayerN>) {
+    for player in &players {
+        player.flush();
+    }
+}
+
 pub struct ProtocolPlugin;
 
 impl Plugin for ProtocolPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins((
+            TaskPoolPlugin::default(),
             TokioTasksPlugin::default(),
             TypeRegistrationPlugin,
             TimePlugin,
             ScheduleRunnerPlugin::run_loop(Duration::from_millis(50)),
+        ))
+        .add_systems(Last, flush_players);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use tokio::net::TcpStream;
+
+    use super::*;
+    use crate::{
+        model::{packets::play::SetBlockDestroyStage, VarInt},
+        testing::connect,
+    };
+
+    /// Much more than the socket buffers hold, so the send task is stuck writing it until the client reads.
+    const STUCK_SIZE: usize = 16 << 20;
+
+    fn message(size: usize) -> PluginMessage {
+        PluginMessage {
+            channel: Identifier::MINECRAFT_BRAND,
+            data: Bytes::from(vec![0; size]),
+        }
+    }
+
+    fn destroy_stage() -> SetBlockDestroyStage {
+        SetBlockDestroyStage {
+            entity_id: VarInt(1),
+            location: Position {
+                x: 0i16.into(),
+                z: 0i16.into(),
+                y: 0i8.into(),
+            },
+            stage: 0,
+        }
+    }
+
+    fn wire_length(packet: &PluginMessage) -> usize {
+        SerializedPacket::new_ref(packet)
+            .and_then(|packet| packet.serialize())
+            .unwrap()
+            .len()
+    }
+
+    /// Leaves the send task stuck writing a packet, and a packet in the send queue, which has a capacity of 1.
+    async fn fill(net: &PlayerNet) {
+        net.send_packet(message(STUCK_SIZE)).await.unwrap();
+        while net.metrics().send_queue_depth > 0 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        net.send_packet(message(1)).await.unwrap();
+    }
+
+    /// Writes packets with 0, 1, 2... bytes of data, returning how many bytes that was.
+    async fn write_packets(client: &mut TcpStream, count: usize) -> usize {
+        let mut written = 0;
+        for size in 0..count {
+            let bytes = SerializedPacket::new(message(size))
+                .and_then(|packet| packet.serialize())
+                .unwrap();
+            client.write_all(&bytes).await.unwrap();
+            written += bytes.len();
+        }
+        written
+    }
+
+    #[tokio::test]
+    async fn drops_non_essential_packets_when_full() {
+        let config = NetConfig {
+            send_capacity: 1,
+            send_policy: SendPolicy::DropNonEssential,
+            ..Default::default()
+        };
+        let (net, _client) = connect(config).await;
+        fill(&net).await;
+
+        net.queue_packet(destroy_stage()).unwrap();
+        net.send_packet(destroy_stage()).await.unwrap();
+
+        let metrics = net.metrics();
+        assert_eq!(metrics.dropped_packets, 2);
+        assert_eq!(metrics.send_queue_depth, 1);
+        assert_eq!(
+            metrics.bytes_in_flight,
+            SerializedPacket::new(message(STUCK_SIZE)).unwrap().length
+                + SerializedPacket::new(message(1)).unwrap().length
+        );
+        assert!(!net.cancellator.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn blocks_until_there_is_room() {
+        let config = NetConfig {
+            send_capacity: 1,
+            send_policy: SendPolicy::Block,
+            ..Default::default()
+        };
+        let (net, mut client) = connect(config).await;
+        let net = Arc::new(net);
+        fill(&net).await;
+
+        let waiting = tokio::spawn({
+            let net = net.clone();
+            async move { net.send_packet(destroy_stage()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!waiting.is_finished());
+
+        // reading lets the send task get to the rest of the queue
+        let reading = tokio::spawn(async move {
+            let mut read = 0;
+            let mut buf = vec![0; 64 * 1024];
+            loop {
+                match client.read(&mut buf).await {
+                    Ok(0) | Err(_) => break read,
+                    Ok(n) => read += n,
+                }
+            }
+        });
+        tokio::time::timeout(Duration::from_secs(10), waiting)
+            .await
+            .expect("the packet was never queued")
+            .unwrap()
+            .unwrap();
+        while net.metrics().bytes_in_flight > 0 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        let sent = wire_length(&message(STUCK_SIZE))
+            + wire_length(&message(1))
+            + SerializedPacket::new(destroy_stage())
+                .and_then(|packet| packet.serialize())
+                .unwrap()
+                .len();
+        assert_eq!(net.metrics().bytes_sent, sent as u64);
+        assert_eq!(net.metrics().dropped_packets, 0);
+
+        net.cancellator.cancel();
+        drop(net);
+        assert_eq!(reading.await.unwrap(), sent);
+    }
+
+    #[tokio::test]
+    async fn queues_in_order_when_full() {
+        let config = NetConfig {
+            send_capacity: 1,
+            send_policy: SendPolicy::Block,
+            ..Default::default()
+        };
+        let (net, mut client) = connect(config).await;
+        fill(&net).await;
+
+        let packets = (0..3)
+            .map(|stage| SetBlockDestroyStage {
+                stage,
+                ..destroy_stage()
+            })
+            .collect::<Vec<_>>();
+        for packet in &packets {
+            net.queue_packet(packet.clone()).unwrap();
+        }
+        assert_eq!(net.metrics().send_queue_depth, 4);
+        assert!(!net.cancellator.is_cancelled());
+
+        let reading = tokio::spawn(async move {
+            let mut bytes = vec![];
+            client.read_to_end(&mut bytes).await.unwrap();
+            bytes
+        });
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while net.metrics().bytes_in_flight > 0 {
+                net.flush();
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .expect("the packets were never sent");
+
+        let expected = packets
+            .into_iter()
+            .flat_map(|packet| {
+                SerializedPacket::new(packet)
+                    .and_then(|packet| packet.serialize())
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        drop(net);
+        assert!(reading.await.unwrap().ends_with(&expected));
+    }
+
+    #[tokio::test]
+    async fn disconnects_when_full() {
+        // raised to 1, which is what the error has to report
+        let config = NetConfig {
+            send_capacity: 0,
+            send_policy: SendPolicy::Disconnect,
+            ..Default::default()
+        };
+        let (net, _client) = connect(config).await;
+        fill(&net).await;
+
+        let error = net.send_packet(message(1)).await.unwrap_err();
+        assert!(matches!(
+            error,
+            crate::error::Error::QueueFull {
+                queue: "send",
+                capacity: 1
+            }
         ));
+        assert!(net.cancellator.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn waits_for_room_to_receive() {
+        let config = NetConfig {
+            recv_capacity: 1,
+            recv_policy: RecvPolicy::Block,
+            ..Default::default()
+        };
+        let (net, mut client) = connect(config).await;
+        let written = write_packets(&mut client, 3).await;
+
+        for size in 0..3 {
+            let packet = net.recv.recv_async().await.unwrap();
+            assert_eq!(
+                packet.data,
+                SerializedPacket::new(message(size)).unwrap().data
+            );
+        }
+        assert_eq!(net.metrics().bytes_received, written as u64);
+        assert!(!net.cancellator.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn disconnects_when_receiving_too_much() {
+        let config = NetConfig {
+            recv_capacity: 1,
+            recv_policy: RecvPolicy::Disconnect,
+            ..Default::default()
+        };
+        let (net, mut client) = connect(config).await;
+        write_packets(&mut client, 3).await;
+
+        tokio::time::timeout(Duration::from_secs(10), net.cancellator.cancelled())
+            .await
+            .expect("the player wasn't disconnected");
+        assert_eq!(net.recv.len(), 1);
     }
 }
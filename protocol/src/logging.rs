@@ -1,5 +1,6 @@
 #![allow(unstable_name_collisions)]
 use indexmap::IndexMap as HashMap;
+use std::sync::Mutex;
 
 use itertools::Itertools;
 use nu_ansi_term::{Color, Style};
@@ -8,6 +9,23 @@ use tracing_subscriber::{registry::LookupSpan, Layer};
 
 pub struct CraftLayer;
 
+type Printer = Box<dyn FnMut(String) + Send>;
+
+static PRINTER: Mutex<Option<Printer>> = Mutex::new(None);
+
+impl CraftLayer {
+    /// Hands every log line to `printer` instead of printing it to stdout,
+    /// e.g. to print above an interactive prompt without breaking it.
+    pub fn set_printer(printer: impl FnMut(String) + Send + 'static) {
+        *PRINTER.lock().unwrap() = Some(Box::new(printer));
+    }
+
+    /// Goes back to printing log lines to stdout.
+    pub fn remove_printer() {
+        *PRINTER.lock().unwrap() = None;
+    }
+}
+
 impl<S: tracing::Subscriber + for<'lo> LookupSpan<'lo>> Layer<S> for CraftLayer {
     fn on_event(&self, event: &tracing::Event<'_>, _cx: tracing_subscriber::layer::Context<'_, S>) {
         // inspiration taken from pnpm
@@ -68,7 +86,12 @@ impl<S: tracing::Subscriber + for<'lo> LookupSpan<'lo>> Layer<S> for CraftLayer
             .intersperse_with(|| Color::LightRed.paint("::").to_string())
             .collect::<String>();
 
-        println!("{level} {target}{message} {other_fields}");
+        let line = format!("{level} {target}{message} {other_fields}");
+
+        match &mut *PRINTER.lock().unwrap() {
+            Some(printer) => printer(line),
+            None => println!("{line}"),
+        }
     }
 }
 
@@ -1,4 +1,8 @@
+pub mod block;
 pub mod chat;
+pub mod chunk;
+pub mod command;
+pub mod metadata;
 pub mod packets;
 pub mod registry;
 mod varint;
@@ -13,6 +17,8 @@ pub mod item;
 
 pub const MAX_PACKET_DATA: usize = 0x1FFFFF;
 pub const PROTOCOL_VERSION: i32 = 763;
+/// The Minecraft version [`PROTOCOL_VERSION`] belongs to.
+pub const MINECRAFT_VERSION: &str = "1.20.1";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum State {
@@ -22,7 +28,8 @@ pub enum State {
     Play,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(crate = "serde", rename_all = "lowercase")]
 #[repr(u8)]
 pub enum Difficulty {
     #[default]
@@ -170,25 +177,112 @@ impl WorldgenBiome {
         scale: None,
         category: Some("minecraft:plains"),
         temperature_modifier: None,
+        effects: Some(Self::EFFECTS),
+    };
+
+    pub const DESERT: Self = Self {
+        has_precipitation: false,
+        temperature: 2.0,
+        downfall: 0.0,
+        category: None,
+        effects: Some(BiomeEffects {
+            sky_color: 0x6eb1ff,
+            ..Self::EFFECTS
+        }),
+        ..Self::PLAINS
+    };
+
+    pub const FOREST: Self = Self {
+        has_precipitation: true,
+        temperature: 0.7,
+        downfall: 0.8,
+        category: None,
+        effects: Some(BiomeEffects {
+            sky_color: 0x79a6ff,
+            ..Self::EFFECTS
+        }),
+        ..Self::PLAINS
+    };
+
+    pub const SNOWY_PLAINS: Self = Self {
+        has_precipitation: true,
+        temperature: 0.0,
+        downfall: 0.5,
+        category: None,
+        effects: Some(BiomeEffects {
+            sky_color: 0x7fa1ff,
+            ..Self::EFFECTS
+        }),
+        ..Self::PLAINS
+    };
+
+    pub const BEACH: Self = Self {
+        has_precipitation: true,
+        temperature: 0.8,
+        downfall: 0.4,
+        category: None,
         effects: Some(BiomeEffects {
-            fog_color: 0xc0d8ff,
             sky_color: 0x78a7ff,
-            water_color: 0x3f76e4,
-            water_fog_color: 0x505330,
-            mood_sound: Some(BiomeMoodSound {
-                sound: "minecraft:ambient.cave",
-                tick_delay: 6000,
-                offset: 2.0,
-                block_search_extend: 8,
-            }),
-            additions_sound: None,
-            ambient_sound: None,
-            foliage_color: None,
-            grass_color: None,
-            grass_color_modifier: None,
-            music: None,
-            particle: None,
+            ..Self::EFFECTS
+        }),
+        ..Self::PLAINS
+    };
+
+    pub const OCEAN: Self = Self {
+        has_precipitation: true,
+        temperature: 0.5,
+        downfall: 0.5,
+        category: None,
+        effects: Some(BiomeEffects {
+            sky_color: 0x7ba4ff,
+            ..Self::EFFECTS
+        }),
+        ..Self::PLAINS
+    };
+
+    pub const DEEP_OCEAN: Self = Self {
+        has_precipitation: true,
+        temperature: 0.5,
+        downfall: 0.5,
+        category: None,
+        effects: Some(BiomeEffects {
+            sky_color: 0x7ba4ff,
+            ..Self::EFFECTS
+        }),
+        ..Self::PLAINS
+    };
+
+    pub const STONY_PEAKS: Self = Self {
+        has_precipitation: true,
+        temperature: 1.0,
+        downfall: 0.3,
+        category: None,
+        effects: Some(BiomeEffects {
+            sky_color: 0x76a8ff,
+            ..Self::EFFECTS
+        }),
+        ..Self::PLAINS
+    };
+
+    /// How plains look and sound, which the other biomes only change the sky color of.
+    const EFFECTS: BiomeEffects = BiomeEffects {
+        fog_color: 0xc0d8ff,
+        sky_color: 0x78a7ff,
+        water_color: 0x3f76e4,
+        water_fog_color: 0x505330,
+        mood_sound: Some(BiomeMoodSound {
+            sound: "minecraft:ambient.cave",
+            tick_delay: 6000,
+            offset: 2.0,
+            block_search_extend: 8,
         }),
+        additions_sound: None,
+        ambient_sound: None,
+        foliage_color: None,
+        grass_color: None,
+        grass_color_modifier: None,
+        music: None,
+        particle: None,
     };
 }
 
@@ -0,0 +1,772 @@
+//! Blocks and their states, as they're sent in packets and stored in chunks.
+//!
+//! Block state ids are indices in the block state registry, which the client has built in,
+//! so they're worked out from `blocks.txt`, every block for [`PROTOCOL_VERSION`](super::PROTOCOL_VERSION) in registry order.
+//! Each line is the name of a block followed by its properties, like `snowy=true,*false`, with the default value marked by `*`.
+//! It can be regenerated from `reports/blocks.json` of the vanilla data generator.
+//!
+//! A block has a state for each combination of its property values. The states of a block have consecutive ids,
+//! with the properties in alphabetical order and the last one changing the fastest.
+
+use std::{collections::HashMap, fmt, ops::Range, str::FromStr, sync::OnceLock};
+
+use super::item::item_name;
+use crate::model::VarInt;
+
+const BLOCKS: &str = include_str!("blocks.txt");
+
+/// A block state by its id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BlockState(pub u16);
+
+/// A property of a block and the values it can have, like `facing` with `north`, `south`, `west` and `east`.
+#[derive(Debug)]
+pub struct Property {
+    pub name: &'static str,
+    pub values: Vec<&'static str>,
+}
+
+#[derive(Debug)]
+pub struct Block {
+    /// Without the namespace.
+    pub name: &'static str,
+    pub properties: Vec<Property>,
+    pub states: Range<u16>,
+    pub default_state: BlockState,
+}
+
+struct BlockRegistry {
+    blocks: Vec<Block>,
+    ids: HashMap<&'static str, usize>,
+    /// The index of the block of each state.
+    state_blocks: Vec<u16>,
+}
+
+fn registry() -> &'static BlockRegistry {
+    static REGISTRY: OnceLock<BlockRegistry> = OnceLock::new();
+
+    REGISTRY.get_or_init(|| {
+        let mut blocks = vec![];
+        let mut state_blocks = vec![];
+
+        for line in BLOCKS.lines() {
+            let mut words = line.split(' ');
+            let name = words.next().unwrap_or_default();
+
+            let first = state_blocks.len() as u16;
+            let mut default = 0;
+            let mut properties = vec![];
+            for property in words {
+                let (property, values) = property.split_once('=').unwrap_or((property, ""));
+                let values = values.split(',').collect::<Vec<_>>();
+
+                let default_index = values
+                    .iter()
+                    .position(|value| value.starts_with('*'))
+                    .unwrap_or_default();
+                default = default * values.len() + default_index;
+
+                properties.push(Property {
+                    name: property,
+                    values: values
+                        .into_iter()
+                        .map(|value| value.trim_start_matches('*'))
+                        .collect(),
+                });
+            }
+
+            let count = properties
+                .iter()
+                .map(|property| property.values.len())
+                .product::<usize>();
+            state_blocks.resize(state_blocks.len() + count, blocks.len() as u16);
+
+            blocks.push(Block {
+                name,
+                properties,
+                states: first..first + count as u16,
+                default_state: BlockState(first + default as u16),
+            });
+        }
+
+        let ids = blocks
+            .iter()
+            .enumerate()
+            .map(|(id, block)| (block.name, id))
+            .collect();
+
+        BlockRegistry {
+            blocks,
+            ids,
+            state_blocks,
+        }
+    })
+}
+
+impl Block {
+    /// The block with the name, with or without the `minecraft:` namespace.
+    pub fn from_name(name: &str) -> Option<&'static Self> {
+        let name = name.strip_prefix("minecraft:").unwrap_or(name);
+        let registry = registry();
+        registry.ids.get(name).map(|id| &registry.blocks[*id])
+    }
+
+    pub fn all() -> &'static [Self] {
+        &registry().blocks
+    }
+
+    /// How many states change at once when the property goes to its next value.
+    fn stride(&self, index: usize) -> u16 {
+        self.properties[index + 1..]
+            .iter()
+            .map(|property| property.values.len() as u16)
+            .product()
+    }
+
+    fn property(&self, name: &str) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|property| property.name == name)
+    }
+}
+
+/// Which tools break a block faster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Pickaxe,
+    Shovel,
+    Axe,
+    Hoe,
+}
+
+/// Blocks made of wood have one of these in their name.
+const WOODS: [&str; 11] = [
+    "oak", "spruce", "birch", "jungle", "acacia", "cherry", "dark_oak", "mangrove", "bamboo",
+    "crimson", "warped",
+];
+
+/// How long a block takes to break, negative if it can't be, which tool breaks it faster,
+/// and whether it drops anything when broken without that tool.
+///
+/// This isn't in the data generator's reports, so only the common blocks are listed,
+/// others take as long as dirt.
+fn mining(name: &str) -> (f32, Option<Tool>, bool) {
+    use Tool::*;
+
+    let wooden = WOODS.iter().any(|wood| name.contains(wood));
+    let ends_with = |suffixes: &[&str]| suffixes.iter().any(|suffix| name.ends_with(suffix));
+    match name {
+        "bedrock"
+        | "barrier"
+        | "light"
+        | "end_portal"
+        | "end_portal_frame"
+        | "end_gateway"
+        | "nether_portal"
+        | "moving_piston"
+        | "water"
+        | "lava"
+        | "command_block"
+        | "repeating_command_block"
+        | "chain_command_block"
+        | "structure_block"
+        | "jigsaw" => (-1.0, None, false),
+        "air"
+        | "cave_air"
+        | "void_air"
+        | "grass"
+        | "fern"
+        | "dead_bush"
+        | "tall_grass"
+        | "large_fern"
+        | "torch"
+        | "wall_torch"
+        | "redstone_torch"
+        | "redstone_wall_torch"
+        | "soul_torch"
+        | "soul_wall_torch"
+        | "redstone_wire"
+        | "sugar_cane"
+        | "wheat"
+        | "carrots"
+        | "potatoes"
+        | "beetroots"
+        | "flower_pot"
+        | "slime_block"
+        | "honey_block" => (0.0, None, false),
+        "obsidian" | "crying_obsidian" | "netherite_block" | "respawn_anchor" => {
+            (50.0, Some(Pickaxe), true)
+        }
+        "ancient_debris" => (30.0, Some(Pickaxe), true),
+        "diamond_block" | "emerald_block" | "iron_block" | "iron_door" | "iron_trapdoor"
+        | "iron_bars" | "anvil" | "chipped_anvil" | "damaged_anvil" => (5.0, Some(Pickaxe), true),
+        "gold_block" | "deepslate" => (3.0, Some(Pickaxe), true),
+        "cobbled_deepslate" | "polished_deepslate" | "deepslate_bricks" | "deepslate_tiles"
+        | "furnace" | "dispenser" | "dropper" => (3.5, Some(Pickaxe), true),
+        "stone"
+        | "granite"
+        | "polished_granite"
+        | "diorite"
+        | "polished_diorite"
+        | "andesite"
+        | "polished_andesite"
+        | "stone_bricks"
+        | "mossy_stone_bricks"
+        | "cracked_stone_bricks"
+        | "chiseled_stone_bricks"
+        | "blackstone" => (1.5, Some(Pickaxe), true),
+        "cobblestone" | "mossy_cobblestone" | "bricks" | "nether_bricks" | "smooth_stone" => {
+            (2.0, Some(Pickaxe), true)
+        }
+        "sandstone" | "red_sandstone" | "chiseled_sandstone" | "cut_sandstone" | "quartz_block" => {
+            (0.8, Some(Pickaxe), true)
+        }
+        "netherrack" => (0.4, Some(Pickaxe), true),
+        "end_stone" => (3.0, Some(Pickaxe), true),
+        "grass_block" | "mycelium" | "podzol" | "gravel" | "clay" | "farmland" => {
+            (0.6, Some(Shovel), false)
+        }
+        "dirt" | "coarse_dirt" | "rooted_dirt" | "mud" | "sand" | "red_sand" | "soul_sand"
+        | "soul_soil" => (0.5, Some(Shovel), false),
+        "snow" => (0.1, Some(Shovel), true),
+        "snow_block" => (0.2, Some(Shovel), true),
+        "ice" | "packed_ice" | "glass" | "glowstone" | "sea_lantern" => (0.3, None, false),
+        "chest" | "trapped_chest" | "crafting_table" | "bookshelf" => (2.5, Some(Axe), false),
+        _ if name.starts_with("deepslate_") && name.ends_with("_ore") => (4.5, Some(Pickaxe), true),
+        _ if name.ends_with("_ore") => (3.0, Some(Pickaxe), true),
+        _ if name.ends_with("_leaves") => (0.2, Some(Hoe), false),
+        _ if name.ends_with("_wool") => (0.8, None, false),
+        _ if name.ends_with("_carpet") => (0.1, None, false),
+        _ if ends_with(&["_glass", "_pane"]) => (0.3, None, false),
+        _ if name.ends_with("_concrete") => (1.8, Some(Pickaxe), true),
+        _ if name.ends_with("_terracotta") || name == "terracotta" => (1.25, Some(Pickaxe), true),
+        _ if ends_with(&["_sapling", "_tulip", "_mushroom"]) => (0.0, None, false),
+        _ if wooden && ends_with(&["_door", "_trapdoor"]) => (3.0, Some(Axe), false),
+        _ if wooden
+            && ends_with(&[
+                "_planks",
+                "_log",
+                "_wood",
+                "_stem",
+                "_hyphae",
+                "_fence",
+                "_fence_gate",
+                "_stairs",
+                "_slab",
+                "_block",
+                "_mosaic",
+            ]) =>
+        {
+            (2.0, Some(Axe), false)
+        }
+        _ if wooden && ends_with(&["_sign"]) => (1.0, Some(Axe), false),
+        _ if ends_with(&["_stairs", "_slab", "_wall"]) => (2.0, Some(Pickaxe), true),
+        _ => (0.5, None, false),
+    }
+}
+
+/// How much light a block gives off, and how much it takes away from light going through it, both from 0 to 15.
+///
+/// Like [`mining`], only the common blocks are listed. Blocks that aren't full cubes let all light through,
+/// where vanilla lets it through only some of their sides.
+fn light(state: BlockState) -> (u8, u8) {
+    let Some(name) = state.name() else {
+        return (0, 15);
+    };
+
+    let lit = state.get("lit") == Some("true");
+    let emission: u8 = match name {
+        "glowstone"
+        | "sea_lantern"
+        | "jack_o_lantern"
+        | "beacon"
+        | "lava"
+        | "fire"
+        | "lantern"
+        | "shroomlight"
+        | "end_gateway"
+        | "end_portal"
+        | "conduit"
+        | "ochre_froglight"
+        | "verdant_froglight"
+        | "pearlescent_froglight" => 15,
+        "campfire" | "redstone_lamp" if lit => 15,
+        "torch" | "wall_torch" | "end_rod" => 14,
+        "cave_vines" | "cave_vines_plant" if state.get("berries") == Some("true") => 14,
+        "furnace" | "blast_furnace" | "smoker" if lit => 13,
+        "nether_portal" => 11,
+        "soul_torch" | "soul_wall_torch" | "soul_lantern" | "soul_fire" | "crying_obsidian" => 10,
+        "soul_campfire" if lit => 10,
+        "redstone_ore" | "deepslate_redstone_ore" if lit => 9,
+        "redstone_torch" | "redstone_wall_torch" if lit => 7,
+        "glow_lichen" | "enchanting_table" | "ender_chest" => 7,
+        "amethyst_cluster" => 5,
+        "large_amethyst_bud" => 4,
+        "magma_block" => 3,
+        "medium_amethyst_bud" => 2,
+        "small_amethyst_bud"
+        | "brewing_stand"
+        | "brown_mushroom"
+        | "dragon_egg"
+        | "end_portal_frame"
+        | "sculk_sensor"
+        | "calibrated_sculk_sensor" => 1,
+        "light" => state.get_as("level").unwrap_or(15),
+        "respawn_anchor" => [0, 3, 7, 11, 15][state.get_as("charges").unwrap_or(0).min(4)],
+        "sea_pickle" if state.get("waterlogged") == Some("true") => {
+            3 + 3 * state.get_as::<u8>("pickles").unwrap_or(1)
+        }
+        _ if name.ends_with("candle") && lit => 3 * state.get_as::<u8>("candles").unwrap_or(1),
+        _ => 0,
+    };
+
+    let opacity = match name {
+        "water" | "lava" | "bubble_column" | "ice" | "frosted_ice" | "cobweb" | "seagrass"
+        | "tall_seagrass" | "kelp" | "kelp_plant" | "slime_block" | "honey_block" => 1,
+        _ if name.ends_with("_leaves") || state.get("waterlogged") == Some("true") => 1,
+        _ if lets_light_through(name) => 0,
+        _ => 15,
+    };
+
+    (emission, opacity)
+}
+
+fn lets_light_through(name: &str) -> bool {
+    let ends_with = |suffixes: &[&str]| suffixes.iter().any(|suffix| name.ends_with(suffix));
+    matches!(
+        name,
+        "air"
+            | "cave_air"
+            | "void_air"
+            | "glass"
+            | "iron_bars"
+            | "chain"
+            | "barrier"
+            | "light"
+            | "structure_void"
+            | "moving_piston"
+            | "end_portal"
+            | "end_gateway"
+            | "nether_portal"
+            | "fire"
+            | "soul_fire"
+            | "grass"
+            | "fern"
+            | "dead_bush"
+            | "tall_grass"
+            | "large_fern"
+            | "dandelion"
+            | "poppy"
+            | "blue_orchid"
+            | "allium"
+            | "azure_bluet"
+            | "oxeye_daisy"
+            | "cornflower"
+            | "lily_of_the_valley"
+            | "wither_rose"
+            | "torchflower"
+            | "sunflower"
+            | "lilac"
+            | "rose_bush"
+            | "peony"
+            | "pink_petals"
+            | "sugar_cane"
+            | "cactus"
+            | "bamboo"
+            | "vine"
+            | "glow_lichen"
+            | "lily_pad"
+            | "sweet_berry_bush"
+            | "cave_vines"
+            | "cave_vines_plant"
+            | "hanging_roots"
+            | "wheat"
+            | "carrots"
+            | "potatoes"
+            | "beetroots"
+            | "torch"
+            | "wall_torch"
+            | "redstone_torch"
+            | "redstone_wall_torch"
+            | "lantern"
+            | "soul_lantern"
+            | "end_rod"
+            | "lightning_rod"
+            | "ladder"
+            | "lever"
+            | "rail"
+            | "redstone_wire"
+            | "repeater"
+            | "comparator"
+            | "tripwire"
+            | "tripwire_hook"
+            | "flower_pot"
+            | "snow"
+            | "scaffolding"
+            | "pointed_dripstone"
+            | "sea_pickle"
+            | "turtle_egg"
+            | "cake"
+            | "candle"
+            | "chest"
+            | "trapped_chest"
+            | "ender_chest"
+            | "enchanting_table"
+            | "brewing_stand"
+            | "cauldron"
+            | "hopper"
+            | "anvil"
+            | "chipped_anvil"
+            | "damaged_anvil"
+            | "bell"
+            | "lectern"
+            | "stonecutter"
+            | "grindstone"
+            | "campfire"
+            | "soul_campfire"
+            | "conduit"
+            | "beacon"
+            | "amethyst_cluster"
+            | "large_amethyst_bud"
+            | "medium_amethyst_bud"
+            | "small_amethyst_bud"
+    ) || name.starts_with("potted_")
+        || ends_with(&[
+            "_glass",
+            "_pane",
+            "_slab",
+            "_stairs",
+            "_fence",
+            "_fence_gate",
+            "_wall",
+            "_door",
+            "_trapdoor",
+            "_sign",
+            "_banner",
+            "_button",
+            "_pressure_plate",
+            "_carpet",
+            "_bed",
+            "_torch",
+            "_rail",
+            "_sapling",
+            "_tulip",
+            "_mushroom",
+            "_roots",
+            "_candle",
+            "_head",
+            "_skull",
+            "_coral",
+            "_coral_fan",
+        ])
+}
+
+/// The light emission and opacity of every block state, by id.
+fn lights() -> &'static [(u8, u8)] {
+    static LIGHTS: OnceLock<Vec<(u8, u8)>> = OnceLock::new();
+    LIGHTS.get_or_init(|| {
+        (0..BlockState::count())
+            .map(|id| light(BlockState(id as u16)))
+            .collect()
+    })
+}
+
+impl BlockState {
+    pub const AIR: Self = Self(0);
+    pub const STONE: Self = Self(1);
+    /// Not snowy.
+    pub const GRASS_BLOCK: Self = Self(9);
+    pub const DIRT: Self = Self(10);
+    pub const BEDROCK: Self = Self(79);
+
+    /// How many block states there are.
+    pub fn count() -> usize {
+        registry().state_blocks.len()
+    }
+
+    pub fn block(self) -> Option<&'static Block> {
+        let registry = registry();
+        registry
+            .state_blocks
+            .get(usize::from(self.0))
+            .map(|block| &registry.blocks[usize::from(*block)])
+    }
+
+    /// The default state of the block with the name, with or without the `minecraft:` namespace.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Block::from_name(name).map(|block| block.default_state)
+    }
+
+    /// The block placed by the item, which has the same name as the block.
+    pub fn from_item(item: VarInt) -> Option<Self> {
+        Self::from_name(item_name(item)?).filter(|state| !state.is_air())
+    }
+
+    pub fn name(self) -> Option<&'static str> {
+        self.block().map(|block| block.name)
+    }
+
+    /// The value of each property, in order.
+    pub fn properties(self) -> Vec<(&'static str, &'static str)> {
+        let Some(block) = self.block() else {
+            return vec![];
+        };
+
+        let offset = self.0 - block.states.start;
+        block
+            .properties
+            .iter()
+            .enumerate()
+            .map(|(index, property)| {
+                let value = offset / block.stride(index) % property.values.len() as u16;
+                (property.name, property.values[usize::from(value)])
+            })
+            .collect()
+    }
+
+    /// The value of the property, like `north` for `facing`.
+    pub fn get(self, property: &str) -> Option<&'static str> {
+        self.properties()
+            .into_iter()
+            .find(|(name, _)| *name == property)
+            .map(|(_, value)| value)
+    }
+
+    /// The value of the property as a number or boolean, like `state.get_as::<bool>("waterlogged")`.
+    pub fn get_as<T: FromStr>(self, property: &str) -> Option<T> {
+        self.get(property)?.parse().ok()
+    }
+
+    /// The same block with the property changed, or `None` if the block doesn't have the property or value.
+    pub fn with(self, property: &str, value: impl fmt::Display) -> Option<Self> {
+        let block = self.block()?;
+        let index = block.property(property)?;
+        let values = &block.properties[index].values;
+
+        let value = value.to_string();
+        let new = values.iter().position(|other| *other == value)? as u16;
+        let stride = block.stride(index);
+        let old = (self.0 - block.states.start) / stride % values.len() as u16;
+
+        Some(Self(self.0 - old * stride + new * stride))
+    }
+
+    pub fn is_air(self) -> bool {
+        matches!(self.name(), Some("air" | "cave_air" | "void_air"))
+    }
+
+    /// Whether placing a block here replaces it, instead of putting the new block next to it.
+    pub fn is_replaceable(self) -> bool {
+        match self.name() {
+            Some("snow") => self.get("layers") == Some("1"),
+            name => matches!(
+                name,
+                Some(
+                    "air"
+                        | "cave_air"
+                        | "void_air"
+                        | "water"
+                        | "lava"
+                        | "bubble_column"
+                        | "grass"
+                        | "fern"
+                        | "dead_bush"
+                        | "tall_grass"
+                        | "large_fern"
+                        | "seagrass"
+                        | "tall_seagrass"
+                        | "vine"
+                        | "glow_lichen"
+                        | "hanging_roots"
+                        | "crimson_roots"
+                        | "warped_roots"
+                        | "nether_sprouts"
+                        | "fire"
+                        | "soul_fire"
+                        | "light"
+                        | "structure_void"
+                )
+            ),
+        }
+    }
+
+    pub fn hardness(self) -> f32 {
+        self.name().map_or(0.5, |name| mining(name).0)
+    }
+
+    pub fn tool(self) -> Option<Tool> {
+        self.name().and_then(|name| mining(name).1)
+    }
+
+    pub fn needs_tool(self) -> bool {
+        self.name().is_some_and(|name| mining(name).2)
+    }
+
+    /// How much block light it gives off, from 0 to 15.
+    pub fn light_emission(self) -> u8 {
+        lights().get(usize::from(self.0)).map_or(0, |light| light.0)
+    }
+
+    /// How much light going through it is dimmed, from 0 for air to 15 for blocks light can't get through.
+    /// Light goes down by at least 1 for every block it spreads to anyway.
+    pub fn light_opacity(self) -> u8 {
+        lights()
+            .get(usize::from(self.0))
+            .map_or(15, |light| light.1)
+    }
+}
+
+/// Like `minecraft:oak_stairs[facing=east,half=bottom,shape=straight,waterlogged=false]`.
+impl fmt::Display for BlockState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(name) = self.name() else {
+            return write!(f, "<unknown block state {}>", self.0);
+        };
+
+        write!(f, "minecraft:{name}")?;
+        let properties = self.properties();
+        if !properties.is_empty() {
+            let properties = properties
+                .iter()
+                .map(|(property, value)| format!("{property}={value}"))
+                .collect::<Vec<_>>();
+            write!(f, "[{}]", properties.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Properties that aren't given keep their default value.
+impl FromStr for BlockState {
+    type Err = InvalidBlockState;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, properties) = match s.split_once('[') {
+            Some((name, properties)) => (
+                name,
+                properties
+                    .strip_suffix(']')
+                    .ok_or_else(|| InvalidBlockState::Syntax(s.to_owned()))?,
+            ),
+            None => (s, ""),
+        };
+
+        let mut state = Self::from_name(name)
+            .ok_or_else(|| InvalidBlockState::UnknownBlock(name.to_owned()))?;
+        for property in properties
+            .split(',')
+            .filter(|property| !property.is_empty())
+        {
+            let (property, value) = property
+                .split_once('=')
+                .ok_or_else(|| InvalidBlockState::Syntax(s.to_owned()))?;
+            state = state.with(property.trim(), value.trim()).ok_or_else(|| {
+                InvalidBlockState::InvalidProperty {
+                    block: name.to_owned(),
+                    property: format!("{property}={value}"),
+                }
+            })?;
+        }
+
+        Ok(state)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error, miette::Diagnostic)]
+pub enum InvalidBlockState {
+    #[error("unknown block: {_0:?}")]
+    #[diagnostic(code(protocol::block::unknown_block))]
+    UnknownBlock(String),
+    #[error("{block:?} has no property {property:?}")]
+    #[diagnostic(code(protocol::block::invalid_property))]
+    InvalidProperty { block: String, property: String },
+    #[error("invalid block state: {_0:?}")]
+    #[diagnostic(
+        code(protocol::block::syntax),
+        help("a block state is a block name with optional properties, like `oak_stairs[facing=east]`")
+    )]
+    Syntax(String),
+}
+
+impl From<BlockState> for VarInt {
+    fn from(state: BlockState) -> Self {
+        VarInt(state.0.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::chunk::{bits_for, DIRECT_BLOCK_BITS};
+
+    #[test]
+    fn state_ids() {
+        assert_eq!(bits_for(BlockState::count()), DIRECT_BLOCK_BITS);
+
+        for (state, name) in [
+            (BlockState::AIR, "air"),
+            (BlockState::STONE, "stone"),
+            (BlockState::GRASS_BLOCK, "grass_block"),
+            (BlockState::DIRT, "dirt"),
+            (BlockState::BEDROCK, "bedrock"),
+        ] {
+            assert_eq!(BlockState::from_name(name), Some(state));
+        }
+
+        let stairs = Block::from_name("minecraft:oak_stairs").unwrap();
+        assert_eq!(stairs.states, 2874..2954);
+        assert_eq!(stairs.default_state, BlockState(2885));
+        assert_eq!(BlockState::from_name("chest"), Some(BlockState(2955)));
+    }
+
+    #[test]
+    fn properties() {
+        let stairs = BlockState::from_name("oak_stairs").unwrap();
+        assert_eq!(stairs.get("facing"), Some("north"));
+        assert_eq!(stairs.get_as::<bool>("waterlogged"), Some(false));
+
+        let east = stairs.with("facing", "east").unwrap();
+        assert_eq!(east.get("facing"), Some("east"));
+        assert_eq!(east.get("half"), Some("bottom"));
+        assert_eq!(east.with("facing", "north"), Some(stairs));
+        assert_eq!(stairs.with("waterlogged", true).unwrap().0, stairs.0 - 1);
+        assert_eq!(stairs.with("facing", "up"), None);
+        assert_eq!(stairs.with("age", 1), None);
+
+        let water = BlockState::from_name("water").unwrap();
+        assert_eq!(
+            water.with("level", 15).unwrap().get_as::<u8>("level"),
+            Some(15)
+        );
+
+        let parsed = "minecraft:oak_stairs[facing=east,waterlogged=true]"
+            .parse::<BlockState>()
+            .unwrap();
+        assert_eq!(
+            parsed.to_string(),
+            "minecraft:oak_stairs[facing=east,half=bottom,shape=straight,waterlogged=true]"
+        );
+        assert_eq!(parsed.to_string().parse(), Ok(parsed));
+        assert!(matches!(
+            "oak_stairs[facing=up]".parse::<BlockState>(),
+            Err(InvalidBlockState::InvalidProperty { .. })
+        ));
+    }
+
+    #[test]
+    fn light() {
+        let block = |name| BlockState::from_name(name).unwrap();
+        assert_eq!(BlockState::AIR.light_opacity(), 0);
+        assert_eq!(BlockState::STONE.light_opacity(), 15);
+        assert_eq!(block("water").light_opacity(), 1);
+        assert_eq!(block("oak_leaves").light_opacity(), 1);
+        assert_eq!(block("oak_stairs").light_opacity(), 0);
+
+        assert_eq!(block("torch").light_emission(), 14);
+        assert_eq!(block("glowstone").light_emission(), 15);
+        assert_eq!(block("glowstone").light_opacity(), 15);
+        let furnace = block("furnace");
+        assert_eq!(furnace.light_emission(), 0);
+        assert_eq!(furnace.with("lit", true).unwrap().light_emission(), 13);
+    }
+}
@@ -1,13 +1,27 @@
-use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
 
-use super::item::ItemData;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
+pub mod legacy;
+pub mod markup;
+
+/// A text component, in the JSON format the client understands.
+///
+/// Variants are tried in order when deserializing,
+/// so [`Normal`](Self::Normal) (which has no required fields) has to stay last.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ChatComponent {
-    Normal(BasicChatComponent),
+    /// A bare string, the same as `{"text": "..."}` without any formatting.
+    Plain(String),
     String(ChatStringComponent),
+    Translatable(ChatTranslatableComponent),
+    Keybind(ChatKeybindComponent),
+    Score(ChatScoreComponent),
+    Selector(ChatSelectorComponent),
+    Nbt(ChatNbtComponent),
     Multi(Vec<ChatComponent>),
+    Normal(BasicChatComponent),
 }
 
 impl Default for ChatComponent {
@@ -16,6 +30,53 @@ impl Default for ChatComponent {
     }
 }
 
+impl ChatComponent {
+    /// A plain text component without any formatting.
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::String(ChatStringComponent {
+            text: text.into(),
+            ..Default::default()
+        })
+    }
+
+    /// A component that the client translates into its language, like `multiplayer.player.joined`,
+    /// substituting `with` for the `%s` placeholders in the translation.
+    pub fn translatable(key: impl Into<String>, with: Vec<ChatComponent>) -> Self {
+        Self::Translatable(ChatTranslatableComponent {
+            translate: key.into(),
+            fallback: None,
+            with,
+            basic: BasicChatComponent::default(),
+        })
+    }
+
+    /// The formatting and children of this component, if it can have any.
+    pub fn basic(&self) -> Option<&BasicChatComponent> {
+        match self {
+            Self::Plain(_) | Self::Multi(_) => None,
+            Self::String(ChatStringComponent { basic, .. })
+            | Self::Translatable(ChatTranslatableComponent { basic, .. })
+            | Self::Keybind(ChatKeybindComponent { basic, .. })
+            | Self::Score(ChatScoreComponent { basic, .. })
+            | Self::Selector(ChatSelectorComponent { basic, .. })
+            | Self::Nbt(ChatNbtComponent { basic, .. })
+            | Self::Normal(basic) => Some(basic),
+        }
+    }
+}
+
+impl From<&str> for ChatComponent {
+    fn from(value: &str) -> Self {
+        Self::text(value)
+    }
+}
+
+impl From<String> for ChatComponent {
+    fn from(value: String) -> Self {
+        Self::text(value)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub struct ChatStringComponent {
     pub text: String,
@@ -23,14 +84,96 @@ pub struct ChatStringComponent {
     pub basic: BasicChatComponent,
 }
 
+/// `{"translate": "death.attack.arrow", "with": [...]}`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct ChatTranslatableComponent {
+    pub translate: String,
+    /// Shown instead of `translate` if the client doesn't know the translation key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub with: Vec<ChatComponent>,
+    #[serde(flatten)]
+    pub basic: BasicChatComponent,
+}
+
+/// `{"keybind": "key.jump"}`, shown as whatever key the player bound to that action.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct ChatKeybindComponent {
+    pub keybind: String,
+    #[serde(flatten)]
+    pub basic: BasicChatComponent,
+}
+
+/// `{"score": {"name": "@p", "objective": "kills"}}`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct ChatScoreComponent {
+    pub score: ChatScore,
+    #[serde(flatten)]
+    pub basic: BasicChatComponent,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct ChatScore {
+    /// A player name or an entity selector, whose score should be shown.
+    pub name: String,
+    pub objective: String,
+    /// The resolved value, if the server already resolved it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+/// `{"selector": "@a[distance=..5]"}`, shown as the names of the selected entities.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct ChatSelectorComponent {
+    pub selector: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub separator: Option<Box<ChatComponent>>,
+    #[serde(flatten)]
+    pub basic: BasicChatComponent,
+}
+
+/// `{"nbt": "Items[0].id", "block": "~ ~-1 ~"}`, shown as the NBT value at the path.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatNbtComponent {
+    pub nbt: String,
+    /// Whether the value should be parsed as a text component instead of being shown as SNBT.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interpret: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub separator: Option<Box<ChatComponent>>,
+    #[serde(flatten)]
+    pub source: ChatNbtSource,
+    #[serde(flatten)]
+    pub basic: BasicChatComponent,
+}
+
+/// Where a [`ChatNbtComponent`] takes its NBT from.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatNbtSource {
+    /// Coordinates of a block entity, like `~ ~-1 ~`.
+    Block(String),
+    /// An entity selector.
+    Entity(String),
+    /// A command storage identifier.
+    Storage(String),
+}
+
+/// The formatting of a component. Unset (`None`) values are inherited from the parent component.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct BasicChatComponent {
-    pub bold: bool,
-    pub italic: bool,
-    pub underlined: bool,
-    pub strikethrough: bool,
-    pub obfuscated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bold: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub italic: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub underlined: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strikethrough: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub obfuscated: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub font: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -45,17 +188,64 @@ pub struct BasicChatComponent {
     pub extra: Vec<ChatComponent>,
 }
 
-#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(untagged)]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
 pub enum ChatColor {
     Named(ChatColorNamed),
     ShortCode(ChatShortCode),
     Web(ChatColorWeb),
     #[default]
-    #[serde(rename = "reset")]
     Reset,
 }
 
+impl fmt::Display for ChatColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Named(named) => f.write_str(named.name()),
+            Self::ShortCode(code) => write!(f, "{}", code.code()),
+            Self::Web(web) => write!(f, "{web}"),
+            Self::Reset => f.write_str("reset"),
+        }
+    }
+}
+
+impl FromStr for ChatColor {
+    type Err = InvalidChatColor;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reset" => Ok(Self::Reset),
+            "f" => Ok(Self::ShortCode(ChatShortCode::White)),
+            "a" => Ok(Self::ShortCode(ChatShortCode::Green)),
+            "0" => Ok(Self::ShortCode(ChatShortCode::Black)),
+            web if web.starts_with('#') => web.parse().map(Self::Web),
+            named => ChatColorNamed::from_name(named)
+                .map(Self::Named)
+                .ok_or_else(|| InvalidChatColor(named.to_owned())),
+        }
+    }
+}
+
+impl Serialize for ChatColor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ChatColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let color = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        color.parse().map_err(de::Error::custom)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error, miette::Diagnostic)]
+#[error("invalid color: {_0:?}")]
+#[diagnostic(
+    code(protocol::chat::invalid_color),
+    help("a color is either a named color (like `dark_aqua`), `reset`, or a hex color (like `#FF8800`)")
+)]
+pub struct InvalidChatColor(pub String);
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ChatShortCode {
     #[serde(rename = "f")]
@@ -66,15 +256,25 @@ pub enum ChatShortCode {
     Black,
 }
 
+impl ChatShortCode {
+    pub const fn code(self) -> char {
+        match self {
+            Self::White => 'f',
+            Self::Green => 'a',
+            Self::Black => '0',
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ChatColorNamed {
     Black,
     DarkBlue,
-    DarkGren,
-    DarkCyan,
+    DarkGreen,
+    DarkAqua,
     DarkRed,
-    Purple,
+    DarkPurple,
     Gold,
     Gray,
     DarkGray,
@@ -87,11 +287,139 @@ pub enum ChatColorNamed {
     White,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+impl ChatColorNamed {
+    /// All named colors, in the order of their legacy codes (`0` ..= `f`).
+    pub const ALL: [Self; 16] = [
+        Self::Black,
+        Self::DarkBlue,
+        Self::DarkGreen,
+        Self::DarkAqua,
+        Self::DarkRed,
+        Self::DarkPurple,
+        Self::Gold,
+        Self::Gray,
+        Self::DarkGray,
+        Self::Blue,
+        Self::Green,
+        Self::Aqua,
+        Self::Red,
+        Self::LightPurple,
+        Self::Yellow,
+        Self::White,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Black => "black",
+            Self::DarkBlue => "dark_blue",
+            Self::DarkGreen => "dark_green",
+            Self::DarkAqua => "dark_aqua",
+            Self::DarkRed => "dark_red",
+            Self::DarkPurple => "dark_purple",
+            Self::Gold => "gold",
+            Self::Gray => "gray",
+            Self::DarkGray => "dark_gray",
+            Self::Blue => "blue",
+            Self::Green => "green",
+            Self::Aqua => "aqua",
+            Self::Red => "red",
+            Self::LightPurple => "light_purple",
+            Self::Yellow => "yellow",
+            Self::White => "white",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|color| color.name() == name)
+    }
+
+    /// The character after `§` that selects this color in legacy formatting.
+    pub fn legacy_code(self) -> char {
+        std::char::from_digit(self as u32, 16).expect("there are only 16 named colors")
+    }
+
+    pub fn from_legacy_code(code: char) -> Option<Self> {
+        code.to_digit(16).map(|index| Self::ALL[index as usize])
+    }
+
+    /// The color the client renders this as, in `0xRRGGBB`.
+    pub const fn rgb(self) -> u32 {
+        match self {
+            Self::Black => 0x000000,
+            Self::DarkBlue => 0x0000AA,
+            Self::DarkGreen => 0x00AA00,
+            Self::DarkAqua => 0x00AAAA,
+            Self::DarkRed => 0xAA0000,
+            Self::DarkPurple => 0xAA00AA,
+            Self::Gold => 0xFFAA00,
+            Self::Gray => 0xAAAAAA,
+            Self::DarkGray => 0x555555,
+            Self::Blue => 0x5555FF,
+            Self::Green => 0x55FF55,
+            Self::Aqua => 0x55FFFF,
+            Self::Red => 0xFF5555,
+            Self::LightPurple => 0xFF55FF,
+            Self::Yellow => 0xFFFF55,
+            Self::White => 0xFFFFFF,
+        }
+    }
+}
+
+/// A color as `#RRGGBB` in JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ChatColorWeb {
     Hex(u32),
 }
 
+impl ChatColorWeb {
+    /// The named color that looks the most like this one, for clients and outputs that only support named colors.
+    pub fn nearest_named(self) -> ChatColorNamed {
+        let Self::Hex(hex) = self;
+        let channels = |rgb: u32| [(rgb >> 16) & 0xff, (rgb >> 8) & 0xff, rgb & 0xff];
+        let [r, g, b] = channels(hex);
+
+        ChatColorNamed::ALL
+            .into_iter()
+            .min_by_key(|named| {
+                let [nr, ng, nb] = channels(named.rgb());
+                r.abs_diff(nr).pow(2) + g.abs_diff(ng).pow(2) + b.abs_diff(nb).pow(2)
+            })
+            .expect("there are named colors")
+    }
+}
+
+impl fmt::Display for ChatColorWeb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self::Hex(hex) = self;
+        write!(f, "#{:06X}", hex & 0xFFFFFF)
+    }
+}
+
+impl FromStr for ChatColorWeb {
+    type Err = InvalidChatColor;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.strip_prefix('#')
+            .filter(|hex| hex.len() == 6)
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .map(Self::Hex)
+            .ok_or_else(|| InvalidChatColor(s.to_owned()))
+    }
+}
+
+impl Serialize for ChatColorWeb {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ChatColorWeb {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let color = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        color.parse().map_err(de::Error::custom)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(tag = "action", content = "value", rename_all = "snake_case")]
 pub enum ChatClickEvent {
@@ -103,10 +431,42 @@ pub enum ChatClickEvent {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(tag = "action", content = "value", rename_all = "snake_case")]
+#[serde(tag = "action", content = "contents", rename_all = "snake_case")]
 pub enum ChatHoverEvent {
     ShowText(ChatComponent),
-    ShowItem(ItemData),
+    ShowItem(ChatHoverItem),
+    ShowEntity(ChatHoverEntity),
+}
+
+/// Usually made with [`ItemStack::hover_event`](super::item::ItemStack::hover_event).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatHoverItem {
+    /// The item, like `minecraft:stone`.
+    pub id: String,
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    pub count: i32,
+    /// The NBT of the item stack, in SNBT.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+fn one() -> i32 {
+    1
+}
+
+fn is_one(count: &i32) -> bool {
+    *count == 1
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatHoverEntity {
+    /// The entity type, like `minecraft:player`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The entity's UUID, in the hyphenated form.
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<ChatComponent>,
 }
 
 #[macro_export]
@@ -128,3 +488,133 @@ macro_rules! chat {
         $crate::model::chat::ChatComponent::Multi(vec![$(chat!($what),)*])
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    /// Checks that `value` deserializes, and serializes back into the same JSON.
+    fn roundtrip(value: Value) -> ChatComponent {
+        let component: ChatComponent = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(
+            serde_json::to_value(&component).unwrap(),
+            value,
+            "{component:#?}"
+        );
+        component
+    }
+
+    #[test]
+    fn translatable() {
+        let joined = roundtrip(json!({
+            "color": "yellow",
+            "translate": "multiplayer.player.joined",
+            "with": [{
+                "text": "Steve",
+                "insertion": "Steve",
+                "clickEvent": { "action": "suggest_command", "value": "/tell Steve " },
+                "hoverEvent": {
+                    "action": "show_entity",
+                    "contents": {
+                        "type": "minecraft:player",
+                        "id": "8667ba71-b85a-4004-af54-457a9734eed7",
+                        "name": { "text": "Steve" }
+                    }
+                }
+            }]
+        }));
+        let ChatComponent::Translatable(joined) = joined else {
+            panic!("expected a translatable component, got {joined:?}")
+        };
+        assert_eq!(joined.translate, "multiplayer.player.joined");
+        assert_eq!(
+            joined.basic.color,
+            Some(ChatColor::Named(ChatColorNamed::Yellow))
+        );
+
+        roundtrip(json!({
+            "translate": "death.attack.arrow",
+            "with": [
+                { "text": "Steve" },
+                { "translate": "entity.minecraft.skeleton" }
+            ]
+        }));
+        roundtrip(json!({ "translate": "block.minecraft.stone", "fallback": "Stone" }));
+    }
+
+    #[test]
+    fn other_contents() {
+        assert!(matches!(
+            roundtrip(json!({ "keybind": "key.jump" })),
+            ChatComponent::Keybind(_)
+        ));
+        assert!(matches!(
+            roundtrip(json!({ "score": { "name": "@p", "objective": "kills" }, "bold": true })),
+            ChatComponent::Score(_)
+        ));
+        assert!(matches!(
+            roundtrip(json!({
+                "selector": "@a[distance=..5]",
+                "separator": { "text": ", ", "color": "gray" }
+            })),
+            ChatComponent::Selector(_)
+        ));
+
+        let block =
+            roundtrip(json!({ "nbt": "Items[0].id", "block": "~ ~-1 ~", "interpret": false }));
+        let ChatComponent::Nbt(block) = block else {
+            panic!("expected an nbt component, got {block:?}")
+        };
+        assert_eq!(block.source, ChatNbtSource::Block("~ ~-1 ~".to_string()));
+
+        roundtrip(json!({ "nbt": "foo", "storage": "minecraft:bar", "separator": "; " }));
+        roundtrip(json!({ "nbt": "CustomName", "entity": "@s", "interpret": true }));
+    }
+
+    #[test]
+    fn plain_and_multi() {
+        assert_eq!(
+            roundtrip(json!("hello")),
+            ChatComponent::Plain("hello".to_string())
+        );
+        roundtrip(json!(["", { "text": "a", "italic": false }, "b"]));
+        roundtrip(
+            json!({ "text": "x", "hoverEvent": { "action": "show_text", "contents": "tip" } }),
+        );
+        roundtrip(json!({ "text": "", "extra": [{ "text": "a", "strikethrough": true }, "b"] }));
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(
+            serde_json::to_value(ChatColor::Web(ChatColorWeb::Hex(0x00aaff))).unwrap(),
+            json!("#00AAFF")
+        );
+        assert_eq!(
+            serde_json::to_value(ChatColor::Reset).unwrap(),
+            json!("reset")
+        );
+        assert_eq!(
+            serde_json::from_value::<ChatColor>(json!("#ff8800")).unwrap(),
+            ChatColor::Web(ChatColorWeb::Hex(0xff8800))
+        );
+        assert_eq!(
+            serde_json::from_value::<ChatColor>(json!("dark_aqua")).unwrap(),
+            ChatColor::Named(ChatColorNamed::DarkAqua)
+        );
+        assert!(serde_json::from_value::<ChatColor>(json!("#ff88")).is_err());
+
+        roundtrip(json!({ "text": "hi", "color": "#FF8800", "bold": true }));
+
+        assert_eq!(ChatColorNamed::Gold.legacy_code(), '6');
+        assert_eq!(
+            ChatColorNamed::from_legacy_code('b'),
+            Some(ChatColorNamed::Aqua)
+        );
+        assert_eq!(
+            ChatColorWeb::Hex(0xfa0000).nearest_named(),
+            ChatColorNamed::DarkRed
+        );
+    }
+}
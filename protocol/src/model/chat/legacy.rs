@@ -0,0 +1,404 @@
+//! Legacy `§` formatting codes, as used by old clients, the legacy server list ping and plain-text logs.
+
+use super::*;
+
+/// The character that starts a formatting code, like `§a`.
+pub const SECTION_SIGN: char = '§';
+
+/// The formatting a piece of text ends up with, after inheriting everything that wasn't set from its parents.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResolvedStyle {
+    /// `None` if the color was never set, or was reset.
+    pub color: Option<ChatColorNamed>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underlined: bool,
+    pub strikethrough: bool,
+    pub obfuscated: bool,
+}
+
+impl ResolvedStyle {
+    fn inherit(&self, basic: &BasicChatComponent) -> Self {
+        Self {
+            color: basic.color.map_or(self.color, ChatColor::named),
+            bold: basic.bold.unwrap_or(self.bold),
+            italic: basic.italic.unwrap_or(self.italic),
+            underlined: basic.underlined.unwrap_or(self.underlined),
+            strikethrough: basic.strikethrough.unwrap_or(self.strikethrough),
+            obfuscated: basic.obfuscated.unwrap_or(self.obfuscated),
+        }
+    }
+
+    /// The codes of the enabled formats, in the order vanilla writes them.
+    fn formats(&self) -> impl Iterator<Item = char> {
+        [
+            (self.obfuscated, 'k'),
+            (self.bold, 'l'),
+            (self.strikethrough, 'm'),
+            (self.underlined, 'n'),
+            (self.italic, 'o'),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, code)| enabled.then_some(code))
+    }
+}
+
+impl ChatColor {
+    /// The closest named color, or `None` for [`Reset`](Self::Reset).
+    pub fn named(self) -> Option<ChatColorNamed> {
+        match self {
+            Self::Named(named) => Some(named),
+            Self::ShortCode(ChatShortCode::White) => Some(ChatColorNamed::White),
+            Self::ShortCode(ChatShortCode::Green) => Some(ChatColorNamed::Green),
+            Self::ShortCode(ChatShortCode::Black) => Some(ChatColorNamed::Black),
+            Self::Web(web) => Some(web.nearest_named()),
+            Self::Reset => None,
+        }
+    }
+}
+
+impl ChatComponent {
+    /// Parses text with `§` formatting codes, like `§a§lHello`.
+    pub fn from_legacy(text: &str) -> Self {
+        Self::from_legacy_with(text, SECTION_SIGN)
+    }
+
+    /// Parses text with formatting codes that start with `prefix` instead of `§`, like `&a&lHello`.
+    ///
+    /// As in vanilla, a color code resets all formats set before it.
+    /// Bukkit-style hex colors (`§x§f§f§8§8§0§0`) are supported,
+    /// and unknown codes are kept as they are.
+    pub fn from_legacy_with(text: &str, prefix: char) -> Self {
+        let mut parts = vec![];
+        let mut style = BasicChatComponent::default();
+        let mut buf = String::new();
+        let mut chars = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            let code = match chars.peek() {
+                Some(code) if c == prefix => code.to_ascii_lowercase(),
+                _ => {
+                    buf.push(c);
+                    continue;
+                }
+            };
+
+            let new_style = match code {
+                'x' => legacy_hex(chars.clone().skip(1), prefix).map(|color| {
+                    // skips `x` and the 11 characters after it, the last one is skipped below
+                    chars.nth(11);
+                    BasicChatComponent {
+                        color: Some(ChatColor::Web(color)),
+                        ..Default::default()
+                    }
+                }),
+                'r' => Some(BasicChatComponent::default()),
+                'k' | 'l' | 'm' | 'n' | 'o' => {
+                    let mut style = style.clone();
+                    *match code {
+                        'k' => &mut style.obfuscated,
+                        'l' => &mut style.bold,
+                        'm' => &mut style.strikethrough,
+                        'n' => &mut style.underlined,
+                        _ => &mut style.italic,
+                    } = Some(true);
+                    Some(style)
+                }
+                code => ChatColorNamed::from_legacy_code(code).map(|color| BasicChatComponent {
+                    color: Some(ChatColor::Named(color)),
+                    ..Default::default()
+                }),
+            };
+
+            // unknown codes are left in the text
+            let Some(new_style) = new_style else {
+                buf.push(c);
+                continue;
+            };
+            chars.next();
+
+            if !buf.is_empty() {
+                parts.push(Self::String(ChatStringComponent {
+                    text: std::mem::take(&mut buf),
+                    basic: style,
+                }));
+            }
+            style = new_style;
+        }
+
+        if !buf.is_empty() {
+            parts.push(Self::String(ChatStringComponent {
+                text: buf,
+                basic: style,
+            }));
+        }
+
+        match parts.len() {
+            0 => Self::text(""),
+            1 => parts.pop().unwrap(),
+            _ => Self::String(ChatStringComponent {
+                text: String::new(),
+                basic: BasicChatComponent {
+                    extra: parts,
+                    ..Default::default()
+                },
+            }),
+        }
+    }
+
+    /// The text of this component with `§` formatting codes, for clients and outputs that don't support JSON text.
+    ///
+    /// Hex colors are replaced with the closest named color, and click and hover events are lost.
+    pub fn to_legacy(&self) -> String {
+        let mut current = ResolvedStyle::default();
+        let mut out = String::new();
+
+        for (style, text) in self.runs() {
+            if style != current {
+                let removes_formats = current
+                    .formats()
+                    .any(|code| !style.formats().any(|c| c == code));
+
+                if style.color != current.color || removes_formats {
+                    // a color code resets the formats, so they have to be written again
+                    out.push(SECTION_SIGN);
+                    out.push(style.color.map_or('r', ChatColorNamed::legacy_code));
+                    for code in style.formats() {
+                        out.push(SECTION_SIGN);
+                        out.push(code);
+                    }
+                } else {
+                    for code in style
+                        .formats()
+                        .filter(|code| !current.formats().any(|c| c == *code))
+                    {
+                        out.push(SECTION_SIGN);
+                        out.push(code);
+                    }
+                }
+
+                current = style;
+            }
+
+            out.push_str(&text);
+        }
+
+        out
+    }
+
+    /// The text of this component without any formatting.
+    ///
+    /// Translations aren't known to the server, so they're shown as their `fallback` if there is one,
+    /// and as the translation key followed by its arguments otherwise.
+    pub fn to_plain(&self) -> String {
+        self.runs().into_iter().map(|(_, text)| text).collect()
+    }
+
+    /// Pieces of text with their resolved formatting, in the order they're shown.
+    fn runs(&self) -> Vec<(ResolvedStyle, String)> {
+        let mut runs = vec![];
+        self.collect_runs(&ResolvedStyle::default(), &mut runs);
+        runs.retain(|(_, text)| !text.is_empty());
+        runs
+    }
+
+    fn collect_runs(&self, parent: &ResolvedStyle, runs: &mut Vec<(ResolvedStyle, String)>) {
+        let style = self
+            .basic()
+            .map_or_else(|| parent.clone(), |basic| parent.inherit(basic));
+
+        let push = |runs: &mut Vec<(ResolvedStyle, String)>, text: &str| match runs.last_mut() {
+            Some((last, last_text)) if *last == style => last_text.push_str(text),
+            _ => runs.push((style.clone(), text.to_owned())),
+        };
+
+        match self {
+            Self::Plain(text) | Self::String(ChatStringComponent { text, .. }) => push(runs, text),
+            Self::Translatable(ChatTranslatableComponent {
+                fallback: Some(fallback),
+                with,
+                ..
+            }) => {
+                let mut args = with.iter();
+                let mut rest = fallback.as_str();
+
+                while let Some(at) = rest.find('%') {
+                    push(runs, &rest[..at]);
+                    let spec = &rest[at + 1..];
+
+                    if let Some(after) = spec.strip_prefix('%') {
+                        push(runs, "%");
+                        rest = after;
+                    } else if let Some(after) = spec.strip_prefix('s') {
+                        if let Some(arg) = args.next() {
+                            arg.collect_runs(&style, runs);
+                        }
+                        rest = after;
+                    } else {
+                        // positional, like `%1$s`
+                        let digits = spec
+                            .find(|c: char| !c.is_ascii_digit())
+                            .unwrap_or(spec.len());
+
+                        match spec[digits..].strip_prefix("$s") {
+                            Some(after) if digits > 0 => {
+                                if let Some(arg) = spec[..digits]
+                                    .parse::<usize>()
+                                    .ok()
+                                    .and_then(|index| index.checked_sub(1))
+                                    .and_then(|index| with.get(index))
+                                {
+                                    arg.collect_runs(&style, runs);
+                                }
+                                rest = after;
+                            }
+                            _ => {
+                                push(runs, "%");
+                                rest = spec;
+                            }
+                        }
+                    }
+                }
+
+                push(runs, rest);
+            }
+            Self::Translatable(ChatTranslatableComponent {
+                translate, with, ..
+            }) => {
+                push(runs, translate);
+
+                if !with.is_empty() {
+                    push(runs, "[");
+                    for (index, arg) in with.iter().enumerate() {
+                        if index != 0 {
+                            push(runs, ", ");
+                        }
+                        arg.collect_runs(&style, runs);
+                    }
+                    push(runs, "]");
+                }
+            }
+            Self::Keybind(ChatKeybindComponent { keybind, .. }) => push(runs, keybind),
+            Self::Score(ChatScoreComponent { score, .. }) => {
+                push(runs, score.value.as_deref().unwrap_or(&score.name))
+            }
+            Self::Selector(ChatSelectorComponent { selector, .. }) => push(runs, selector),
+            Self::Nbt(ChatNbtComponent { nbt, .. }) => push(runs, nbt),
+            // the first component of an array is the parent of the rest
+            Self::Multi(components) => {
+                if let Some((first, rest)) = components.split_first() {
+                    first.collect_runs(parent, runs);
+
+                    let style = first
+                        .basic()
+                        .map_or_else(|| parent.clone(), |basic| parent.inherit(basic));
+                    for component in rest {
+                        component.collect_runs(&style, runs);
+                    }
+                }
+            }
+            Self::Normal(_) => {}
+        }
+
+        if let Some(basic) = self.basic() {
+            for extra in &basic.extra {
+                extra.collect_runs(&style, runs);
+            }
+        }
+    }
+}
+
+/// Parses the `§f§f§8§8§0§0` after a `§x`.
+fn legacy_hex(chars: impl Iterator<Item = char>, prefix: char) -> Option<ChatColorWeb> {
+    let mut hex = String::with_capacity(6);
+    let mut chars = chars.take(12);
+
+    while let Some(c) = chars.next() {
+        if c != prefix {
+            return None;
+        }
+        hex.push(chars.next().filter(char::is_ascii_hexdigit)?);
+    }
+
+    if hex.len() != 6 {
+        return None;
+    }
+
+    u32::from_str_radix(&hex, 16).ok().map(ChatColorWeb::Hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse() {
+        let component = ChatComponent::from_legacy("§a§lHello §rworld§x§f§f§8§8§0§0!§q");
+        assert_eq!(
+            serde_json::to_value(&component).unwrap(),
+            json!({
+                "text": "",
+                "extra": [
+                    { "text": "Hello ", "color": "green", "bold": true },
+                    { "text": "world" },
+                    { "text": "!§q", "color": "#FF8800" },
+                ]
+            })
+        );
+
+        assert_eq!(
+            ChatComponent::from_legacy_with("&6gold", '&'),
+            ChatComponent::String(ChatStringComponent {
+                text: "gold".into(),
+                basic: BasicChatComponent {
+                    color: Some(ChatColor::Named(ChatColorNamed::Gold)),
+                    ..Default::default()
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        for legacy in [
+            "§a§lHello §rworld",
+            "plain",
+            "§6§lbold§6 not bold",
+            "§c§oa§nb§c§nc",
+        ] {
+            assert_eq!(ChatComponent::from_legacy(legacy).to_legacy(), legacy);
+        }
+    }
+
+    #[test]
+    fn plain() {
+        let joined = ChatComponent::Translatable(ChatTranslatableComponent {
+            translate: "multiplayer.player.joined".into(),
+            fallback: Some("%s joined the game (%%)".into()),
+            with: vec![ChatComponent::text("Steve")],
+            basic: BasicChatComponent::default(),
+        });
+        assert_eq!(joined.to_plain(), "Steve joined the game (%)");
+
+        let death = ChatComponent::translatable(
+            "death.attack.arrow",
+            vec![
+                ChatComponent::text("Steve"),
+                ChatComponent::text("Skeleton"),
+            ],
+        );
+        assert_eq!(death.to_plain(), "death.attack.arrow[Steve, Skeleton]");
+
+        let positional = ChatComponent::Translatable(ChatTranslatableComponent {
+            translate: "test.positional".into(),
+            fallback: Some("%2$s, %1$s".into()),
+            with: vec![
+                ChatComponent::text("Steve"),
+                ChatComponent::text("Skeleton"),
+            ],
+            basic: BasicChatComponent::default(),
+        });
+        assert_eq!(positional.to_plain(), "Skeleton, Steve");
+    }
+}
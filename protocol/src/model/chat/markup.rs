@@ -0,0 +1,534 @@
+//! A tag-based markup for text components, modeled after [MiniMessage](https://docs.advntr.dev/minimessage/format.html).
+//!
+//! ```text
+//! <gold><bold>Hello</bold> <hover:show_text:'<red>hi'>world</hover>
+//! ```
+//!
+//! Supported tags:
+//! - colors: `<gold>`, `<#ff8800>`, `<color:gold>` (also `colour` and `c`)
+//! - formats: `<bold>`/`<b>`, `<italic>`/`<i>`/`<em>`, `<underlined>`/`<u>`, `<strikethrough>`/`<st>`,
+//!   `<obfuscated>`/`<obf>`; `<!bold>` or `<bold:false>` turn a format off
+//! - `<hover:show_text:'text'>`, where the text is markup itself
+//! - `<click:run_command:'/spawn'>`, as well as `suggest_command`, `open_url`, `copy_to_clipboard` and `change_page`
+//! - `<insert:'text'>` and `<font:minecraft:uniform>`
+//! - `<key:key.jump>` and `<lang:death.attack.arrow:'Steve':'Skeleton'>` (also `tr` and `translate`)
+//! - `<newline>`/`<br>`, and `<reset>`, which closes every open tag
+//!
+//! `</name>` closes the last open tag with that name (and every tag opened after it), `</>` closes the last open tag,
+//! and tags that are still open at the end are closed automatically.
+//! Arguments containing `:` or `>` have to be quoted with `'` or `"`,
+//! and a `<` that isn't a tag has to be escaped as `\<`.
+
+use std::ops::Range;
+
+use miette::SourceSpan;
+
+use super::*;
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum MarkupError {
+    #[error("unterminated tag")]
+    #[diagnostic(
+        code(protocol::chat::markup::unterminated_tag),
+        help("end the tag with a `>`, or escape the `<` as `\\<` if it's meant to be text")
+    )]
+    UnterminatedTag {
+        #[source_code]
+        src: String,
+        #[label = "this tag never ends"]
+        at: SourceSpan,
+    },
+    #[error("unknown tag: {name}")]
+    #[diagnostic(
+        code(protocol::chat::markup::unknown_tag),
+        help("escape the `<` as `\\<` if it's meant to be text")
+    )]
+    UnknownTag {
+        name: String,
+        #[source_code]
+        src: String,
+        #[label = "here"]
+        at: SourceSpan,
+    },
+    #[error("there is no open tag named {name:?} to close")]
+    #[diagnostic(code(protocol::chat::markup::unmatched_close))]
+    UnmatchedClose {
+        name: String,
+        #[source_code]
+        src: String,
+        #[label = "here"]
+        at: SourceSpan,
+    },
+    #[error("invalid arguments for tag {tag:?}: {reason}")]
+    #[diagnostic(code(protocol::chat::markup::invalid_arguments))]
+    InvalidArguments {
+        tag: String,
+        reason: String,
+        #[source_code]
+        src: String,
+        #[label = "here"]
+        at: SourceSpan,
+    },
+}
+
+impl ChatComponent {
+    /// Parses the [markup](crate::model::chat::markup) format, like `<gold><bold>Hello</bold> world`.
+    pub fn from_markup(input: &str) -> Result<Self, MarkupError> {
+        parse(input)
+    }
+}
+
+/// Parses the [markup](self) format into a text component.
+pub fn parse(input: &str) -> Result<ChatComponent, MarkupError> {
+    let mut parser = Parser {
+        input,
+        stack: vec![Frame::root()],
+        text: String::new(),
+    };
+    let mut pos = 0;
+
+    while let Some(c) = input[pos..].chars().next() {
+        pos += c.len_utf8();
+
+        match c {
+            '\\' => match input[pos..].chars().next() {
+                Some(escaped @ ('<' | '\\')) => {
+                    parser.text.push(escaped);
+                    pos += 1;
+                }
+                _ => parser.text.push('\\'),
+            },
+            '<' => {
+                let start = pos - 1;
+                let end = tag_end(input, pos).ok_or_else(|| MarkupError::UnterminatedTag {
+                    src: input.to_owned(),
+                    at: (start..input.len()).into(),
+                })?;
+
+                parser.tag(&input[pos..end], start..end + 1)?;
+                pos = end + 1;
+            }
+            c => parser.text.push(c),
+        }
+    }
+
+    parser.flush();
+    while parser.stack.len() > 1 {
+        parser.pop();
+    }
+
+    Ok(parser.stack.pop().unwrap().into_component())
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    /// The root frame is at the bottom, and is never popped until the end.
+    stack: Vec<Frame>,
+    text: String,
+}
+
+struct Frame {
+    /// The name the tag was opened with, like `gold` or `b`.
+    name: String,
+    /// What the tag does, like `color` or `bold`. Closing tags can use either.
+    kind: &'static str,
+    style: BasicChatComponent,
+    children: Vec<ChatComponent>,
+}
+
+impl Frame {
+    fn root() -> Self {
+        Self {
+            name: String::new(),
+            kind: "",
+            style: BasicChatComponent::default(),
+            children: vec![],
+        }
+    }
+
+    fn into_component(self) -> ChatComponent {
+        let Self {
+            style,
+            mut children,
+            ..
+        } = self;
+
+        if children.len() == 1 {
+            match children.pop().unwrap() {
+                ChatComponent::String(ChatStringComponent { text, basic })
+                    if basic == BasicChatComponent::default() =>
+                {
+                    return ChatComponent::String(ChatStringComponent { text, basic: style })
+                }
+                child if style == BasicChatComponent::default() => return child,
+                child => children.push(child),
+            }
+        }
+
+        ChatComponent::String(ChatStringComponent {
+            text: String::new(),
+            basic: BasicChatComponent {
+                extra: children,
+                ..style
+            },
+        })
+    }
+}
+
+impl<'a> Parser<'a> {
+    fn tag(&mut self, content: &str, span: Range<usize>) -> Result<(), MarkupError> {
+        self.flush();
+
+        if let Some(name) = content.strip_prefix('/') {
+            return self.close(name, span);
+        }
+
+        let (negated, content) = match content.strip_prefix('!') {
+            Some(content) => (true, content),
+            None => (false, content),
+        };
+        let mut args = split_args(content);
+        let name = args.remove(0).trim().to_ascii_lowercase();
+        let mut style = BasicChatComponent::default();
+
+        let format = match canonical(&name) {
+            "bold" => Some(("bold", &mut style.bold)),
+            "italic" => Some(("italic", &mut style.italic)),
+            "underlined" => Some(("underlined", &mut style.underlined)),
+            "strikethrough" => Some(("strikethrough", &mut style.strikethrough)),
+            "obfuscated" => Some(("obfuscated", &mut style.obfuscated)),
+            _ => None,
+        };
+
+        let kind = if let Some((kind, format)) = format {
+            let enabled = match args.as_slice() {
+                [] => true,
+                [value] if value == "true" => true,
+                [value] if value == "false" => false,
+                _ => return Err(self.invalid(&name, "expected `true` or `false`", span)),
+            };
+            *format = Some(enabled != negated);
+            kind
+        } else if let (Some(color), []) = (named_color(&name), args.as_slice()) {
+            style.color = Some(color);
+            "color"
+        } else {
+            match (canonical(&name), args.as_slice()) {
+                ("color", [color]) => {
+                    style.color = Some(color.parse().map_err(|error: InvalidChatColor| {
+                        self.invalid(&name, error.to_string(), span.clone())
+                    })?);
+                    "color"
+                }
+                ("reset", []) => {
+                    while self.stack.len() > 1 {
+                        self.pop();
+                    }
+                    return Ok(());
+                }
+                ("newline", []) => {
+                    self.text.push('\n');
+                    return Ok(());
+                }
+                ("key", [keybind]) => {
+                    self.push(ChatComponent::Keybind(ChatKeybindComponent {
+                        keybind: keybind.clone(),
+                        basic: BasicChatComponent::default(),
+                    }));
+                    return Ok(());
+                }
+                ("lang", [key, with @ ..]) => {
+                    let with = with
+                        .iter()
+                        .map(|arg| parse(arg))
+                        .collect::<Result<_, _>>()
+                        .map_err(|error| self.invalid(&name, error.to_string(), span.clone()))?;
+                    self.push(ChatComponent::translatable(key.clone(), with));
+                    return Ok(());
+                }
+                ("hover", [action, text @ ..]) if action == "show_text" && !text.is_empty() => {
+                    let text = parse(&text.join(":"))
+                        .map_err(|error| self.invalid(&name, error.to_string(), span.clone()))?;
+                    style.hover_event = Some(Box::new(ChatHoverEvent::ShowText(text)));
+                    "hover"
+                }
+                ("hover", _) => {
+                    return Err(self.invalid(
+                        &name,
+                        "expected `show_text` and the text to show, like `<hover:show_text:'hi'>`",
+                        span,
+                    ))
+                }
+                ("click", [action, value @ ..]) if !value.is_empty() => {
+                    let value = value.join(":");
+                    style.click_event = Some(match action.as_str() {
+                        "open_url" => ChatClickEvent::OpenUrl(value),
+                        "run_command" => ChatClickEvent::RunCommand(value),
+                        "suggest_command" => ChatClickEvent::SuggestCommand(value),
+                        "copy_to_clipboard" => ChatClickEvent::CopyToClipboard(value),
+                        "change_page" => {
+                            ChatClickEvent::ChangePage(value.parse().map_err(|_| {
+                                self.invalid(&name, "the page has to be a number", span.clone())
+                            })?)
+                        }
+                        _ => {
+                            return Err(self.invalid(
+                                &name,
+                                format!("unknown click action: {action:?}"),
+                                span,
+                            ))
+                        }
+                    });
+                    "click"
+                }
+                ("insert", [_, ..]) => {
+                    style.insertion = Some(args.join(":"));
+                    "insert"
+                }
+                ("font", [_, ..]) => {
+                    style.font = Some(args.join(":"));
+                    "font"
+                }
+                (
+                    "color" | "reset" | "newline" | "key" | "lang" | "click" | "insert" | "font",
+                    _,
+                ) => return Err(self.invalid(&name, "wrong number of arguments", span)),
+                _ => {
+                    return Err(MarkupError::UnknownTag {
+                        name: name.clone(),
+                        src: self.input.to_owned(),
+                        at: span.into(),
+                    })
+                }
+            }
+        };
+
+        self.stack.push(Frame {
+            name,
+            kind,
+            style,
+            children: vec![],
+        });
+
+        Ok(())
+    }
+
+    fn close(&mut self, name: &str, span: Range<usize>) -> Result<(), MarkupError> {
+        let name = name
+            .split(':')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+
+        let index = self
+            .stack
+            .iter()
+            .enumerate()
+            .skip(1)
+            .rev()
+            .find(|(_, frame)| {
+                name.is_empty() || frame.name == name || frame.kind == canonical(&name)
+            })
+            .map(|(index, _)| index)
+            .ok_or_else(|| MarkupError::UnmatchedClose {
+                name,
+                src: self.input.to_owned(),
+                at: span.into(),
+            })?;
+
+        while self.stack.len() > index {
+            self.pop();
+        }
+
+        Ok(())
+    }
+
+    fn invalid(&self, tag: &str, reason: impl Into<String>, span: Range<usize>) -> MarkupError {
+        MarkupError::InvalidArguments {
+            tag: tag.to_owned(),
+            reason: reason.into(),
+            src: self.input.to_owned(),
+            at: span.into(),
+        }
+    }
+
+    fn push(&mut self, component: ChatComponent) {
+        self.stack.last_mut().unwrap().children.push(component);
+    }
+
+    fn pop(&mut self) {
+        let frame = self.stack.pop().unwrap();
+        self.push(frame.into_component());
+    }
+
+    fn flush(&mut self) {
+        if !self.text.is_empty() {
+            let text = std::mem::take(&mut self.text);
+            self.push(ChatComponent::text(text));
+        }
+    }
+}
+
+/// Resolves tag aliases, like `b` to `bold`.
+fn canonical(name: &str) -> &str {
+    match name {
+        "b" => "bold",
+        "i" | "em" => "italic",
+        "u" => "underlined",
+        "st" => "strikethrough",
+        "obf" => "obfuscated",
+        "colour" | "c" => "color",
+        "br" => "newline",
+        "tr" | "translate" => "lang",
+        "insertion" => "insert",
+        name => name,
+    }
+}
+
+/// `gold` or `#ff8800`, used as a tag on its own.
+fn named_color(name: &str) -> Option<ChatColor> {
+    if name.starts_with('#') {
+        name.parse().ok().map(ChatColor::Web)
+    } else {
+        ChatColorNamed::from_name(name).map(ChatColor::Named)
+    }
+}
+
+/// Finds the `>` that ends the tag starting at `start`, skipping over quoted arguments.
+fn tag_end(input: &str, start: usize) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (index, c) in input[start..].char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some(start + index),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Splits `hover:show_text:'a:b'` into `hover`, `show_text` and `a:b`.
+fn split_args(content: &str) -> Vec<String> {
+    let mut args = vec![String::new()];
+    let mut quote = None;
+    let mut chars = content.chars();
+
+    while let Some(c) = chars.next() {
+        let arg = args.last_mut().unwrap();
+
+        match (quote, c) {
+            (Some(_), '\\') => arg.extend(chars.next()),
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, ':') => args.push(String::new()),
+            (_, c) => arg.push(c),
+        }
+    }
+
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn markup(input: &str) -> serde_json::Value {
+        serde_json::to_value(parse(input).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(
+            markup("<gold><bold>Hello</bold> <hover:show_text:'<red>hi'>world</hover>"),
+            json!({
+                "text": "",
+                "color": "gold",
+                "extra": [
+                    { "text": "Hello", "bold": true },
+                    { "text": " " },
+                    {
+                        "text": "world",
+                        "hoverEvent": {
+                            "action": "show_text",
+                            "contents": { "text": "hi", "color": "red" }
+                        }
+                    }
+                ]
+            })
+        );
+
+        assert_eq!(
+            markup("<#FF8800><!i>a</color>b<click:run_command:'/tp 0 0 0'><u>c"),
+            json!({
+                "text": "",
+                "extra": [
+                    { "text": "", "color": "#FF8800", "extra": [{ "text": "a", "italic": false }] },
+                    { "text": "b" },
+                    {
+                        "text": "",
+                        "clickEvent": { "action": "run_command", "value": "/tp 0 0 0" },
+                        "extra": [{ "text": "c", "underlined": true }]
+                    }
+                ]
+            })
+        );
+
+        assert_eq!(
+            markup(r"\<red> <key:key.jump><br><lang:death.attack.arrow:Steve:'<red>Skeleton'>"),
+            json!({
+                "text": "",
+                "extra": [
+                    { "text": "<red> " },
+                    { "keybind": "key.jump" },
+                    { "text": "\n" },
+                    {
+                        "translate": "death.attack.arrow",
+                        "with": [{ "text": "Steve" }, { "text": "Skeleton", "color": "red" }]
+                    }
+                ]
+            })
+        );
+
+        assert_eq!(markup("plain"), json!({ "text": "plain" }));
+        assert_eq!(
+            markup("<b>a<reset>b"),
+            json!({ "text": "", "extra": [{ "text": "a", "bold": true }, { "text": "b" }] })
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            parse("<red"),
+            Err(MarkupError::UnterminatedTag { .. })
+        ));
+        assert!(matches!(
+            parse("a <b"),
+            Err(MarkupError::UnterminatedTag { .. })
+        ));
+        assert!(matches!(
+            parse("<bogus>"),
+            Err(MarkupError::UnknownTag { name, .. }) if name == "bogus"
+        ));
+        assert!(matches!(
+            parse("<red>a</bold>"),
+            Err(MarkupError::UnmatchedClose { .. })
+        ));
+        assert!(matches!(
+            parse("<click:explode:now>"),
+            Err(MarkupError::InvalidArguments { .. })
+        ));
+        assert!(matches!(
+            parse("<hover:show_text:'<nope>'>"),
+            Err(MarkupError::InvalidArguments { .. })
+        ));
+    }
+}
@@ -0,0 +1,276 @@
+//! How chunks are sent to clients, see <https://wiki.vg/Chunk_Format>.
+//!
+//! Chunks are split into sections of 16×16×16 blocks from the bottom of the world up.
+//! Each section has its block states and biomes in [`PalettedContainer`]s, which only use as many bits per entry as they need.
+
+use bytes::{BufMut, BytesMut};
+
+use crate::{error::Error, model::VarInt, nbt::Nbt, ser::Serialize};
+
+/// Blocks in a section, indexed by `y << 8 | z << 4 | x`.
+pub const SECTION_BLOCKS: usize = 16 * 16 * 16;
+/// Biomes in a section, in cells of 4×4×4 blocks indexed by `y << 4 | z << 2 | x`.
+pub const SECTION_BIOMES: usize = 4 * 4 * 4;
+/// Bytes in the light array of a section, half a byte per block.
+pub const LIGHT_BYTES: usize = SECTION_BLOCKS / 2;
+
+/// Bits per entry when block states are sent with their ids in the block state registry instead of a palette.
+pub const DIRECT_BLOCK_BITS: u8 = 15;
+
+/// Values of a section, either all the same, looked up in a palette, or direct registry ids.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PalettedContainer {
+    bits: u8,
+    /// Empty when the values are direct.
+    palette: Vec<i32>,
+    data: Vec<i64>,
+}
+
+impl PalettedContainer {
+    pub fn single(value: i32) -> Self {
+        Self {
+            bits: 0,
+            palette: vec![value],
+            data: vec![],
+        }
+    }
+
+    /// Block states by their ids, [`SECTION_BLOCKS`] of them.
+    pub fn blocks(states: &[u16]) -> Self {
+        Self::new(states, 4, 8, DIRECT_BLOCK_BITS)
+    }
+
+    /// Biomes by their index in the biome registry sent in [`LoginPlay`](super::packets::play::LoginPlay),
+    /// [`SECTION_BIOMES`] of them.
+    pub fn biomes(biomes: &[u16], registry_len: usize) -> Self {
+        Self::new(biomes, 1, 3, bits_for(registry_len).max(1))
+    }
+
+    /// Palettes take at least `min` bits per entry, and aren't used for more than `max`.
+    fn new(values: &[u16], min: u8, max: u8, direct: u8) -> Self {
+        let mut palette = Vec::<i32>::new();
+        for value in values {
+            if !palette.contains(&i32::from(*value)) {
+                palette.push(i32::from(*value));
+            }
+        }
+
+        match palette.len() {
+            0 => Self::single(0),
+            1 => Self::single(palette[0]),
+            len if bits_for(len) > max => Self {
+                bits: direct,
+                palette: vec![],
+                data: pack(values.iter().map(|value| u64::from(*value)), direct),
+            },
+            len => {
+                let bits = bits_for(len).max(min);
+                let data = pack(
+                    values.iter().map(|value| {
+                        palette
+                            .iter()
+                            .position(|entry| *entry == i32::from(*value))
+                            .unwrap_or_default() as u64
+                    }),
+                    bits,
+                );
+
+                Self {
+                    bits,
+                    palette,
+                    data,
+                }
+            }
+        }
+    }
+}
+
+impl Serialize for PalettedContainer {
+    fn serialize_to(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        try {
+            buf.put_u8(self.bits);
+            match (self.bits, &self.palette[..]) {
+                (0, [value]) => VarInt(*value).serialize_to(buf)?,
+                (_, []) => {}
+                (_, palette) => {
+                    VarInt(palette.len() as i32).serialize_to(buf)?;
+                    for entry in palette {
+                        VarInt(*entry).serialize_to(buf)?;
+                    }
+                }
+            }
+            VarInt(self.data.len() as i32).serialize_to(buf)?;
+            for long in &self.data {
+                buf.put_i64(*long);
+            }
+        }
+    }
+}
+
+/// How many bits it takes to tell `count` values apart.
+pub fn bits_for(count: usize) -> u8 {
+    (usize::BITS - count.saturating_sub(1).leading_zeros()) as u8
+}
+
+/// Packs the values into longs, as many as fit into each without spanning two.
+pub fn pack(values: impl ExactSizeIterator<Item = u64>, bits: u8) -> Vec<i64> {
+    let per_long = 64 / usize::from(bits);
+    let mut data = vec![0u64; values.len().div_ceil(per_long)];
+
+    for (index, value) in values.enumerate() {
+        let shift = (index % per_long) * usize::from(bits);
+        data[index / per_long] |= (value & ((1 << bits) - 1)) << shift;
+    }
+
+    data.into_iter().map(|long| long as i64).collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkSection {
+    /// How many blocks aren't air, the client uses it to skip empty sections.
+    pub block_count: i16,
+    pub blocks: PalettedContainer,
+    pub biomes: PalettedContainer,
+}
+
+impl Serialize for ChunkSection {
+    fn serialize_to(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        try {
+            self.block_count.serialize_to(buf)?;
+            self.blocks.serialize_to(buf)?;
+            self.biomes.serialize_to(buf)?;
+        }
+    }
+}
+
+/// A set of bits, sent as the longs it's made of.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BitSet(pub Vec<i64>);
+
+impl BitSet {
+    pub fn set(&mut self, index: usize) {
+        if self.0.len() <= index / 64 {
+            self.0.resize(index / 64 + 1, 0);
+        }
+        self.0[index / 64] |= 1 << (index % 64);
+    }
+
+    pub fn get(&self, index: usize) -> bool {
+        self.0
+            .get(index / 64)
+            .is_some_and(|long| long & (1 << (index % 64)) != 0)
+    }
+}
+
+impl Serialize for BitSet {
+    fn serialize_to(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        try {
+            VarInt(self.0.len() as i32).serialize_to(buf)?;
+            for long in &self.0 {
+                buf.put_i64(*long);
+            }
+        }
+    }
+}
+
+/// The light of a chunk's sections, including one below and one above the world, so bit 0 is the section below the world.
+///
+/// Sections in neither the mask nor the empty mask keep the light the client has for them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LightData {
+    pub sky_light_mask: BitSet,
+    pub block_light_mask: BitSet,
+    pub empty_sky_light_mask: BitSet,
+    pub empty_block_light_mask: BitSet,
+    /// One array of [`LIGHT_BYTES`] for each bit in the mask, in order.
+    pub sky_light: Vec<Vec<u8>>,
+    pub block_light: Vec<Vec<u8>>,
+}
+
+impl Serialize for LightData {
+    fn serialize_to(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        try {
+            self.sky_light_mask.serialize_to(buf)?;
+            self.block_light_mask.serialize_to(buf)?;
+            self.empty_sky_light_mask.serialize_to(buf)?;
+            self.empty_block_light_mask.serialize_to(buf)?;
+            for arrays in [&self.sky_light, &self.block_light] {
+                VarInt(arrays.len() as i32).serialize_to(buf)?;
+                for array in arrays {
+                    VarInt(array.len() as i32).serialize_to(buf)?;
+                    buf.put_slice(array);
+                }
+            }
+        }
+    }
+}
+
+/// The contents of a chunk, without where it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkData {
+    /// A compound with the `MOTION_BLOCKING` and `WORLD_SURFACE` heightmaps, see [`heightmap`].
+    pub heightmaps: Nbt,
+    /// From the bottom of the world up.
+    pub sections: Vec<ChunkSection>,
+}
+
+impl Serialize for ChunkData {
+    fn serialize_to(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        try {
+            self.heightmaps.serialize_root(buf)?;
+
+            let mut data = BytesMut::new();
+            for section in &self.sections {
+                section.serialize_to(&mut data)?;
+            }
+            VarInt(data.len() as i32).serialize_to(buf)?;
+            buf.put_slice(&data);
+
+            // no block entities yet
+            VarInt(0).serialize_to(buf)?;
+        }
+    }
+}
+
+/// Packs the height of each column of a chunk, indexed by `z << 4 | x`, counted from the bottom of the world.
+pub fn heightmap(heights: &[u16], world_height: usize) -> Vec<i64> {
+    pack(
+        heights.iter().map(|height| u64::from(*height)),
+        bits_for(world_height + 1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palettes() {
+        assert_eq!(bits_for(1), 0);
+        assert_eq!(bits_for(2), 1);
+        assert_eq!(bits_for(385), 9);
+
+        let air = PalettedContainer::blocks(&[0; SECTION_BLOCKS]);
+        assert_eq!(&air.serialize().unwrap()[..], [0, 0, 0]);
+
+        let mut states = [0; SECTION_BLOCKS];
+        states[1] = 1;
+        let blocks = PalettedContainer::blocks(&states);
+        assert_eq!((blocks.bits, &blocks.palette[..]), (4, &[0, 1][..]));
+        assert_eq!(blocks.data.len(), SECTION_BLOCKS / 16);
+        assert_eq!(blocks.data[0], 1 << 4);
+
+        let states = (0..SECTION_BLOCKS as u16).collect::<Vec<_>>();
+        let direct = PalettedContainer::blocks(&states);
+        assert!(direct.palette.is_empty());
+        assert_eq!(direct.data.len(), SECTION_BLOCKS.div_ceil(64 / 15));
+    }
+
+    #[test]
+    fn heightmaps() {
+        // 7 heights of 9 bits fit into a long
+        let data = heightmap(&[1; 256], 384);
+        assert_eq!(data.len(), 37);
+        assert_eq!(data[0], (0..7).map(|i| 1i64 << (i * 9)).sum::<i64>());
+    }
+}
@@ -0,0 +1,208 @@
+//! The command graph as the client sees it, sent in the [`Commands`](super::packets::play::Commands) packet.
+//!
+//! The nodes are sent as a flat list, referring to their children by their index in that list.
+
+use bytes::{BufMut, BytesMut};
+
+use crate::{
+    error::Error,
+    model::VarInt,
+    ser::{Identifier, Namespace, Serialize},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandNode {
+    pub kind: CommandNodeKind,
+    /// Whether the command can be run if it ends at this node.
+    pub executable: bool,
+    /// Indices of the children of this node.
+    pub children: Vec<i32>,
+    /// The node to continue parsing at after this one, like `execute run` redirecting to the root.
+    pub redirect: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandNodeKind {
+    Root,
+    Literal(String),
+    Argument {
+        name: String,
+        parser: ArgumentParser,
+        /// Whether the client should ask the server for suggestions
+        /// (with the Command Suggestions Request packet) instead of suggesting by itself.
+        ask_server: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringKind {
+    /// A single word.
+    Word,
+    /// A single word, or anything in double quotes.
+    Quotable,
+    /// Everything until the end of the command.
+    Greedy,
+}
+
+/// How an argument is parsed, the client uses this for highlighting and its own suggestions.
+/// Only the parsers the server knows how to parse itself are here.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentParser {
+    Bool,
+    Float { min: Option<f32>, max: Option<f32> },
+    Double { min: Option<f64>, max: Option<f64> },
+    Integer { min: Option<i32>, max: Option<i32> },
+    Long { min: Option<i64>, max: Option<i64> },
+    String(StringKind),
+    Entity { single: bool, players_only: bool },
+    GameProfile,
+    BlockPos,
+    ColumnPos,
+    Vec3,
+    Vec2,
+    Message,
+    GameMode,
+    Uuid,
+}
+
+impl ArgumentParser {
+    /// The id of this parser in the `minecraft:command_argument_type` registry.
+    pub fn id(&self) -> i32 {
+        match self {
+            Self::Bool => 0,
+            Self::Float { .. } => 1,
+            Self::Double { .. } => 2,
+            Self::Integer { .. } => 3,
+            Self::Long { .. } => 4,
+            Self::String(_) => 5,
+            Self::Entity { .. } => 6,
+            Self::GameProfile => 7,
+            Self::BlockPos => 8,
+            Self::ColumnPos => 9,
+            Self::Vec3 => 10,
+            Self::Vec2 => 11,
+            Self::Message => 18,
+            Self::GameMode => 39,
+            Self::Uuid => 48,
+        }
+    }
+}
+
+const NODE_ROOT: u8 = 0;
+const NODE_LITERAL: u8 = 1;
+const NODE_ARGUMENT: u8 = 2;
+const NODE_EXECUTABLE: u8 = 0x04;
+const NODE_REDIRECT: u8 = 0x08;
+const NODE_SUGGESTIONS: u8 = 0x10;
+
+/// Writes the flags for the minimum and maximum of a number argument, and then the ones that are there.
+fn range<T: Serialize>(buf: &mut BytesMut, min: &Option<T>, max: &Option<T>) -> Result<(), Error> {
+    try {
+        buf.put_u8(u8::from(min.is_some()) | u8::from(max.is_some()) << 1);
+        if let Some(min) = min {
+            min.serialize_to(buf)?;
+        }
+        if let Some(max) = max {
+            max.serialize_to(buf)?;
+        }
+    }
+}
+
+impl Serialize for ArgumentParser {
+    fn serialize_to(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        try {
+            VarInt(self.id()).serialize_to(buf)?;
+
+            match self {
+                Self::Float { min, max } => range(buf, min, max)?,
+                Self::Double { min, max } => range(buf, min, max)?,
+                Self::Integer { min, max } => range(buf, min, max)?,
+                Self::Long { min, max } => range(buf, min, max)?,
+                Self::String(kind) => VarInt(*kind as i32).serialize_to(buf)?,
+                Self::Entity {
+                    single,
+                    players_only,
+                } => buf.put_u8(u8::from(*single) | u8::from(*players_only) << 1),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Serialize for CommandNode {
+    fn serialize_to(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        try {
+            let mut flags = match &self.kind {
+                CommandNodeKind::Root => NODE_ROOT,
+                CommandNodeKind::Literal(_) => NODE_LITERAL,
+                CommandNodeKind::Argument { ask_server, .. } => {
+                    NODE_ARGUMENT | if *ask_server { NODE_SUGGESTIONS } else { 0 }
+                }
+            };
+            if self.executable {
+                flags |= NODE_EXECUTABLE;
+            }
+            if self.redirect.is_some() {
+                flags |= NODE_REDIRECT;
+            }
+            buf.put_u8(flags);
+
+            VarInt(self.children.len() as i32).serialize_to(buf)?;
+            for child in &self.children {
+                VarInt(*child).serialize_to(buf)?;
+            }
+
+            if let Some(redirect) = self.redirect {
+                VarInt(redirect).serialize_to(buf)?;
+            }
+
+            match &self.kind {
+                CommandNodeKind::Root => {}
+                CommandNodeKind::Literal(name) => name.as_str().serialize_to(buf)?,
+                CommandNodeKind::Argument {
+                    name,
+                    parser,
+                    ask_server,
+                } => {
+                    name.as_str().serialize_to(buf)?;
+                    parser.serialize_to(buf)?;
+
+                    if *ask_server {
+                        Identifier::<crate::ser::YesSync>::new_static(
+                            Namespace::Minecraft,
+                            "ask_server",
+                        )
+                        .serialize_to(buf)?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn argument_node() {
+        let node = CommandNode {
+            kind: CommandNodeKind::Argument {
+                name: String::from("n"),
+                parser: ArgumentParser::Integer {
+                    min: Some(0),
+                    max: None,
+                },
+                ask_server: false,
+            },
+            executable: true,
+            children: vec![3],
+            redirect: None,
+        };
+
+        assert_eq!(
+            &node.serialize().unwrap()[..],
+            [0x06, 1, 3, 1, b'n', 3, 0x01, 0, 0, 0, 0]
+        );
+    }
+}
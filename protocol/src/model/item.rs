@@ -1,15 +1,240 @@
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
+//! Items, as they're sent in packets, stored in NBT, and shown in chat.
+//!
+//! Item ids are indices in the `minecraft:item` registry, which the client has built in,
+//! so they're taken from `items.txt`, the item names in registry order for [`PROTOCOL_VERSION`](super::PROTOCOL_VERSION).
+//! Items that don't stack to 64 have their max stack size after the name, like `ender_pearl 16`.
+//! The names can be regenerated from `reports/registries.json` of the vanilla data generator,
+//! the stack sizes are the ones vanilla's `Items` class gives them.
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-pub struct ResourceId {
-    pub namespace: String,
-    pub value: String,
+use std::{collections::HashMap, sync::OnceLock};
+
+use bytes::{BufMut, BytesMut};
+use indexmap::IndexMap;
+
+use crate::{
+    model::{
+        chat::{ChatHoverEvent, ChatHoverItem},
+        VarInt,
+    },
+    nbt::{Nbt, NbtTagType},
+    ser::{deser_cx, parser, Extra, Serialize as _},
+};
+
+const ITEMS: &str = include_str!("items.txt");
+
+struct ItemRegistry {
+    names: Vec<&'static str>,
+    max_stack_sizes: Vec<i8>,
+    ids: HashMap<&'static str, i32>,
+}
+
+fn registry() -> &'static ItemRegistry {
+    static REGISTRY: OnceLock<ItemRegistry> = OnceLock::new();
+
+    REGISTRY.get_or_init(|| {
+        let (names, max_stack_sizes) = ITEMS
+            .lines()
+            .map(|line| match line.split_once(' ') {
+                Some((name, size)) => (name, size.parse().expect("invalid max stack size")),
+                None => (line, 64),
+            })
+            .unzip::<_, _, Vec<_>, Vec<_>>();
+        let ids = names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| (*name, id as i32))
+            .collect();
+
+        ItemRegistry {
+            names,
+            max_stack_sizes,
+            ids,
+        }
+    })
+}
+
+/// The id of an item by its name, with or without the `minecraft:` namespace.
+pub fn item_id(name: &str) -> Option<VarInt> {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    registry().ids.get(name).copied().map(VarInt)
+}
+
+/// The name of an item by its id, without the namespace.
+pub fn item_name(id: VarInt) -> Option<&'static str> {
+    usize::try_from(id.0)
+        .ok()
+        .and_then(|id| registry().names.get(id))
+        .copied()
+}
+
+/// How many of an item fit in a slot, 64 for items that aren't in the registry.
+pub fn max_stack_size(id: VarInt) -> i8 {
+    usize::try_from(id.0)
+        .ok()
+        .and_then(|id| registry().max_stack_sizes.get(id))
+        .copied()
+        .unwrap_or(64)
+}
+
+/// An item stack as it's sent in packets. Empty slots are sent as [`Slot`]s that are `None`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemStack {
+    /// The id of the item in the `minecraft:item` registry.
+    pub item: VarInt,
+    pub count: i8,
+    /// Everything else about the item, like its enchantments or custom name.
+    pub nbt: Option<Nbt>,
+}
+
+pub type Slot = Option<ItemStack>;
+
+impl ItemStack {
+    /// A stack of `count` of the item with the name, if there's such an item.
+    pub fn new(name: &str, count: i8) -> Option<Self> {
+        Some(Self {
+            item: item_id(name)?,
+            count,
+            nbt: None,
+        })
+    }
+
+    /// Whether the stacks can be merged into one, which they can if they only differ in their count.
+    pub fn stacks_with(&self, other: &Self) -> bool {
+        self.item == other.item && self.nbt == other.nbt
+    }
+
+    pub fn max_stack_size(&self) -> i8 {
+        max_stack_size(self.item)
+    }
+
+    /// The namespaced name of the item, like `minecraft:stone`.
+    pub fn name(&self) -> Option<String> {
+        item_name(self.item).map(|name| format!("minecraft:{name}"))
+    }
+
+    /// The stack as it's stored in chunks and player data, like `{id: "minecraft:stone", Count: 1b}`.
+    /// `None` if the item isn't in the registry.
+    pub fn to_nbt(&self) -> Option<Nbt> {
+        let mut compound = IndexMap::new();
+        compound.insert("id".to_owned(), Nbt::String(self.name()?));
+        compound.insert("Count".to_owned(), Nbt::Byte(self.count));
+        if let Some(tag) = &self.nbt {
+            compound.insert("tag".to_owned(), tag.clone());
+        }
+
+        Some(Nbt::Compound(compound))
+    }
+
+    /// The opposite of [`ItemStack::to_nbt`], `None` if it isn't a stack of a known item.
+    pub fn from_nbt(nbt: &Nbt) -> Option<Self> {
+        let Nbt::Compound(compound) = nbt else {
+            return None;
+        };
+        let Some(Nbt::String(id)) = compound.get("id") else {
+            return None;
+        };
+        let Some(Nbt::Byte(count)) = compound.get("Count") else {
+            return None;
+        };
+
+        Some(Self {
+            item: item_id(id)?,
+            count: *count,
+            nbt: compound.get("tag").cloned(),
+        })
+    }
+
+    /// Shows the stack's tooltip when hovering over text in chat.
+    pub fn hover_event(&self) -> Option<ChatHoverEvent> {
+        Some(ChatHoverEvent::ShowItem(ChatHoverItem {
+            id: self.name()?,
+            count: self.count.into(),
+            tag: self.nbt.as_ref().map(ToString::to_string),
+        }))
+    }
+}
+
+impl crate::ser::Serialize for ItemStack {
+    fn serialize_to(&self, buf: &mut BytesMut) -> Result<(), crate::error::Error> {
+        self.item.serialize_to(buf)?;
+        self.count.serialize_to(buf)?;
+        match &self.nbt {
+            Some(nbt) => nbt.serialize_root(buf),
+            None => {
+                buf.put_u8(NbtTagType::End as u8);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl crate::ser::Deserialize for ItemStack {
+    #[parser(extras = "Extra<Self::Context>")]
+    fn deserialize(input: &[u8]) -> Self {
+        Ok(Self {
+            item: deser_cx(input)?,
+            count: deser_cx(input)?,
+            nbt: Nbt::root(input)?,
+        })
+    }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-pub struct ItemData {
-    pub id: ResourceId,
-    pub count: u8,
-    pub tag: Option<Value>,
+#[cfg(test)]
+mod tests {
+    use aott::prelude::Parser;
+
+    use super::*;
+    use crate::ser::{Deserialize, Serialize};
+
+    #[test]
+    fn ids() {
+        assert_eq!(item_id("air"), Some(VarInt(0)));
+        assert_eq!(item_id("minecraft:stone"), Some(VarInt(1)));
+        assert_eq!(item_name(VarInt(14)), Some("grass_block"));
+        assert_eq!(item_id("not_an_item"), None);
+        assert_eq!(item_name(VarInt(-1)), None);
+
+        assert_eq!(max_stack_size(item_id("diamond_sword").unwrap()), 1);
+        assert_eq!(max_stack_size(item_id("water_bucket").unwrap()), 1);
+        assert_eq!(max_stack_size(item_id("bucket").unwrap()), 16);
+        assert_eq!(max_stack_size(item_id("oak_sign").unwrap()), 16);
+        assert_eq!(max_stack_size(item_id("stone").unwrap()), 64);
+        assert_eq!(max_stack_size(item_id("globe_banner_pattern").unwrap()), 1);
+        assert_eq!(max_stack_size(item_id("white_banner").unwrap()), 16);
+        assert_eq!(max_stack_size(item_id("disc_fragment_5").unwrap()), 64);
+        assert_eq!(max_stack_size(VarInt(-1)), 64);
+    }
+
+    #[test]
+    fn network() {
+        let stack = ItemStack::new("stone", 64).unwrap();
+        let bytes = Some(stack).serialize().unwrap();
+        assert_eq!(&bytes[..], [1, 1, 64, 0]);
+
+        let stack = ItemStack::deserialize.parse(&bytes[1..]).unwrap();
+        assert_eq!((stack.item, stack.count), (VarInt(1), 64));
+        assert!(stack.nbt.is_none());
+    }
+
+    #[test]
+    fn nbt() {
+        let mut stack = ItemStack::new("diamond_sword", 1).unwrap();
+        stack.nbt = Some(Nbt::Compound(
+            [("Damage".to_owned(), Nbt::Int(3))].into_iter().collect(),
+        ));
+
+        let nbt = stack.to_nbt().unwrap();
+        assert_eq!(
+            nbt.to_string(),
+            r#"{id:"minecraft:diamond_sword",Count:1b,tag:{Damage:3}}"#
+        );
+
+        let back = ItemStack::from_nbt(&nbt).unwrap();
+        assert_eq!((back.item, back.count), (stack.item, 1));
+
+        let Some(ChatHoverEvent::ShowItem(hover)) = stack.hover_event() else {
+            panic!("not an item hover event");
+        };
+        assert_eq!(hover.tag.as_deref(), Some("{Damage:3}"));
+    }
 }
@@ -0,0 +1,244 @@
+//! Entity metadata, the values that make up how an entity looks to clients, like whether it's on fire or sneaking,
+//! sent in the [`SetEntityMetadata`](super::packets::play::SetEntityMetadata) packet.
+//!
+//! Every entity type has its own list of what's at which index, see <https://wiki.vg/Entity_metadata>.
+
+use bytes::{BufMut, BytesMut};
+use uuid::Uuid;
+
+use crate::{
+    error::Error,
+    model::{chat::ChatComponent, item::Slot, VarInt},
+    nbt::Nbt,
+    ser::{Json, Position, Serialize},
+};
+
+/// Ends the list of entries.
+const END: u8 = 0xff;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(i32)]
+pub enum Pose {
+    #[default]
+    Standing = 0,
+    FallFlying = 1,
+    Sleeping = 2,
+    Swimming = 3,
+    SpinAttack = 4,
+    Sneaking = 5,
+    LongJumping = 6,
+    Dying = 7,
+    Croaking = 8,
+    UsingTongue = 9,
+    Sitting = 10,
+    Roaring = 11,
+    Sniffing = 12,
+    Emerging = 13,
+    Digging = 14,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum Direction {
+    Down = 0,
+    Up = 1,
+    North = 2,
+    South = 3,
+    West = 4,
+    East = 5,
+}
+
+/// The value of a metadata entry. Only the types the server has a use for are here,
+/// the particle and global position types are left out.
+#[derive(Debug, Clone)]
+pub enum MetadataValue {
+    Byte(i8),
+    VarInt(VarInt),
+    VarLong(i64),
+    Float(f32),
+    String(String),
+    Chat(ChatComponent),
+    OptChat(Option<ChatComponent>),
+    Slot(Slot),
+    Boolean(bool),
+    /// Rotation around the x, y and z axes in degrees, like armor stand poses.
+    Rotation([f32; 3]),
+    Position(Position),
+    OptPosition(Option<Position>),
+    Direction(Direction),
+    OptUuid(Option<Uuid>),
+    /// An id in the block state registry.
+    BlockId(VarInt),
+    /// An id in the block state registry, where 0 (air) stands for none.
+    OptBlockId(VarInt),
+    Nbt(Nbt),
+    VillagerData {
+        kind: VarInt,
+        profession: VarInt,
+        level: VarInt,
+    },
+    /// Sent as 0 for `None` and the value plus one otherwise.
+    OptVarInt(Option<i32>),
+    Pose(Pose),
+    CatVariant(VarInt),
+    FrogVariant(VarInt),
+    PaintingVariant(VarInt),
+    SnifferState(VarInt),
+    Vector3([f32; 3]),
+    Quaternion([f32; 4]),
+}
+
+impl MetadataValue {
+    /// The id of the type of this value.
+    pub fn type_id(&self) -> i32 {
+        match self {
+            Self::Byte(_) => 0,
+            Self::VarInt(_) => 1,
+            Self::VarLong(_) => 2,
+            Self::Float(_) => 3,
+            Self::String(_) => 4,
+            Self::Chat(_) => 5,
+            Self::OptChat(_) => 6,
+            Self::Slot(_) => 7,
+            Self::Boolean(_) => 8,
+            Self::Rotation(_) => 9,
+            Self::Position(_) => 10,
+            Self::OptPosition(_) => 11,
+            Self::Direction(_) => 12,
+            Self::OptUuid(_) => 13,
+            Self::BlockId(_) => 14,
+            Self::OptBlockId(_) => 15,
+            Self::Nbt(_) => 16,
+            Self::VillagerData { .. } => 18,
+            Self::OptVarInt(_) => 19,
+            Self::Pose(_) => 20,
+            Self::CatVariant(_) => 21,
+            Self::FrogVariant(_) => 22,
+            Self::PaintingVariant(_) => 24,
+            Self::SnifferState(_) => 25,
+            Self::Vector3(_) => 26,
+            Self::Quaternion(_) => 27,
+        }
+    }
+}
+
+impl Serialize for MetadataValue {
+    fn serialize_to(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        try {
+            match self {
+                Self::Byte(byte) => byte.serialize_to(buf)?,
+                Self::VarInt(value)
+                | Self::BlockId(value)
+                | Self::OptBlockId(value)
+                | Self::CatVariant(value)
+                | Self::FrogVariant(value)
+                | Self::PaintingVariant(value)
+                | Self::SnifferState(value) => value.serialize_to(buf)?,
+                Self::VarLong(value) => write_var_long(*value, buf),
+                Self::Float(value) => value.serialize_to(buf)?,
+                Self::String(string) => string.as_str().serialize_to(buf)?,
+                Self::Chat(chat) => Json(chat).serialize_to(buf)?,
+                Self::OptChat(chat) => chat.as_ref().map(Json).serialize_to(buf)?,
+                Self::Slot(slot) => slot.serialize_to(buf)?,
+                Self::Boolean(value) => value.serialize_to(buf)?,
+                Self::Rotation(floats) | Self::Vector3(floats) => floats
+                    .iter()
+                    .try_for_each(|float| float.serialize_to(buf))?,
+                Self::Position(position) => position.serialize_to(buf)?,
+                Self::OptPosition(position) => position.serialize_to(buf)?,
+                Self::Direction(direction) => VarInt(*direction as i32).serialize_to(buf)?,
+                Self::OptUuid(uuid) => uuid.serialize_to(buf)?,
+                Self::Nbt(nbt) => nbt.serialize_root(buf)?,
+                Self::VillagerData {
+                    kind,
+                    profession,
+                    level,
+                } => {
+                    kind.serialize_to(buf)?;
+                    profession.serialize_to(buf)?;
+                    level.serialize_to(buf)?;
+                }
+                Self::OptVarInt(value) => {
+                    VarInt(value.map_or(0, |value| value + 1)).serialize_to(buf)?
+                }
+                Self::Pose(pose) => VarInt(*pose as i32).serialize_to(buf)?,
+                Self::Quaternion(floats) => floats
+                    .iter()
+                    .try_for_each(|float| float.serialize_to(buf))?,
+            }
+        }
+    }
+}
+
+/// VarLongs are VarInts with up to 10 bytes, they only come up here and in block updates.
+pub(crate) fn write_var_long(value: i64, buf: &mut BytesMut) {
+    let mut value = value as u64;
+    loop {
+        if value & !0x7f == 0 {
+            buf.put_u8(value as u8);
+            return;
+        }
+        buf.put_u8((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+/// Metadata entries by their index. Only the entries that are in here are changed on the client.
+#[derive(Debug, Clone, Default)]
+pub struct EntityMetadata(pub Vec<(u8, MetadataValue)>);
+
+impl EntityMetadata {
+    pub fn set(&mut self, index: u8, value: MetadataValue) {
+        match self.0.iter_mut().find(|(at, _)| *at == index) {
+            Some((_, old)) => *old = value,
+            None => self.0.push((index, value)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Serialize for EntityMetadata {
+    fn serialize_to(&self, buf: &mut BytesMut) -> Result<(), Error> {
+        try {
+            for (index, value) in &self.0 {
+                debug_assert_ne!(*index, END, "{END} marks the end of the entries");
+                buf.put_u8(*index);
+                VarInt(value.type_id()).serialize_to(buf)?;
+                value.serialize_to(buf)?;
+            }
+            buf.put_u8(END);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries() {
+        let mut metadata = EntityMetadata::default();
+        metadata.set(0, MetadataValue::Byte(0x02));
+        metadata.set(6, MetadataValue::Pose(Pose::Sneaking));
+        metadata.set(0, MetadataValue::Byte(0x0a));
+        metadata.set(2, MetadataValue::OptChat(None));
+
+        assert_eq!(
+            &metadata.serialize().unwrap()[..],
+            [0, 0, 0x0a, 6, 20, 5, 2, 6, 0, 0xff]
+        );
+    }
+
+    #[test]
+    fn var_long() {
+        let mut buf = BytesMut::new();
+        write_var_long(-1, &mut buf);
+        assert_eq!(
+            &buf[..],
+            [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]
+        );
+    }
+}
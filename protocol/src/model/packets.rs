@@ -48,6 +48,10 @@ pub struct PacketContext {
 pub trait Packet {
     const ID: super::VarInt;
     const STATE: super::State;
+    /// Whether the packet must reach the client even if its send queue is full.
+    /// Packets that only carry absolute state the next one of their kind replaces (like entity rotation)
+    /// should set this to `false`, so they can be dropped under [`SendPolicy::DropNonEssential`](crate::net::SendPolicy::DropNonEssential).
+    const ESSENTIAL: bool = true;
 }
 
 #[derive(Debug, Clone)]
@@ -85,13 +89,16 @@ impl SerializedPacket {
             ))
     }
 
-    pub fn serialize_compressing(&self, compression: Option<usize>) -> Result<Bytes, Error> {
+    pub fn serialize_compressing(
+        &self,
+        compression: Option<usize>,
+        codec: Zlib,
+    ) -> Result<Bytes, Error> {
         if let Some(cmp) = compression {
-            let (data_length, length) = if self.length >= cmp {
+            let (data_length, payload) = if self.length >= cmp {
                 (
                     self.length,
-                    Compress((&self.id, &self.data), Zlib).serialize()?.len()
-                        + VarInt(self.length as i32).length_of(),
+                    Compress((&self.id, &self.data), codec).serialize()?,
                 )
             } else {
                 trace!(
@@ -102,14 +109,15 @@ impl SerializedPacket {
                     data_length=%self.data.len(),
                     "packet was smaller than threshold, sending uncompressed"
                 );
-                (0, self.length + 1)
+                (0, (&self.id, &self.data).serialize()?)
             };
 
             let pack = SerializedPacketCompressed {
-                length,
+                length: VarInt(data_length as i32).length_of() + payload.len(),
                 data_length,
                 id: self.id,
                 data: self.data.clone(),
+                payload,
             };
             pack.serialize()
         } else {
@@ -142,6 +150,87 @@ impl SerializedPacket {
     }
 }
 
+/// Reusable state for compressing outgoing packets.
+/// Keeps one zlib stream and one output buffer per connection, instead of allocating new ones for every packet.
+pub struct PacketCompressor {
+    threshold: usize,
+    zlib: flate2::Compress,
+    scratch: Vec<u8>,
+}
+
+impl std::fmt::Debug for PacketCompressor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PacketCompressor")
+            .field("threshold", &self.threshold)
+            .field("scratch_capacity", &self.scratch.capacity())
+            .finish_non_exhaustive()
+    }
+}
+
+impl PacketCompressor {
+    pub fn new(threshold: usize, codec: Zlib) -> Self {
+        Self {
+            threshold,
+            zlib: flate2::Compress::new(flate2::Compression::new(codec.level), true),
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Writes `packet` to the end of `buf` in the compressed packet format,
+    /// compressing it only if it's at least as long as the threshold.
+    pub fn write(&mut self, packet: &SerializedPacket, buf: &mut BytesMut) -> Result<(), Error> {
+        let length_of = |length: usize| -> Result<VarInt, Error> {
+            Ok(VarInt(length.try_into().map_err(|_| Error::VarIntTooBig)?))
+        };
+
+        if packet.length < self.threshold {
+            let length = length_of(packet.length + 1)?;
+            buf.reserve(length.length_of() + 1 + packet.length);
+            length.serialize_to(buf)?;
+            VarInt(0).serialize_to(buf)?;
+            packet.id.serialize_to(buf)?;
+            buf.put_slice(&packet.data);
+            return Ok(());
+        }
+
+        self.zlib.reset();
+        self.scratch.clear();
+        let mut id = BytesMut::with_capacity(packet.id.length_of());
+        packet.id.serialize_to(&mut id)?;
+        self.deflate(&id, flate2::FlushCompress::None)?;
+        self.deflate(&packet.data, flate2::FlushCompress::Finish)?;
+
+        let data_length = length_of(packet.length)?;
+        let length = length_of(data_length.length_of() + self.scratch.len())?;
+        buf.reserve(length.length_of() + data_length.length_of() + self.scratch.len());
+        length.serialize_to(buf)?;
+        data_length.serialize_to(buf)?;
+        buf.put_slice(&self.scratch);
+
+        Ok(())
+    }
+
+    fn deflate(&mut self, mut input: &[u8], flush: flate2::FlushCompress) -> Result<(), Error> {
+        loop {
+            // compress_vec only writes into spare capacity, so make sure there is some.
+            self.scratch.reserve(input.len() / 2 + 64);
+
+            let before = self.zlib.total_in();
+            let status = self
+                .zlib
+                .compress_vec(input, &mut self.scratch, flush)
+                .map_err(std::io::Error::from)?;
+            input = &input[(self.zlib.total_in() - before) as usize..];
+
+            match status {
+                flate2::Status::StreamEnd => return Ok(()),
+                _ if input.is_empty() && flush == flate2::FlushCompress::None => return Ok(()),
+                _ => continue,
+            }
+        }
+    }
+}
+
 impl Deserialize for SerializedPacket {
     #[parser(extras = "Extra<Self::Context>")]
     fn deserialize(input: &[u8]) -> Self {
@@ -174,27 +263,29 @@ pub struct SerializedPacketCompressed {
     pub data_length: usize,
     pub id: super::VarInt,
     pub data: Bytes,
+    /// `id` and `data` as they're written, compressed unless `data_length` is 0.
+    payload: Bytes,
 }
 
 impl SerializedPacketCompressed {
-    pub fn new<P: Packet + Serialize>(packet: P) -> Result<Self, Error> {
-        Self::new_ref(&packet)
+    pub fn new<P: Packet + Serialize>(packet: P, codec: Zlib) -> Result<Self, Error> {
+        Self::new_ref(&packet, codec)
     }
 
-    pub fn new_ref<P: Packet + Serialize + ?Sized>(packet: &P) -> Result<Self, Error> {
+    pub fn new_ref<P: Packet + Serialize + ?Sized>(packet: &P, codec: Zlib) -> Result<Self, Error> {
         try {
             let data = packet.serialize()?;
             let id = P::ID;
             let data_length = id.length_of() + data.len();
             let datalength = VarInt::<i32>(data_length.try_into().unwrap());
-            let length = datalength.length_of() + Compress((&id, &data), Zlib).serialize()?.len();
-
+            let payload = Compress((&id, &data), codec).serialize()?;
 
             Self {
-                length,
+                length: datalength.length_of() + payload.len(),
                 data_length,
                 id,
                 data,
+                payload,
             }
         }
     }
@@ -266,6 +357,7 @@ impl Deserialize for SerializedPacketCompressed {
                 data_length,
                 id,
                 data: Bytes::copy_from_slice(data),
+                payload: Bytes::copy_from_slice(data_maybe),
             }
         }
     }
@@ -283,11 +375,7 @@ impl Serialize for SerializedPacketCompressed {
                 .map_err(|_| Error::VarIntTooBig)?,
         );
         data_length.serialize_to(buf)?;
-        if self.data_length > 0 {
-            Compress((&self.id, &self.data), Zlib).serialize_to(buf)?
-        } else {
-            (&self.id, &self.data).serialize_to(buf)?
-        }
+        buf.put_slice(&self.payload);
         Ok(())
     }
 }
@@ -314,3 +402,50 @@ impl Packet for PluginMessage {
     const ID: super::VarInt = VarInt(0x17);
     const STATE: super::State = State::Play;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compressor_matches_serialize_compressing() {
+        let threshold = 256;
+
+        for codec in [Zlib::new(1), Zlib::default(), Zlib::new(9)] {
+            let mut compressor = PacketCompressor::new(threshold, codec);
+
+            for size in [0, 100, threshold - 2, threshold - 1, 5000, 100_000] {
+                let id = VarInt(0x17);
+                let packet = SerializedPacket {
+                    length: id.length_of() + size,
+                    id,
+                    data: (0..size).map(|i| (i % 251) as u8).collect(),
+                };
+
+                let mut buf = BytesMut::new();
+                compressor.write(&packet, &mut buf).unwrap();
+                let expected = packet
+                    .serialize_compressing(Some(threshold), codec)
+                    .unwrap();
+                assert_eq!(
+                    &buf[..],
+                    &expected[..],
+                    "{} bytes at {codec:?}",
+                    packet.length
+                );
+
+                let decoded = SerializedPacketCompressed::deserialize
+                    .parse(&buf[..])
+                    .unwrap();
+                let compressed = packet.length >= threshold;
+                assert_eq!(
+                    decoded.data_length,
+                    if compressed { packet.length } else { 0 }
+                );
+                assert_eq!((decoded.id, &decoded.data), (packet.id, &packet.data));
+                // written back as it was read, without compressing it again
+                assert_eq!(&decoded.serialize().unwrap()[..], &buf[..]);
+            }
+        }
+    }
+}
@@ -1,16 +1,24 @@
 use crate::{
-    model::{chat::ChatComponent, Difficulty, State, VarInt},
+    model::{
+        chat::ChatComponent,
+        chunk::{ChunkData, LightData},
+        command::CommandNode,
+        item::Slot,
+        metadata::{write_var_long, EntityMetadata},
+        Difficulty, State, VarInt,
+    },
     nbt::Nbt,
     ser::*,
     PacketContext,
 };
 use std::ptr;
+use uuid::Uuid;
 
 use aott::primitive::filter;
 use bytes::BufMut;
 use indexmap::IndexMap;
 
-use super::Packet;
+use super::{login::Property, Packet};
 
 #[derive(Debug)]
 pub struct DisconnectPlay {
@@ -86,7 +94,8 @@ impl_ser!(DeathLocation => [
     dimension, location
 ]);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(crate = "serde", rename_all = "lowercase")]
 #[repr(u8)]
 pub enum GameMode {
     Survival = 0,
@@ -197,6 +206,936 @@ impl Packet for SetDefaultSpawnPosition {
     const STATE: crate::model::State = State::Play;
 }
 
+/// Sent when the view distance of the server changes, the client stops rendering chunks farther than this.
+#[derive(Debug, Clone)]
+pub struct SetRenderDistance {
+    pub view_distance: VarInt,
+}
+
+impl_ser!(|PacketContext| SetRenderDistance => [view_distance]);
+impl Packet for SetRenderDistance {
+    const ID: crate::model::VarInt = VarInt(0x4f);
+    const STATE: crate::model::State = State::Play;
+}
+
+#[derive(Debug, Clone)]
+pub struct SetSimulationDistance {
+    pub simulation_distance: VarInt,
+}
+
+impl_ser!(|PacketContext| SetSimulationDistance => [simulation_distance]);
+impl Packet for SetSimulationDistance {
+    const ID: crate::model::VarInt = VarInt(0x5c);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// A chat message from the server itself, rather than from a player.
+#[derive(Debug, Clone)]
+pub struct SystemChatMessage {
+    pub content: Json<ChatComponent>,
+    /// Shows the message above the hotbar instead of in the chat.
+    pub overlay: bool,
+}
+
+impl_ser!(|PacketContext| SystemChatMessage => [content, overlay]);
+impl Packet for SystemChatMessage {
+    const ID: crate::model::VarInt = VarInt(0x64);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Changes some part of the game state, what `value` means depends on the `event`.
+#[derive(Debug, Clone)]
+pub struct GameEvent {
+    pub event: u8,
+    pub value: f32,
+}
+
+impl GameEvent {
+    /// `value` is the new game mode.
+    pub const CHANGE_GAME_MODE: u8 = 3;
+}
+
+impl_ser!(|PacketContext| GameEvent => [event, value]);
+impl Packet for GameEvent {
+    const ID: crate::model::VarInt = VarInt(0x1f);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// The command graph, which the client uses to highlight and suggest commands.
+#[derive(Debug, Clone)]
+pub struct Commands {
+    pub nodes: Vec<CommandNode>,
+    /// The index of the root node in `nodes`.
+    pub root: VarInt,
+}
+
+impl Serialize for Commands {
+    fn serialize_to(&self, buf: &mut bytes::BytesMut) -> Result<(), crate::error::Error> {
+        try {
+            VarInt(self.nodes.len() as i32).serialize_to(buf)?;
+            for node in &self.nodes {
+                node.serialize_to(buf)?;
+            }
+            self.root.serialize_to(buf)?;
+        }
+    }
+}
+
+impl Packet for Commands {
+    const ID: crate::model::VarInt = VarInt(0x10);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// A command the player ran, without the leading `/`.
+///
+/// The command is followed by a timestamp, a salt and signatures of the arguments,
+/// which aren't read since the server doesn't check them.
+#[derive(Debug, Clone)]
+pub struct ChatCommand {
+    pub command: FixedStr<256>,
+}
+
+impl_ser!(|PacketContext| ChatCommand => [command]);
+impl Packet for ChatCommand {
+    const ID: crate::model::VarInt = VarInt(0x04);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Sent when the player types a command, if the argument being typed asks the server for suggestions.
+#[derive(Debug, Clone)]
+pub struct CommandSuggestionsRequest {
+    pub transaction_id: VarInt,
+    /// Everything before the cursor, including the leading `/`.
+    pub text: FixedStr<32500>,
+}
+
+impl_ser!(|PacketContext| CommandSuggestionsRequest => [transaction_id, text]);
+impl Packet for CommandSuggestionsRequest {
+    const ID: crate::model::VarInt = VarInt(0x09);
+    const STATE: crate::model::State = State::Play;
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandSuggestionsResponse {
+    pub transaction_id: VarInt,
+    /// Where the text that is replaced by the suggestions starts.
+    pub start: VarInt,
+    /// How long the replaced text is.
+    pub length: VarInt,
+    pub matches: Array<CommandSuggestion>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandSuggestion {
+    pub suggestion: FixedStr<32767>,
+    pub tooltip: Option<Json<ChatComponent>>,
+}
+
+impl Serialize for CommandSuggestion {
+    fn serialize_to(&self, buf: &mut bytes::BytesMut) -> Result<(), crate::error::Error> {
+        try {
+            self.suggestion.serialize_to(buf)?;
+            self.tooltip.serialize_to(buf)?;
+        }
+    }
+}
+
+impl Serialize for CommandSuggestionsResponse {
+    fn serialize_to(&self, buf: &mut bytes::BytesMut) -> Result<(), crate::error::Error> {
+        try {
+            self.transaction_id.serialize_to(buf)?;
+            self.start.serialize_to(buf)?;
+            self.length.serialize_to(buf)?;
+            self.matches.serialize_to(buf)?;
+        }
+    }
+}
+
+impl Packet for CommandSuggestionsResponse {
+    const ID: crate::model::VarInt = VarInt(0x0f);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Sent every few seconds, the client has to answer with a [`KeepAliveResponse`] with the same id,
+/// or it's disconnected.
+#[derive(Debug, Clone)]
+pub struct KeepAlive {
+    pub id: i64,
+}
+
+impl_ser!(|PacketContext| KeepAlive => [id]);
+impl Packet for KeepAlive {
+    const ID: crate::model::VarInt = VarInt(0x23);
+    const STATE: crate::model::State = State::Play;
+}
+
+#[derive(Debug, Clone)]
+pub struct KeepAliveResponse {
+    pub id: i64,
+}
+
+impl_ser!(|PacketContext| KeepAliveResponse => [id]);
+impl Packet for KeepAliveResponse {
+    const ID: crate::model::VarInt = VarInt(0x12);
+    const STATE: crate::model::State = State::Play;
+}
+
+bitflags::bitflags! {
+    /// Which parts of the entries are in a [`PlayerInfoUpdate`].
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PlayerInfoActions: u8 {
+        const ADD_PLAYER = 0x01;
+        const INITIALIZE_CHAT = 0x02;
+        const UPDATE_GAME_MODE = 0x04;
+        const UPDATE_LISTED = 0x08;
+        const UPDATE_LATENCY = 0x10;
+        const UPDATE_DISPLAY_NAME = 0x20;
+    }
+}
+
+/// A player in the tab list.
+#[derive(Debug, Clone)]
+pub struct PlayerInfoEntry {
+    pub uuid: Uuid,
+    pub name: FixedStr<16>,
+    /// The skin and cape, signed by Mojang.
+    pub properties: Array<Property>,
+    pub game_mode: GameMode,
+    /// Whether the player is shown in the tab list.
+    pub listed: bool,
+    /// The ping in milliseconds, shown as bars.
+    pub latency: VarInt,
+    /// Shown instead of the name if set.
+    pub display_name: Option<Json<ChatComponent>>,
+}
+
+/// Adds players to the tab list, or changes their entries.
+/// Only the parts of the entries in `actions` are sent, the rest is ignored.
+#[derive(Debug, Clone)]
+pub struct PlayerInfoUpdate {
+    pub actions: PlayerInfoActions,
+    pub players: Vec<PlayerInfoEntry>,
+}
+
+impl Serialize for PlayerInfoUpdate {
+    fn serialize_to(&self, buf: &mut bytes::BytesMut) -> Result<(), crate::error::Error> {
+        try {
+            let actions = self.actions;
+            actions.bits().serialize_to(buf)?;
+            VarInt(self.players.len() as i32).serialize_to(buf)?;
+
+            for player in &self.players {
+                player.uuid.serialize_to(buf)?;
+
+                if actions.contains(PlayerInfoActions::ADD_PLAYER) {
+                    player.name.serialize_to(buf)?;
+                    player.properties.serialize_to(buf)?;
+                }
+                if actions.contains(PlayerInfoActions::INITIALIZE_CHAT) {
+                    // there are no chat sessions without online mode
+                    false.serialize_to(buf)?;
+                }
+                if actions.contains(PlayerInfoActions::UPDATE_GAME_MODE) {
+                    VarInt(player.game_mode as i32).serialize_to(buf)?;
+                }
+                if actions.contains(PlayerInfoActions::UPDATE_LISTED) {
+                    player.listed.serialize_to(buf)?;
+                }
+                if actions.contains(PlayerInfoActions::UPDATE_LATENCY) {
+                    player.latency.serialize_to(buf)?;
+                }
+                if actions.contains(PlayerInfoActions::UPDATE_DISPLAY_NAME) {
+                    player.display_name.serialize_to(buf)?;
+                }
+            }
+        }
+    }
+}
+
+impl Packet for PlayerInfoUpdate {
+    const ID: crate::model::VarInt = VarInt(0x3a);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Removes players from the tab list.
+#[derive(Debug, Clone)]
+pub struct PlayerInfoRemove {
+    pub players: Array<Uuid>,
+}
+
+impl Serialize for PlayerInfoRemove {
+    fn serialize_to(&self, buf: &mut bytes::BytesMut) -> Result<(), crate::error::Error> {
+        self.players.serialize_to(buf)
+    }
+}
+
+impl Packet for PlayerInfoRemove {
+    const ID: crate::model::VarInt = VarInt(0x39);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// The text above and below the tab list. An empty text hides it.
+#[derive(Debug, Clone)]
+pub struct SetTabListHeaderAndFooter {
+    pub header: Json<ChatComponent>,
+    pub footer: Json<ChatComponent>,
+}
+
+impl_ser!(|PacketContext| SetTabListHeaderAndFooter => [header, footer]);
+impl Packet for SetTabListHeaderAndFooter {
+    const ID: crate::model::VarInt = VarInt(0x65);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Spawns an entity that isn't a player.
+#[derive(Debug, Clone)]
+pub struct SpawnEntity {
+    pub entity_id: VarInt,
+    pub uuid: Uuid,
+    /// The id in the `minecraft:entity_type` registry.
+    pub kind: VarInt,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub pitch: Angle,
+    pub yaw: Angle,
+    pub head_yaw: Angle,
+    /// Depends on the kind of entity, like the block of a falling block.
+    pub data: VarInt,
+    /// In 1/8000 of a block per tick.
+    pub velocity_x: i16,
+    pub velocity_y: i16,
+    pub velocity_z: i16,
+}
+
+impl_ser!(|PacketContext| SpawnEntity => [
+    entity_id, uuid, kind, x, y, z, pitch, yaw, head_yaw, data, velocity_x, velocity_y, velocity_z
+]);
+impl Packet for SpawnEntity {
+    const ID: crate::model::VarInt = VarInt(0x01);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Spawns another player. The player has to be in the tab list already.
+#[derive(Debug, Clone)]
+pub struct SpawnPlayer {
+    pub entity_id: VarInt,
+    pub uuid: Uuid,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: Angle,
+    pub pitch: Angle,
+}
+
+impl_ser!(|PacketContext| SpawnPlayer => [entity_id, uuid, x, y, z, yaw, pitch]);
+impl Packet for SpawnPlayer {
+    const ID: crate::model::VarInt = VarInt(0x03);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Moves an entity by less than 8 blocks.
+/// The deltas are `(current * 32 - previous * 32) * 128`, so 4096 of them make up a block.
+#[derive(Debug, Clone)]
+pub struct UpdateEntityPosition {
+    pub entity_id: VarInt,
+    pub delta_x: i16,
+    pub delta_y: i16,
+    pub delta_z: i16,
+    pub on_ground: bool,
+}
+
+impl_ser!(|PacketContext| UpdateEntityPosition => [entity_id, delta_x, delta_y, delta_z, on_ground]);
+impl Packet for UpdateEntityPosition {
+    const ID: crate::model::VarInt = VarInt(0x2b);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// [`UpdateEntityPosition`] and [`UpdateEntityRotation`] in one.
+#[derive(Debug, Clone)]
+pub struct UpdateEntityPositionAndRotation {
+    pub entity_id: VarInt,
+    pub delta_x: i16,
+    pub delta_y: i16,
+    pub delta_z: i16,
+    pub yaw: Angle,
+    pub pitch: Angle,
+    pub on_ground: bool,
+}
+
+impl_ser!(|PacketContext| UpdateEntityPositionAndRotation => [
+    entity_id, delta_x, delta_y, delta_z, yaw, pitch, on_ground
+]);
+impl Packet for UpdateEntityPositionAndRotation {
+    const ID: crate::model::VarInt = VarInt(0x2c);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Turns the body of an entity. The head is turned with [`SetHeadRotation`].
+#[derive(Debug, Clone)]
+pub struct UpdateEntityRotation {
+    pub entity_id: VarInt,
+    pub yaw: Angle,
+    pub pitch: Angle,
+    pub on_ground: bool,
+}
+
+impl_ser!(|PacketContext| UpdateEntityRotation => [entity_id, yaw, pitch, on_ground]);
+impl Packet for UpdateEntityRotation {
+    const ID: crate::model::VarInt = VarInt(0x2d);
+    const STATE: crate::model::State = State::Play;
+    const ESSENTIAL: bool = false;
+}
+
+#[derive(Debug, Clone)]
+pub struct SetHeadRotation {
+    pub entity_id: VarInt,
+    pub head_yaw: Angle,
+}
+
+impl_ser!(|PacketContext| SetHeadRotation => [entity_id, head_yaw]);
+impl Packet for SetHeadRotation {
+    const ID: crate::model::VarInt = VarInt(0x42);
+    const STATE: crate::model::State = State::Play;
+    const ESSENTIAL: bool = false;
+}
+
+/// Moves an entity by any distance.
+#[derive(Debug, Clone)]
+pub struct TeleportEntity {
+    pub entity_id: VarInt,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: Angle,
+    pub pitch: Angle,
+    pub on_ground: bool,
+}
+
+impl_ser!(|PacketContext| TeleportEntity => [entity_id, x, y, z, yaw, pitch, on_ground]);
+impl Packet for TeleportEntity {
+    const ID: crate::model::VarInt = VarInt(0x68);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Shows an item entity flying into whoever picked it up.
+/// The client removes the item entity by itself once all of it was picked up.
+#[derive(Debug, Clone)]
+pub struct PickupItem {
+    pub collected_entity_id: VarInt,
+    pub collector_entity_id: VarInt,
+    pub count: VarInt,
+}
+
+serialize!(PickupItem => [collected_entity_id, collector_entity_id, count]);
+impl Packet for PickupItem {
+    const ID: crate::model::VarInt = VarInt(0x67);
+    const STATE: crate::model::State = State::Play;
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoveEntities {
+    pub entity_ids: Array<VarInt>,
+}
+
+impl_ser!(|PacketContext| RemoveEntities => [entity_ids]);
+impl Packet for RemoveEntities {
+    const ID: crate::model::VarInt = VarInt(0x3e);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Moves the player itself. The client answers with a Confirm Teleportation with the same `teleport_id`.
+#[derive(Debug, Clone)]
+pub struct SynchronizePlayerPosition {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Which of the values are relative to where the player is, none of them if 0.
+    pub flags: u8,
+    pub teleport_id: VarInt,
+}
+
+impl_ser!(|PacketContext| SynchronizePlayerPosition => [x, y, z, yaw, pitch, flags, teleport_id]);
+impl Packet for SynchronizePlayerPosition {
+    const ID: crate::model::VarInt = VarInt(0x3c);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Sent by the client when the player moves. `y` is where the feet are.
+#[derive(Debug, Clone)]
+pub struct SetPlayerPosition {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub on_ground: bool,
+}
+
+impl_ser!(|PacketContext| SetPlayerPosition => [x, y, z, on_ground]);
+impl Packet for SetPlayerPosition {
+    const ID: crate::model::VarInt = VarInt(0x14);
+    const STATE: crate::model::State = State::Play;
+}
+
+#[derive(Debug, Clone)]
+pub struct SetPlayerPositionAndRotation {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+
+impl_ser!(|PacketContext| SetPlayerPositionAndRotation => [x, y, z, yaw, pitch, on_ground]);
+impl Packet for SetPlayerPositionAndRotation {
+    const ID: crate::model::VarInt = VarInt(0x15);
+    const STATE: crate::model::State = State::Play;
+}
+
+#[derive(Debug, Clone)]
+pub struct SetPlayerRotation {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+
+impl_ser!(|PacketContext| SetPlayerRotation => [yaw, pitch, on_ground]);
+impl Packet for SetPlayerRotation {
+    const ID: crate::model::VarInt = VarInt(0x16);
+    const STATE: crate::model::State = State::Play;
+}
+
+#[derive(Debug, Clone)]
+pub struct SetPlayerOnGround {
+    pub on_ground: bool,
+}
+
+impl_ser!(|PacketContext| SetPlayerOnGround => [on_ground]);
+impl Packet for SetPlayerOnGround {
+    const ID: crate::model::VarInt = VarInt(0x17);
+    const STATE: crate::model::State = State::Play;
+}
+
+#[derive(Debug, Clone)]
+pub struct SetEntityMetadata {
+    pub entity_id: VarInt,
+    pub metadata: EntityMetadata,
+}
+
+serialize!(SetEntityMetadata => [entity_id, metadata]);
+impl Packet for SetEntityMetadata {
+    const ID: crate::model::VarInt = VarInt(0x52);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Sent by the client when the player starts or stops sneaking or sprinting, and a few other things.
+#[derive(Debug, Clone)]
+pub struct PlayerCommand {
+    pub entity_id: VarInt,
+    pub action: VarInt,
+    /// How strong a horse jump is, from 0 to 100.
+    pub jump_boost: VarInt,
+}
+
+impl PlayerCommand {
+    pub const START_SNEAKING: VarInt = VarInt(0);
+    pub const STOP_SNEAKING: VarInt = VarInt(1);
+    pub const LEAVE_BED: VarInt = VarInt(2);
+    pub const START_SPRINTING: VarInt = VarInt(3);
+    pub const STOP_SPRINTING: VarInt = VarInt(4);
+    pub const START_HORSE_JUMP: VarInt = VarInt(5);
+    pub const STOP_HORSE_JUMP: VarInt = VarInt(6);
+    pub const OPEN_HORSE_INVENTORY: VarInt = VarInt(7);
+    pub const START_FLYING_WITH_ELYTRA: VarInt = VarInt(8);
+}
+
+impl_ser!(|PacketContext| PlayerCommand => [entity_id, action, jump_boost]);
+impl Packet for PlayerCommand {
+    const ID: crate::model::VarInt = VarInt(0x1e);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Replaces every slot of a window, and the item the cursor is carrying.
+#[derive(Debug, Clone)]
+pub struct SetContainerContent {
+    pub window_id: u8,
+    /// The client sends it back when clicking, so that clicks on outdated contents can be told apart.
+    pub state_id: VarInt,
+    pub slots: Array<Slot>,
+    pub carried: Slot,
+}
+
+serialize!(SetContainerContent => [window_id, state_id, slots, carried]);
+impl Packet for SetContainerContent {
+    const ID: crate::model::VarInt = VarInt(0x12);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Replaces a single slot of a window. A `window_id` and `slot` of -1 set the item the cursor is carrying.
+#[derive(Debug, Clone)]
+pub struct SetContainerSlot {
+    pub window_id: i8,
+    pub state_id: VarInt,
+    pub slot: i16,
+    pub item: Slot,
+}
+
+serialize!(SetContainerSlot => [window_id, state_id, slot, item]);
+impl Packet for SetContainerSlot {
+    const ID: crate::model::VarInt = VarInt(0x14);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Opens a window for a container, like a chest.
+#[derive(Debug, Clone)]
+pub struct OpenScreen {
+    pub window_id: VarInt,
+    /// The id of the kind of window in the `minecraft:menu` registry, like [`OpenScreen::GENERIC_9X3`].
+    pub kind: VarInt,
+    pub title: Json<ChatComponent>,
+}
+
+impl OpenScreen {
+    /// Single and double chests.
+    pub const GENERIC_9X3: VarInt = VarInt(2);
+    pub const GENERIC_9X6: VarInt = VarInt(5);
+}
+
+serialize!(OpenScreen => [window_id, kind, title]);
+impl Packet for OpenScreen {
+    const ID: crate::model::VarInt = VarInt(0x30);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Closes the window the client has open. Also sent by the client when the player closes it.
+#[derive(Debug, Clone)]
+pub struct CloseContainer {
+    pub window_id: u8,
+}
+
+serialize!(CloseContainer => [window_id]);
+impl Packet for CloseContainer {
+    const ID: crate::model::VarInt = VarInt(0x11);
+    const STATE: crate::model::State = State::Play;
+}
+
+#[derive(Debug, Clone)]
+pub struct CloseContainerServerbound {
+    pub window_id: u8,
+}
+
+impl_ser!(|PacketContext| CloseContainerServerbound => [window_id]);
+impl Packet for CloseContainerServerbound {
+    const ID: crate::model::VarInt = VarInt(0x0c);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Changes which hotbar slot is selected.
+#[derive(Debug, Clone)]
+pub struct SetHeldItem {
+    pub slot: i8,
+}
+
+serialize!(SetHeldItem => [slot]);
+impl Packet for SetHeldItem {
+    const ID: crate::model::VarInt = VarInt(0x4d);
+    const STATE: crate::model::State = State::Play;
+}
+
+#[derive(Debug, Clone)]
+pub struct SetHeldItemServerbound {
+    pub slot: i16,
+}
+
+impl_ser!(|PacketContext| SetHeldItemServerbound => [slot]);
+impl Packet for SetHeldItemServerbound {
+    const ID: crate::model::VarInt = VarInt(0x28);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// A slot the client changed with a click, and what it thinks is in it now.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangedSlot {
+    pub slot: i16,
+    pub item: Slot,
+}
+
+impl_ser!(ChangedSlot => [slot, item]);
+
+/// Sent when the player clicks in a window. What `button` means depends on the `mode`,
+/// see <https://wiki.vg/Protocol#Click_Container>.
+#[derive(Debug, Clone)]
+pub struct ClickContainer {
+    pub window_id: u8,
+    /// The state id of the contents the player clicked on.
+    pub state_id: VarInt,
+    /// -999 for clicks outside of the window.
+    pub slot: i16,
+    pub button: i8,
+    pub mode: VarInt,
+    /// What the client predicts the click changed.
+    pub changed_slots: Array<ChangedSlot>,
+    pub carried: Slot,
+}
+
+impl ClickContainer {
+    pub const PICKUP: VarInt = VarInt(0);
+    pub const QUICK_MOVE: VarInt = VarInt(1);
+    pub const SWAP: VarInt = VarInt(2);
+    pub const CLONE: VarInt = VarInt(3);
+    pub const THROW: VarInt = VarInt(4);
+    pub const QUICK_CRAFT: VarInt = VarInt(5);
+    pub const PICKUP_ALL: VarInt = VarInt(6);
+
+    /// The slot of clicks outside of the window.
+    pub const OUTSIDE: i16 = -999;
+}
+
+impl_ser!(|PacketContext| ClickContainer => [window_id, state_id, slot, button, mode, changed_slots, carried]);
+impl Packet for ClickContainer {
+    const ID: crate::model::VarInt = VarInt(0x0b);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Sent in creative mode when the player puts an item into a slot of their inventory, or drops it with a slot of -1.
+/// The client is trusted with what the item is.
+#[derive(Debug, Clone)]
+pub struct SetCreativeModeSlot {
+    pub slot: i16,
+    pub item: Slot,
+}
+
+impl_ser!(|PacketContext| SetCreativeModeSlot => [slot, item]);
+impl Packet for SetCreativeModeSlot {
+    const ID: crate::model::VarInt = VarInt(0x2b);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// The contents of a chunk and its light. Chunks have to be sent before the client shows anything in them.
+#[derive(Debug, Clone)]
+pub struct ChunkDataAndUpdateLight {
+    pub x: i32,
+    pub z: i32,
+    pub data: ChunkData,
+    pub light: LightData,
+}
+
+serialize!(ChunkDataAndUpdateLight => [x, z, data, light]);
+impl Packet for ChunkDataAndUpdateLight {
+    const ID: crate::model::VarInt = VarInt(0x24);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Changes the light of a chunk the client already has.
+#[derive(Debug, Clone)]
+pub struct UpdateLight {
+    pub x: VarInt,
+    pub z: VarInt,
+    pub light: LightData,
+}
+
+serialize!(UpdateLight => [x, z, light]);
+impl Packet for UpdateLight {
+    const ID: crate::model::VarInt = VarInt(0x27);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Tells the client to forget a chunk.
+#[derive(Debug, Clone)]
+pub struct UnloadChunk {
+    pub x: i32,
+    pub z: i32,
+}
+
+serialize!(UnloadChunk => [x, z]);
+impl Packet for UnloadChunk {
+    const ID: crate::model::VarInt = VarInt(0x1e);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// The chunk the player is in. The client ignores chunks outside of its view distance from it.
+#[derive(Debug, Clone)]
+pub struct SetCenterChunk {
+    pub x: VarInt,
+    pub z: VarInt,
+}
+
+serialize!(SetCenterChunk => [x, z]);
+impl Packet for SetCenterChunk {
+    const ID: crate::model::VarInt = VarInt(0x4e);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Sent by the client when it joins and whenever its settings change.
+#[derive(Debug, Clone)]
+pub struct ClientInformation {
+    pub locale: FixedStr<16>,
+    /// In chunks, how far the client wants to see.
+    pub view_distance: i8,
+    pub chat_mode: VarInt,
+    pub chat_colors: bool,
+    /// Bits for the cape, jacket, sleeves, pants legs and hat.
+    pub displayed_skin_parts: u8,
+    pub main_hand: VarInt,
+    pub enable_text_filtering: bool,
+    pub allow_server_listings: bool,
+}
+
+impl_ser!(|PacketContext| ClientInformation => [locale, view_distance, chat_mode, chat_colors, displayed_skin_parts, main_hand, enable_text_filtering, allow_server_listings]);
+impl Packet for ClientInformation {
+    const ID: crate::model::VarInt = VarInt(0x08);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Changes a single block.
+#[derive(Debug, Clone)]
+pub struct BlockUpdate {
+    pub location: Position,
+    /// The id of the new block state.
+    pub block_id: VarInt,
+}
+
+serialize!(BlockUpdate => [location, block_id]);
+impl Packet for BlockUpdate {
+    const ID: crate::model::VarInt = VarInt(0x0a);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Changes several blocks in the same chunk section.
+#[derive(Debug, Clone)]
+pub struct UpdateSectionBlocks {
+    /// The position of the section, in sections.
+    pub section: [i32; 3],
+    /// The x, y and z of the blocks in the section, from 0 to 15, and the ids of their new states.
+    pub blocks: Vec<([u8; 3], VarInt)>,
+}
+
+impl Serialize for UpdateSectionBlocks {
+    fn serialize_to(&self, buf: &mut bytes::BytesMut) -> Result<(), crate::error::Error> {
+        try {
+            let [x, y, z] = self.section.map(i64::from);
+            buf.put_i64((x & 0x3fffff) << 42 | (z & 0x3fffff) << 20 | (y & 0xfffff));
+            VarInt(self.blocks.len() as i32).serialize_to(buf)?;
+            for ([x, y, z], state) in &self.blocks {
+                let position = i64::from(*x) << 8 | i64::from(*z) << 4 | i64::from(*y);
+                write_var_long(i64::from(state.0) << 12 | position, buf);
+            }
+        }
+    }
+}
+
+impl Packet for UpdateSectionBlocks {
+    const ID: crate::model::VarInt = VarInt(0x43);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Tells the client that the server is done with the block changes it predicted, up to the sequence number.
+/// Until then, the client ignores block updates for what it predicted.
+#[derive(Debug, Clone)]
+pub struct AcknowledgeBlockChange {
+    pub sequence: VarInt,
+}
+
+serialize!(AcknowledgeBlockChange => [sequence]);
+impl Packet for AcknowledgeBlockChange {
+    const ID: crate::model::VarInt = VarInt(0x06);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Shows the cracks of a block being broken, for anyone but the player breaking it.
+#[derive(Debug, Clone)]
+pub struct SetBlockDestroyStage {
+    /// The entity breaking the block, the client shows one block being broken per entity.
+    pub entity_id: VarInt,
+    pub location: Position,
+    /// 0 to 9, anything else removes the cracks.
+    pub stage: u8,
+}
+
+serialize!(SetBlockDestroyStage => [entity_id, location, stage]);
+impl Packet for SetBlockDestroyStage {
+    const ID: crate::model::VarInt = VarInt(0x07);
+    const STATE: crate::model::State = State::Play;
+    const ESSENTIAL: bool = false;
+}
+
+/// Sent when the player starts, stops or finishes breaking a block, and for the other `status`es in the constants.
+#[derive(Debug, Clone)]
+pub struct PlayerAction {
+    pub status: VarInt,
+    pub location: Position,
+    /// The face of the block, one of [`UseItemOn::FACES`].
+    pub face: i8,
+    /// Sent back in [`AcknowledgeBlockChange`].
+    pub sequence: VarInt,
+}
+
+impl PlayerAction {
+    pub const START_DIGGING: VarInt = VarInt(0);
+    pub const CANCEL_DIGGING: VarInt = VarInt(1);
+    pub const FINISH_DIGGING: VarInt = VarInt(2);
+    pub const DROP_ITEM_STACK: VarInt = VarInt(3);
+    pub const DROP_ITEM: VarInt = VarInt(4);
+    /// Shooting an arrow or finishing eating.
+    pub const RELEASE_USE_ITEM: VarInt = VarInt(5);
+    pub const SWAP_ITEM_IN_HAND: VarInt = VarInt(6);
+}
+
+impl_ser!(|PacketContext| PlayerAction => [status, location, face, sequence]);
+impl Packet for PlayerAction {
+    const ID: crate::model::VarInt = VarInt(0x1d);
+    const STATE: crate::model::State = State::Play;
+}
+
+/// Sent when the player right clicks a block, which places blocks among other things.
+#[derive(Debug, Clone)]
+pub struct UseItemOn {
+    pub hand: VarInt,
+    pub location: Position,
+    pub face: VarInt,
+    /// Where on the face the block was clicked, from 0 to 1.
+    pub cursor_x: f32,
+    pub cursor_y: f32,
+    pub cursor_z: f32,
+    /// Whether the player's head is inside the block.
+    pub inside_block: bool,
+    /// Sent back in [`AcknowledgeBlockChange`].
+    pub sequence: VarInt,
+}
+
+impl UseItemOn {
+    pub const MAIN_HAND: VarInt = VarInt(0);
+    pub const OFF_HAND: VarInt = VarInt(1);
+
+    /// The direction each face of a block points in, by their id.
+    pub const FACES: [[i32; 3]; 6] = [
+        [0, -1, 0],
+        [0, 1, 0],
+        [0, 0, -1],
+        [0, 0, 1],
+        [-1, 0, 0],
+        [1, 0, 0],
+    ];
+}
+
+impl_ser!(|PacketContext| UseItemOn => [
+    hand,
+    location,
+    face,
+    cursor_x,
+    cursor_y,
+    cursor_z,
+    inside_block,
+    sequence
+]);
+impl Packet for UseItemOn {
+    const ID: crate::model::VarInt = VarInt(0x31);
+    const STATE: crate::model::State = State::Play;
+}
+
 /// This packet tells the client that it should enable support for the feature flags listed in the `feature_flags` field.
 /// Note that adding arbitrary identifiers (instead of the constants under this struct,
 /// like `FEATURE_VANILLA` or `FEATURE_BUNDLE`) to the list may cause the client to explode.
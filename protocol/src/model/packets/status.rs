@@ -51,7 +51,9 @@ pub struct StatusResponseJson {
     pub version: Version,
     pub players: Players,
     pub description: ChatComponent,
-    pub favicon: String,
+    /// A 64×64 PNG image, as a `data:image/png;base64,...` URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub favicon: Option<String>,
     pub enforces_secure_chat: bool,
     pub previews_chat: bool,
 }
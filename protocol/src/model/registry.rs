@@ -13,6 +13,16 @@ impl<T: RegistryItem> Default for Registry<T> {
     }
 }
 
+impl<T: RegistryItem> Registry<T> {
+    /// The id the entry is sent with, which is its position in the map.
+    pub fn id(&self, name: &str) -> Option<i32> {
+        self.0
+            .keys()
+            .position(|key| key == name)
+            .and_then(|id| id.try_into().ok())
+    }
+}
+
 pub trait RegistryItem {
     const REGISTRY: &'static str;
 }
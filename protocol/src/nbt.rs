@@ -26,7 +26,7 @@ pub enum Label {
     InvalidTagType(u8),
 }
 
-#[derive(Debug, Clone, derive_more::From)]
+#[derive(Debug, Clone, PartialEq, derive_more::From)]
 pub enum Nbt {
     Byte(i8),
     Short(i16),
@@ -169,6 +169,26 @@ impl Nbt {
             }
         }
     }
+
+    /// Serializes `self` as the root tag of an NBT value sent in packets, which has an empty name.
+    pub fn serialize_root(&self, buf: &mut bytes::BytesMut) -> Result<(), crate::error::Error> {
+        NbtNamed::serialize_named(self.tag(), "", self, buf)
+    }
+
+    /// Parses the root tag of an NBT value sent in packets, `None` if it's just `TAG_End`, which stands for no NBT at all.
+    #[parser(extras = "Extra<()>")]
+    pub fn root(input: &[u8]) -> Option<Self> {
+        match NbtTag::named(input)? {
+            NbtTag::End => Ok(None),
+            NbtTag::Named(NbtNamed { value, .. }) => Ok(Some(value)),
+            NbtTag::List(NbtList { tags, .. }) => {
+                Err(crate::error::Error::Nbt(NbtError::Expected {
+                    expected: NbtExpected::NamedTag,
+                    actual: Nbt::List(tags),
+                }))
+            }
+        }
+    }
 }
 
 impl Serialize for [Nbt] {
@@ -177,7 +197,74 @@ impl Serialize for [Nbt] {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Formats the value as SNBT, the text format used in commands and hover events, like `{Count:1b}`.
+impl std::fmt::Display for Nbt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn string(f: &mut std::fmt::Formatter<'_>, string: &str) -> std::fmt::Result {
+            f.write_str("\"")?;
+            for c in string.chars() {
+                if matches!(c, '"' | '\\') {
+                    f.write_str("\\")?;
+                }
+                write!(f, "{c}")?;
+            }
+            f.write_str("\"")
+        }
+
+        fn list<T>(
+            f: &mut std::fmt::Formatter<'_>,
+            prefix: &str,
+            items: &[T],
+            mut item: impl FnMut(&mut std::fmt::Formatter<'_>, &T) -> std::fmt::Result,
+        ) -> std::fmt::Result {
+            write!(f, "[{prefix}")?;
+            for (i, value) in items.iter().enumerate() {
+                if i != 0 {
+                    f.write_str(",")?;
+                }
+                item(f, value)?;
+            }
+            f.write_str("]")
+        }
+
+        match self {
+            Self::Byte(b) => write!(f, "{b}b"),
+            Self::Short(s) => write!(f, "{s}s"),
+            Self::Int(i) => write!(f, "{i}"),
+            Self::Long(l) => write!(f, "{l}L"),
+            Self::Float(x) => write!(f, "{x}f"),
+            Self::Double(x) => write!(f, "{x}d"),
+            Self::String(s) => string(f, s),
+            Self::ByteArray(bytes) => list(f, "B;", bytes, |f, b| write!(f, "{b}b")),
+            Self::IntArray(ints) => list(f, "I;", ints, |f, i| write!(f, "{i}")),
+            Self::LongArray(longs) => list(f, "L;", longs, |f, l| write!(f, "{l}L")),
+            Self::List(tags) | Self::ListTyped(NbtList { tags, .. }) => {
+                list(f, "", tags, |f, tag| write!(f, "{tag}"))
+            }
+            Self::Compound(compound) => {
+                f.write_str("{")?;
+                for (i, (name, value)) in compound.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(",")?;
+                    }
+                    let bare = !name.is_empty()
+                        && name.chars().all(|c| {
+                            c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+')
+                        });
+                    if bare {
+                        f.write_str(name)?;
+                    } else {
+                        string(f, name)?;
+                    }
+                    write!(f, ":{value}")?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 #[doc(hidden)]
 pub struct NbtList {
     pub tag: NbtTagType,
@@ -354,6 +441,44 @@ impl<T: Serialize> Serialize for SmolArray<T> {
     }
 }
 
+/// A named root compound, which is how NBT files (like `level.dat` or player data) are laid out.
+/// Wrap it in [`Compress`] with [`Gzip`] to read or write such files.
+#[derive(Debug, Clone, Default)]
+pub struct NbtRoot {
+    pub name: String,
+    pub compound: HashMap<String, Nbt>,
+}
+
+impl Serialize for NbtRoot {
+    fn serialize_to(&self, buf: &mut BytesMut) -> Result<(), crate::error::Error> {
+        try {
+            buf.put_u8(NbtTagType::Compound as _);
+            buf.put_u16(self.name.len().try_into().expect("usize > u16"));
+            buf.put_slice(self.name.as_bytes());
+            Nbt::serialize_compound(&self.compound, buf)?
+        }
+    }
+}
+
+impl Deserialize for NbtRoot {
+    #[parser(extras = "Extra<Self::Context>")]
+    fn deserialize(input: &[u8]) -> Self {
+        match NbtTag::named(input)? {
+            NbtTag::Named(NbtNamed {
+                name,
+                value: Nbt::Compound(compound),
+                ..
+            }) => Ok(Self { name, compound }),
+            NbtTag::Named(NbtNamed { value, .. }) => Err(NbtError::Expected {
+                expected: NbtExpected::Type(NbtTagType::Compound),
+                actual: value,
+            }
+            .into()),
+            NbtTag::End | NbtTag::List(_) => Err(NbtError::ExpectedAnythingButEnd.into()),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct NbtSerde<T>(pub T);
 
@@ -467,6 +592,25 @@ mod tests {
             test_nbt(Nbt::List(list), &[NbtTagType::Int as u8, 0x0, 0x0, 0x0, 0x3, 0xf, 0xee, 0xdb, 0xee, 0x0f, 0xca, 0xfe, 0xba, 0xb, 0xe0, 0x00, 0x00])
         }
     }
+
+    #[test]
+    fn snbt() {
+        let nbt = Nbt::Compound(
+            [
+                (
+                    "display name".to_string(),
+                    Nbt::String(r#"say "hi""#.to_string()),
+                ),
+                ("ids".to_string(), Nbt::IntArray(vec![1, 2])),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        assert_eq!(
+            nbt.to_string(),
+            r#"{"display name":"say \"hi\"",ids:[I;1,2]}"#
+        );
+    }
 }
 
 struct NbtSer;
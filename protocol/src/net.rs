@@ -0,0 +1,119 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use bevy::prelude::Resource;
+
+use crate::{model::packets::SerializedPacket, ser::Zlib};
+
+/// What a [`PlayerNet`](crate::PlayerNet) does when its send queue is full,
+/// i.e. when the client doesn't read packets as fast as the server produces them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(crate = "serde", rename_all = "snake_case")]
+pub enum SendPolicy {
+    /// Wait until the send task makes room in the queue.
+    /// Packets queued without waiting are held back in order until then.
+    Block,
+    /// Silently drop packets that aren't [essential](crate::model::packets::Packet::ESSENTIAL),
+    /// and wait for room for the ones that are.
+    #[default]
+    DropNonEssential,
+    /// Disconnect the player with [`Error::QueueFull`](crate::error::Error::QueueFull).
+    Disconnect,
+}
+
+/// What a [`PlayerNet`](crate::PlayerNet) does when its receive queue is full,
+/// i.e. when the client sends packets faster than the server handles them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(crate = "serde", rename_all = "snake_case")]
+pub enum RecvPolicy {
+    /// Stop reading from the socket until the queue has room again,
+    /// leaving the rest to TCP flow control.
+    #[default]
+    Block,
+    /// Disconnect the player with [`Error::QueueFull`](crate::error::Error::QueueFull).
+    Disconnect,
+}
+
+/// Queue sizes, backpressure policies and compression settings for a [`PlayerNet`](crate::PlayerNet).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource)]
+pub struct NetConfig {
+    /// How many packets can wait to be written to the socket. Anything below 1 is raised to 1.
+    pub send_capacity: usize,
+    /// How many packets can wait to be handled by the server. Anything below 1 is raised to 1.
+    pub recv_capacity: usize,
+    pub send_policy: SendPolicy,
+    pub recv_policy: RecvPolicy,
+    /// If enabled, packets are held back until [`PlayerNet::flush`](crate::PlayerNet::flush) is called
+    /// (which [`ProtocolPlugin`](crate::ProtocolPlugin) does at the end of every tick),
+    /// instead of being written as soon as the send queue runs dry.
+    pub flush_on_tick: bool,
+    /// How many bytes the send task buffers before writing them out, regardless of [`flush_on_tick`](Self::flush_on_tick).
+    pub max_batch_bytes: usize,
+    /// The zlib settings for packets above the compression threshold.
+    pub compression: Zlib,
+}
+
+impl Default for NetConfig {
+    fn default() -> Self {
+        Self {
+            send_capacity: 4096,
+            recv_capacity: 256,
+            send_policy: SendPolicy::default(),
+            recv_policy: RecvPolicy::default(),
+            flush_on_tick: false,
+            max_batch_bytes: 256 * 1024,
+            compression: Zlib::default(),
+        }
+    }
+}
+
+/// A packet waiting in the send queue of a [`PlayerNet`](crate::PlayerNet).
+#[derive(Debug, Clone)]
+pub struct QueuedPacket {
+    /// Whether the packet should be compressed (only if compression is enabled on the connection).
+    pub compress: bool,
+    /// Whether the packet can be dropped under [`SendPolicy::DropNonEssential`].
+    pub essential: bool,
+    pub packet: SerializedPacket,
+}
+
+/// Per-connection counters, shared between a [`PlayerNet`](crate::PlayerNet) and its tasks.
+#[derive(Debug, Default)]
+pub struct NetMetrics {
+    pub(crate) bytes_in_flight: AtomicUsize,
+    pub(crate) bytes_sent: AtomicU64,
+    pub(crate) bytes_received: AtomicU64,
+    pub(crate) dropped_packets: AtomicU64,
+}
+
+impl NetMetrics {
+    /// Bytes of packet data that were queued, but not yet written to the socket.
+    pub fn bytes_in_flight(&self) -> usize {
+        self.bytes_in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Total bytes written to the socket.
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    /// Total bytes read from the socket.
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    /// Packets dropped because of [`SendPolicy::DropNonEssential`].
+    pub fn dropped_packets(&self) -> u64 {
+        self.dropped_packets.load(Ordering::Relaxed)
+    }
+}
+
+/// A point-in-time copy of a connection's queue depths and [`NetMetrics`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetMetricsSnapshot {
+    pub send_queue_depth: usize,
+    pub recv_queue_depth: usize,
+    pub bytes_in_flight: usize,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub dropped_packets: u64,
+}
@@ -538,6 +538,35 @@ impl Deserialize for Position {
     }
 }
 
+/// A rotation in steps of 1/256 of a full turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Angle(pub u8);
+
+impl Angle {
+    pub fn from_degrees(degrees: f32) -> Self {
+        // wraps around, so that -90 is the same as 270
+        Self((degrees / 360.0 * 256.0).floor() as i32 as u8)
+    }
+
+    pub fn to_degrees(self) -> f32 {
+        f32::from(self.0) / 256.0 * 360.0
+    }
+}
+
+impl Serialize for Angle {
+    fn serialize_to(&self, buf: &mut BytesMut) -> Result<(), crate::error::Error> {
+        self.0.serialize_to(buf)
+    }
+}
+
+impl Deserialize for Angle {
+    fn deserialize<'a>(
+        input: &mut Input<&'a [u8], Extra<Self::Context>>,
+    ) -> PResult<&'a [u8], Self, Extra<Self::Context>> {
+        Ok(Self(input.next()?))
+    }
+}
+
 impl Serialize for Bytes {
     fn serialize_to(&self, buf: &mut BytesMut) -> Result<(), crate::error::Error> {
         try { buf.put_slice(&self) }
@@ -569,53 +598,141 @@ impl Deserialize for IndexMap<String, Nbt> {
     }
 }
 
-#[derive(Default, Clone, Copy, Debug)]
-pub struct Zlib;
-#[derive(Default, Clone, Copy, Debug)]
-pub struct Zstd;
-#[derive(Default, Clone, Copy, Debug)]
-pub struct Gzip;
+/// zlib (DEFLATE with a zlib header), used for packet compression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Zlib {
+    /// 0 (no compression) ..= 9 (best compression).
+    pub level: u32,
+}
+/// Zstandard, used for our own on-disk formats, like the chunk cache and packet captures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Zstd {
+    /// 1 (fastest) ..= 22 (best compression), or negative for even faster modes.
+    pub level: i32,
+}
+/// gzip (DEFLATE with a gzip header), used by vanilla for `level.dat`, player data and other NBT files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Gzip {
+    /// 0 (no compression) ..= 9 (best compression).
+    pub level: u32,
+}
+
+impl Zlib {
+    pub const fn new(level: u32) -> Self {
+        Self { level }
+    }
+}
+impl Zstd {
+    pub const fn new(level: i32) -> Self {
+        Self { level }
+    }
+}
+impl Gzip {
+    pub const fn new(level: u32) -> Self {
+        Self { level }
+    }
+}
+
+impl Default for Zlib {
+    fn default() -> Self {
+        Self::new(flate2::Compression::default().level())
+    }
+}
+impl Default for Zstd {
+    fn default() -> Self {
+        Self::new(zstd::DEFAULT_COMPRESSION_LEVEL)
+    }
+}
+impl Default for Gzip {
+    fn default() -> Self {
+        Self::new(flate2::Compression::default().level())
+    }
+}
+
 pub trait Compression {
-    fn encode(data: &[u8]) -> Result<Bytes, crate::error::Error>;
+    fn encode(&self, data: &[u8]) -> Result<Bytes, crate::error::Error>;
     fn encode_serialize<T: Serialize>(
+        &self,
         thing: &T,
         buf: &mut BytesMut,
     ) -> Result<(), crate::error::Error>;
     fn decode(data: &[u8]) -> Result<Bytes, crate::error::Error>;
 }
+
+/// An [`io::Write`](std::io::Write) that appends to a [`BufMut`] it doesn't own.
+struct WriterMut<'a, B: BufMut>(&'a mut B);
+impl<'a, B: BufMut> std::io::Write for WriterMut<'a, B> {
+    fn write(&mut self, src: &[u8]) -> std::io::Result<usize> {
+        let n = std::cmp::min(self.0.remaining_mut(), src.len());
+
+        self.0.put(&src[0..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 impl Compression for Zlib {
-    fn encode(data: &[u8]) -> Result<Bytes, crate::error::Error> {
+    fn encode(&self, data: &[u8]) -> Result<Bytes, crate::error::Error> {
         use std::io::Write;
         let mut enc = flate2::write::ZlibEncoder::new(
             BytesMut::new().writer(),
-            flate2::Compression::default(),
+            flate2::Compression::new(self.level),
         );
         enc.write_all(data)?;
         Ok(enc.finish()?.into_inner().freeze())
     }
 
     fn encode_serialize<T: Serialize>(
+        &self,
         thing: &T,
         buf: &mut BytesMut,
     ) -> Result<(), crate::error::Error> {
-        use std::io::{self, Write};
+        use std::io::Write;
 
-        struct WriterMut<'a, B: BufMut>(&'a mut B);
-        impl<'a, B: BufMut> Write for WriterMut<'a, B> {
-            fn write(&mut self, src: &[u8]) -> io::Result<usize> {
-                let n = std::cmp::min(self.0.remaining_mut(), src.len());
+        let mut enc =
+            flate2::write::ZlibEncoder::new(WriterMut(buf), flate2::Compression::new(self.level));
 
-                self.0.put(&src[0..n]);
-                Ok(n)
-            }
+        let mut bmut = BytesMut::new();
+        thing.serialize_to(&mut bmut)?;
+        enc.write_all(&bmut)?;
 
-            fn flush(&mut self) -> io::Result<()> {
-                Ok(())
-            }
-        }
+        enc.finish()?;
+
+        Ok(())
+    }
+
+    fn decode(data: &[u8]) -> Result<Bytes, crate::error::Error> {
+        use std::io::Read;
+        let mut dec = flate2::read::ZlibDecoder::new(std::io::Cursor::new(data));
+        let mut buf = Vec::new();
+        dec.read_to_end(&mut buf)?;
+        Ok(Bytes::from(buf))
+    }
+}
+
+impl Compression for Gzip {
+    fn encode(&self, data: &[u8]) -> Result<Bytes, crate::error::Error> {
+        use std::io::Write;
+        let mut enc = flate2::write::GzEncoder::new(
+            BytesMut::new().writer(),
+            flate2::Compression::new(self.level),
+        );
+        enc.write_all(data)?;
+        Ok(enc.finish()?.into_inner().freeze())
+    }
+
+    fn encode_serialize<T: Serialize>(
+        &self,
+        thing: &T,
+        buf: &mut BytesMut,
+    ) -> Result<(), crate::error::Error> {
+        use std::io::Write;
 
         let mut enc =
-            flate2::write::ZlibEncoder::new(WriterMut(buf), flate2::Compression::default());
+            flate2::write::GzEncoder::new(WriterMut(buf), flate2::Compression::new(self.level));
 
         let mut bmut = BytesMut::new();
         thing.serialize_to(&mut bmut)?;
@@ -628,18 +745,42 @@ impl Compression for Zlib {
 
     fn decode(data: &[u8]) -> Result<Bytes, crate::error::Error> {
         use std::io::Read;
-        let mut dec = flate2::read::ZlibDecoder::new(std::io::Cursor::new(data));
+        // files written by some tools consist of several gzip members, so read all of them.
+        let mut dec = flate2::read::MultiGzDecoder::new(std::io::Cursor::new(data));
         let mut buf = Vec::new();
         dec.read_to_end(&mut buf)?;
         Ok(Bytes::from(buf))
     }
 }
+
+impl Compression for Zstd {
+    fn encode(&self, data: &[u8]) -> Result<Bytes, crate::error::Error> {
+        Ok(Bytes::from(zstd::stream::encode_all(data, self.level)?))
+    }
+
+    fn encode_serialize<T: Serialize>(
+        &self,
+        thing: &T,
+        buf: &mut BytesMut,
+    ) -> Result<(), crate::error::Error> {
+        let mut bmut = BytesMut::new();
+        thing.serialize_to(&mut bmut)?;
+        zstd::stream::copy_encode(&bmut[..], WriterMut(buf), self.level)?;
+
+        Ok(())
+    }
+
+    fn decode(data: &[u8]) -> Result<Bytes, crate::error::Error> {
+        Ok(Bytes::from(zstd::stream::decode_all(data)?))
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Compress<T, C: Compression = Zlib>(pub T, pub C);
 
 impl<T: Serialize, C: Compression> Serialize for Compress<T, C> {
     fn serialize_to(&self, buf: &mut BytesMut) -> Result<(), crate::error::Error> {
-        C::encode_serialize(&self.0, buf)
+        self.1.encode_serialize(&self.0, buf)
     }
 }
 
@@ -649,3 +790,34 @@ impl<T: Deserialize<Context = ()>, C: Compression + Default> Compress<T, C> {
         Ok(Self(T::deserialize.parse(&buffer)?, C::default()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::NbtRoot;
+
+    fn roundtrip<C: Compression + Default>(codec: C) {
+        let root = NbtRoot {
+            name: String::new(),
+            compound: IndexMap::from([
+                ("LevelName".to_string(), Nbt::String("world".to_string())),
+                ("version".to_string(), Nbt::Int(19133)),
+            ]),
+        };
+
+        let compressed = Compress(&root, codec).serialize().unwrap();
+        let Compress(decompressed, _) = Compress::<NbtRoot, C>::decompress(&compressed).unwrap();
+
+        assert_eq!(format!("{root:?}"), format!("{decompressed:?}"));
+    }
+
+    #[test]
+    fn compression_roundtrip() {
+        roundtrip(Zlib::default());
+        roundtrip(Zlib::new(9));
+        roundtrip(Gzip::default());
+        roundtrip(Gzip::new(1));
+        roundtrip(Zstd::default());
+        roundtrip(Zstd::new(19));
+    }
+}
@@ -0,0 +1,51 @@
+//! Fixtures for tests that need a [`PlayerNet`] with a client on the other end.
+
+use std::time::Duration;
+
+use aott::prelude::{Input, Parser};
+use tokio::{
+    io::AsyncReadExt,
+    net::{TcpListener, TcpSocket, TcpStream},
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{model::packets::SerializedPacket, net::NetConfig, ser::Deserialize, PlayerNet};
+
+/// A connection made with `config`, and the client's end of it.
+/// The client has a small receive buffer, so that it's quickly full if nothing reads from it.
+pub async fn connect(config: NetConfig) -> (PlayerNet, TcpStream) {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+    let socket = TcpSocket::new_v4().unwrap();
+    socket.set_recv_buffer_size(4096).unwrap();
+    let client = socket
+        .connect(listener.local_addr().unwrap())
+        .await
+        .unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+    let (read, write) = server.into_split();
+    let net = PlayerNet::new(read, write, CancellationToken::new(), None, config);
+    (net, client)
+}
+
+/// The uncompressed packets the client got until nothing arrived for a while, in order.
+pub async fn received(client: &mut TcpStream) -> Vec<SerializedPacket> {
+    let mut bytes = vec![];
+    let mut buf = [0; 4096];
+    while let Ok(Ok(read @ 1..)) =
+        tokio::time::timeout(Duration::from_millis(200), client.read(&mut buf)).await
+    {
+        bytes.extend_from_slice(&buf[..read]);
+    }
+
+    let bytes = &bytes[..];
+    let mut input = Input::new(&bytes);
+    let mut packets = vec![];
+    while input.offset < bytes.len() {
+        packets.push(
+            SerializedPacket::deserialize
+                .parse_with(&mut input)
+                .unwrap(),
+        );
+    }
+    packets
+}
@@ -1,16 +1,36 @@
-use oxcr_cli::{flag_list, Extra, FlagName, ParseError};
+use oxcr_cli::{flag_list, Expectation, Extra, FlagName, ParseError};
 use oxcr_protocol::{
     aott::{
-        self,
+        self, pfn_type,
         prelude::{Parser, *},
+        text::inline_whitespace,
     },
     miette::{self, bail, IntoDiagnostic},
 };
-use std::borrow::Cow;
+use std::{borrow::Cow, net::IpAddr, num::ParseIntError, path::PathBuf, str::FromStr};
 
+use crate::config::Config;
+
+/// Command line flags. Everything but the config path overrides the value from the config file.
 #[derive(Debug, Clone)]
 pub struct Cli {
-    pub port: u16,
+    pub config: PathBuf,
+    pub port: Option<u16>,
+    pub bind: Option<IpAddr>,
+    pub max_players: Option<u32>,
+    pub view_distance: Option<u8>,
+}
+
+impl Default for Cli {
+    fn default() -> Self {
+        Self {
+            config: PathBuf::from("server.toml"),
+            port: None,
+            bind: None,
+            max_players: None,
+            view_distance: None,
+        }
+    }
 }
 
 impl Cli {
@@ -26,11 +46,57 @@ impl Cli {
             }
         }
     }
+
+    /// Overrides the values in `config` with the ones given on the command line.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(port) = self.port {
+            config.network.port = port;
+        }
+        if let Some(bind) = self.bind {
+            config.network.bind = bind;
+        }
+        if let Some(max_players) = self.max_players {
+            config.status.max_players = max_players;
+        }
+        if let Some(view_distance) = self.view_distance {
+            config.world.view_distance = view_distance;
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 enum Flag {
     Port(u16),
+    Bind(IpAddr),
+    Config(PathBuf),
+    MaxPlayers(u32),
+    ViewDistance(u8),
+}
+
+fn number<'a, T: FromStr<Err = ParseIntError>>(help: &'static str) -> pfn_type!(&'a str, T, Extra) {
+    move |input| {
+        text::int(10)
+            .try_map(move |int: &str, extra| {
+                int.parse::<T>()
+                    .map_err(|error| ParseError::ExpectedNumber {
+                        radix: 10,
+                        actual: int.to_owned(),
+                        at: Into::<miette::SourceSpan>::into(extra.span()),
+                        help: Some(Cow::Borrowed(help)),
+                        error,
+                    })
+            })
+            .parse_with(input)
+    }
+}
+
+/// Anything up to the next whitespace.
+#[parser(extras = Extra)]
+fn value(input: &str) -> &'a str {
+    filter(|ch: &char| !ch.is_whitespace(), Expectation::FlagValue)
+        .repeated()
+        .slice()
+        .parse_with(input)
 }
 
 #[parser(extras = Extra)]
@@ -45,15 +111,34 @@ fn flags(input: &str) -> Vec<Flag> {
                     FlagName::Short("p") | FlagName::Long("port") => {
                         one_of(" =")(input)?;
 
-                        Flag::Port(text::int(10).try_map(|int: &str, extra| {
-                            int.parse::<u16>().map_err(|error| ParseError::ExpectedNumber {
-                                radix: 10,
-                                actual: int.to_owned(),
-                                at: Into::<miette::SourceSpan>::into(extra.span()),
-                                help: Some(Cow::Borrowed("a port can only be a u16 (0 ... 65535), but your input either didn't fit into a u16 or isn't a number at all.")),
-                                error
-                            })
-                        }).parse_with(input)?)
+                        Flag::Port(number("a port can only be a u16 (0 ... 65535), but your input either didn't fit into a u16 or isn't a number at all.")(input)?)
+                    }
+                    FlagName::Short("b") | FlagName::Long("bind") => {
+                        one_of(" =")(input)?;
+                        let before_value = input.offset;
+                        let actual = value(input)?;
+
+                        Flag::Bind(actual.parse().map_err(|_| ParseError::InvalidValue {
+                            flag: String::from("--bind"),
+                            actual: actual.to_owned(),
+                            at: input.span_since(before_value).into(),
+                            help: Some(Cow::Borrowed("the address to listen on has to be an IPv4 or IPv6 address, like 0.0.0.0 or ::")),
+                        })?)
+                    }
+                    FlagName::Short("c") | FlagName::Long("config") => {
+                        one_of(" =")(input)?;
+
+                        Flag::Config(PathBuf::from(value(input)?))
+                    }
+                    FlagName::Long("max-players") => {
+                        one_of(" =")(input)?;
+
+                        Flag::MaxPlayers(number("the player limit has to be a positive number")(input)?)
+                    }
+                    FlagName::Long("view-distance") => {
+                        one_of(" =")(input)?;
+
+                        Flag::ViewDistance(number("the view distance is a number of chunks, from 2 to 32")(input)?)
                     }
                     FlagName::Short(flag) | FlagName::Long(flag) => Err(ParseError::UnknownFlag {
                         flag: flag.to_owned(),
@@ -71,7 +156,11 @@ fn flags_handle<'a>(
 ) -> PResult<&'a str, (), Extra> {
     fn handle(cli: &mut Cli, flag: Flag) {
         match flag {
-            Flag::Port(port) => cli.port = port,
+            Flag::Port(port) => cli.port = Some(port),
+            Flag::Bind(bind) => cli.bind = Some(bind),
+            Flag::Config(config) => cli.config = config,
+            Flag::MaxPlayers(max_players) => cli.max_players = Some(max_players),
+            Flag::ViewDistance(view_distance) => cli.view_distance = Some(view_distance),
         }
     }
     try { flags(input)?.into_iter().for_each(|flag| handle(cli, flag)) }
@@ -80,9 +169,17 @@ fn flags_handle<'a>(
 #[parser(extras = Extra)]
 pub fn yay(input: &str) -> Cli {
     try {
-        let mut cli = Cli { port: 25565 };
+        let mut cli = Cli::default();
 
-        flags_handle(&mut cli, input)?;
+        loop {
+            inline_whitespace().check_with(input)?;
+
+            if input.offset >= input.input.len() {
+                break;
+            }
+
+            flags_handle(&mut cli, input)?;
+        }
 
         cli
     }
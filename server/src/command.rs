@@ -0,0 +1,645 @@
+//! Commands, and the dispatcher that every way of running them (RCON, the console, players) goes through.
+//!
+//! Commands are trees in the same shape as Brigadier, the library the client uses:
+//! every node is either a literal word or an argument, and a command can be run if it ends at a node with a handler.
+//! The tree is sent to players in the [`Commands`] packet, so the client can highlight and suggest commands by itself.
+
+mod argument;
+mod builtin;
+
+use std::{collections::BTreeMap, hash::BuildHasher, net::SocketAddr};
+
+use bevy::prelude::*;
+use oxcr_protocol::{
+    miette,
+    model::{
+        chat::ChatComponent,
+        command::{self as graph, ArgumentParser},
+        packets::play::Commands,
+        VarInt,
+    },
+    thiserror, PlayerN,
+};
+
+pub use argument::{ArgumentValue, Arguments, Coordinate, Coordinates, EntitySelector};
+pub(crate) use builtin::GAME_MODES;
+
+use crate::{
+    config::Config,
+    model::{Player, PlayerName},
+};
+use argument::{default_suggestions, Reader};
+
+/// Who is running a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandSender {
+    Console,
+    Rcon(SocketAddr),
+    Player(Entity),
+}
+
+impl CommandSender {
+    /// How the sender is called in messages to players.
+    pub fn name(&self, world: &World) -> String {
+        match self {
+            Self::Console => String::from("Server"),
+            Self::Rcon(_) => String::from("Rcon"),
+            Self::Player(entity) => world
+                .get::<PlayerName>(*entity)
+                .map_or_else(String::new, |name| name.0.to_string()),
+        }
+    }
+
+    /// Whether the sender can run commands that are only for operators.
+    pub fn is_op(&self, world: &World) -> bool {
+        match self {
+            Self::Console | Self::Rcon(_) => true,
+            Self::Player(entity) => world
+                .get::<PlayerName>(*entity)
+                .is_some_and(|name| world.resource::<Config>().permissions.is_op(&name.0)),
+        }
+    }
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum CommandError {
+    #[error("Unknown command: {name}")]
+    #[diagnostic(code(server::command::unknown), help("run `help` to see all commands"))]
+    Unknown { name: String },
+    #[error("Incomplete command: {near}<--[HERE]")]
+    #[diagnostic(code(server::command::incomplete), help("usage: {usage}"))]
+    Incomplete { near: String, usage: String },
+    #[error("Unexpected argument: {near}<--[HERE]")]
+    #[diagnostic(code(server::command::unexpected_argument))]
+    UnexpectedArgument { near: String },
+    #[error("Invalid {what}: {actual}")]
+    #[diagnostic(code(server::command::invalid_argument))]
+    InvalidArgument {
+        what: &'static str,
+        actual: String,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("No player named {name} is online")]
+    #[diagnostic(code(server::command::no_such_player))]
+    NoSuchPlayer { name: String },
+    #[error("No player was found")]
+    #[diagnostic(code(server::command::no_players))]
+    NoPlayers,
+    #[error("Only players can use {selector}")]
+    #[diagnostic(code(server::command::requires_player))]
+    RequiresPlayer { selector: &'static str },
+}
+
+/// Runs a command with the arguments that were parsed on the way to the node.
+pub type CommandHandler = fn(&mut CommandContext, &Arguments) -> Result<(), CommandError>;
+
+/// Suggests values for an argument, instead of the suggestions every argument of its kind has.
+pub type CommandSuggester = fn(&mut World) -> Vec<String>;
+
+pub struct CommandContext<'w> {
+    pub world: &'w mut World,
+    pub sender: CommandSender,
+    output: Vec<ChatComponent>,
+}
+
+impl CommandContext<'_> {
+    /// Sends a line of output back to whoever ran the command.
+    pub fn reply(&mut self, message: impl Into<ChatComponent>) {
+        self.output.push(message.into());
+    }
+
+    /// The players an entity argument refers to. Players are the only entities there are, so `@e` is the same as `@a`.
+    pub fn players(&mut self, selector: &EntitySelector) -> Result<Vec<Entity>, CommandError> {
+        let mut players = self
+            .world
+            .query_filtered::<(Entity, &PlayerName), (With<Player>, With<PlayerN>)>();
+        let mut all = players.iter(self.world);
+
+        let selected = match selector {
+            EntitySelector::Name(name) => {
+                return all
+                    .find(|(_, player)| player.0.eq_ignore_ascii_case(name))
+                    .map(|(entity, _)| vec![entity])
+                    .ok_or_else(|| CommandError::NoSuchPlayer { name: name.clone() })
+            }
+            EntitySelector::AllPlayers | EntitySelector::AllEntities => {
+                all.map(|(entity, _)| entity).collect()
+            }
+            EntitySelector::Sender | EntitySelector::NearestPlayer => match self.sender {
+                CommandSender::Player(entity) => vec![entity],
+                _ => {
+                    return Err(CommandError::RequiresPlayer {
+                        selector: if *selector == EntitySelector::Sender {
+                            "@s"
+                        } else {
+                            "@p"
+                        },
+                    })
+                }
+            },
+            EntitySelector::RandomPlayer => {
+                let players = all.map(|(entity, _)| entity).collect::<Vec<_>>();
+                let random =
+                    std::collections::hash_map::RandomState::new().hash_one(players.len()) as usize;
+                players
+                    .get(random.checked_rem(players.len()).unwrap_or_default())
+                    .into_iter()
+                    .copied()
+                    .collect()
+            }
+        };
+
+        if selected.is_empty() {
+            return Err(CommandError::NoPlayers);
+        }
+
+        Ok(selected)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeKind {
+    Literal(&'static str),
+    Argument {
+        name: &'static str,
+        parser: ArgumentParser,
+    },
+}
+
+/// A node of a command tree, built with [`literal`] and [`argument`].
+#[derive(Debug, Clone)]
+pub struct CommandNode {
+    pub kind: NodeKind,
+    pub children: Vec<CommandNode>,
+    pub handler: Option<CommandHandler>,
+    pub suggest: Option<CommandSuggester>,
+    /// Shown by `help`, only used on the top node of a command.
+    pub description: &'static str,
+    /// Whether only operators can see and run this node and everything under it.
+    pub op_only: bool,
+}
+
+/// A node that matches exactly `name`.
+pub fn literal(name: &'static str) -> CommandNode {
+    CommandNode::new(NodeKind::Literal(name))
+}
+
+/// A node that parses an argument with `parser`, and passes it to the handler as `name`.
+pub fn argument(name: &'static str, parser: ArgumentParser) -> CommandNode {
+    CommandNode::new(NodeKind::Argument { name, parser })
+}
+
+impl CommandNode {
+    fn new(kind: NodeKind) -> Self {
+        Self {
+            kind,
+            children: vec![],
+            handler: None,
+            suggest: None,
+            description: "",
+            op_only: false,
+        }
+    }
+
+    pub fn then(mut self, child: CommandNode) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    /// Makes the command runnable if it ends at this node.
+    pub fn executes(mut self, handler: CommandHandler) -> Self {
+        self.handler = Some(handler);
+        self
+    }
+
+    /// Has the client ask the server for suggestions for this argument.
+    pub fn suggests(mut self, suggest: CommandSuggester) -> Self {
+        self.suggest = Some(suggest);
+        self
+    }
+
+    pub fn describe(mut self, description: &'static str) -> Self {
+        self.description = description;
+        self
+    }
+
+    pub fn requires_op(mut self) -> Self {
+        self.op_only = true;
+        self
+    }
+
+    /// The word of a literal, or the name of an argument.
+    pub fn name(&self) -> &'static str {
+        match self.kind {
+            NodeKind::Literal(name) | NodeKind::Argument { name, .. } => name,
+        }
+    }
+
+    fn children(&self, op: bool) -> impl Iterator<Item = &CommandNode> {
+        // literals before arguments, so that `gamemode creative` isn't taken as a player named creative
+        let literals = self
+            .children
+            .iter()
+            .filter(|child| matches!(child.kind, NodeKind::Literal(_)));
+        let arguments = self
+            .children
+            .iter()
+            .filter(|child| matches!(child.kind, NodeKind::Argument { .. }));
+
+        literals
+            .chain(arguments)
+            .filter(move |child| op || !child.op_only)
+    }
+
+    /// How to use the command from this node on, like `kick <targets> [<reason>]`.
+    pub fn usage(&self, op: bool) -> String {
+        let name = match &self.kind {
+            NodeKind::Literal(name) => (*name).to_owned(),
+            NodeKind::Argument { name, .. } => format!("<{name}>"),
+        };
+
+        let children = self
+            .children(op)
+            .map(|child| child.usage(op))
+            .collect::<Vec<_>>();
+        let rest = match &children[..] {
+            [] => return name,
+            [child] => child.clone(),
+            children => format!("({})", children.join("|")),
+        };
+
+        if self.handler.is_some() {
+            format!("{name} [{rest}]")
+        } else {
+            format!("{name} {rest}")
+        }
+    }
+
+    /// Parses the input after this node, starting at `at`, and returns the handler the command ends at.
+    fn parse(
+        &self,
+        input: &str,
+        at: usize,
+        op: bool,
+        arguments: &mut Arguments,
+    ) -> Result<CommandHandler, CommandError> {
+        if at == input.len() {
+            return self.handler.ok_or_else(|| CommandError::Incomplete {
+                near: near(input, at),
+                usage: self.usage(op),
+            });
+        }
+
+        // every argument is followed by a space, or the end of the input
+        let at = at + 1;
+        let mut error = None;
+
+        for child in self.children(op) {
+            match &child.kind {
+                NodeKind::Literal(name) => {
+                    if Reader::new(input, at).word() == *name {
+                        return child.parse(input, at + name.len(), op, arguments);
+                    }
+                }
+                NodeKind::Argument { name, parser } => {
+                    let mut reader = Reader::new(input, at);
+                    let parsed = reader.parse(parser).and_then(|value| {
+                        if !reader.rest().is_empty() && !reader.rest().starts_with(' ') {
+                            return Err(CommandError::UnexpectedArgument {
+                                near: near(input, reader.at),
+                            });
+                        }
+
+                        arguments.0.insert(name, value);
+                        child.parse(input, reader.at, op, arguments)
+                    });
+
+                    match parsed {
+                        Ok(handler) => return Ok(handler),
+                        Err(e) => {
+                            arguments.0.remove(name);
+                            error.get_or_insert(e);
+                        }
+                    }
+                }
+            }
+        }
+
+        Err(error.unwrap_or_else(|| {
+            let actual = Reader::new(input, at).word().to_owned();
+            let expected = self
+                .children(op)
+                .map(|child| child.name())
+                .collect::<Vec<_>>();
+
+            if expected.is_empty() {
+                CommandError::UnexpectedArgument {
+                    near: near(input, at + actual.len()),
+                }
+            } else {
+                CommandError::InvalidArgument {
+                    what: "argument",
+                    actual,
+                    help: Some(format!("expected {}", expected.join(", "))),
+                }
+            }
+        }))
+    }
+
+    /// Suggestions for the last argument of the input, and where in the input they start.
+    fn suggest(&self, world: &mut World, input: &str, at: usize, op: bool) -> (usize, Vec<String>) {
+        let Some(rest) = input[at..].strip_prefix(' ') else {
+            return (at, vec![]);
+        };
+        let at = at + 1;
+        let last = !rest.contains(' ');
+
+        let mut suggestions = vec![];
+        let mut deeper = None;
+
+        for child in self.children(op) {
+            match &child.kind {
+                NodeKind::Literal(name) => {
+                    if last && name.starts_with(rest) {
+                        suggestions.push((*name).to_owned());
+                    } else if rest
+                        .strip_prefix(*name)
+                        .is_some_and(|rest| rest.starts_with(' '))
+                    {
+                        deeper.get_or_insert_with(|| {
+                            child.suggest(world, input, at + name.len(), op)
+                        });
+                    }
+                }
+                NodeKind::Argument { parser, .. } => {
+                    let mut reader = Reader::new(input, at);
+                    let parsed = reader.parse(parser).is_ok();
+
+                    if last || !parsed {
+                        let all = match child.suggest {
+                            Some(suggest) => suggest(world),
+                            None => default_suggestions(parser, || player_names(world)),
+                        };
+                        suggestions.extend(all.into_iter().filter(|s| s.starts_with(rest)));
+                    } else if reader.rest().starts_with(' ') {
+                        deeper.get_or_insert_with(|| child.suggest(world, input, reader.at, op));
+                    }
+                }
+            }
+        }
+
+        match deeper {
+            Some(deeper) if suggestions.is_empty() => deeper,
+            _ => (at, suggestions),
+        }
+    }
+
+    /// Adds this node and everything under it to a flat list of nodes, the way the [`Commands`] packet has them.
+    fn flatten(&self, nodes: &mut Vec<graph::CommandNode>, op: bool) -> i32 {
+        let index = nodes.len();
+        nodes.push(graph::CommandNode {
+            kind: match &self.kind {
+                NodeKind::Literal(name) => graph::CommandNodeKind::Literal((*name).to_owned()),
+                NodeKind::Argument { name, parser } => graph::CommandNodeKind::Argument {
+                    name: (*name).to_owned(),
+                    parser: parser.clone(),
+                    ask_server: self.suggest.is_some(),
+                },
+            },
+            executable: self.handler.is_some(),
+            children: vec![],
+            redirect: None,
+        });
+
+        let children = self
+            .children(op)
+            .map(|child| child.flatten(nodes, op))
+            .collect();
+        nodes[index].children = children;
+
+        index as i32
+    }
+}
+
+/// The end of the input before `at`, the way the client shows where a command is wrong.
+fn near(input: &str, at: usize) -> String {
+    let before = &input[..at];
+    match before.char_indices().rev().nth(9) {
+        Some((start, _)) if start > 0 => format!("...{}", &before[start..]),
+        _ => before.to_owned(),
+    }
+}
+
+pub(crate) fn player_names(world: &mut World) -> Vec<String> {
+    world
+        .query_filtered::<&PlayerName, With<Player>>()
+        .iter(world)
+        .map(|name| name.0.to_string())
+        .collect()
+}
+
+/// Every command the server knows, by name.
+#[derive(Resource, Debug, Default)]
+pub struct CommandRegistry {
+    commands: BTreeMap<&'static str, CommandNode>,
+}
+
+impl CommandRegistry {
+    /// Adds a command, replacing the one with the same name if there is one.
+    ///
+    /// # Panics
+    ///
+    /// If the node isn't a [`literal`], since commands start with their name.
+    pub fn register(&mut self, command: CommandNode) -> &mut Self {
+        let NodeKind::Literal(name) = command.kind else {
+            panic!("commands have to start with a literal, not an argument");
+        };
+
+        self.commands.insert(name, command);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandNode> {
+        self.commands.get(name)
+    }
+
+    /// The commands, with the ones only operators can run left out if `op` is false.
+    pub fn iter(&self, op: bool) -> impl Iterator<Item = &CommandNode> {
+        self.commands
+            .values()
+            .filter(move |command| op || !command.op_only)
+    }
+
+    /// The command graph for the [`Commands`] packet, with only the commands the player can run.
+    pub fn graph(&self, op: bool) -> Commands {
+        let mut nodes = vec![graph::CommandNode {
+            kind: graph::CommandNodeKind::Root,
+            executable: false,
+            children: vec![],
+            redirect: None,
+        }];
+
+        let children = self
+            .iter(op)
+            .map(|command| command.flatten(&mut nodes, op))
+            .collect();
+        nodes[0].children = children;
+
+        Commands {
+            nodes,
+            root: VarInt(0),
+        }
+    }
+}
+
+/// Adds commands to the [`CommandRegistry`], for plugins that add their own.
+pub trait AddCommand {
+    fn add_command(&mut self, command: CommandNode) -> &mut Self;
+}
+
+impl AddCommand for App {
+    fn add_command(&mut self, command: CommandNode) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(CommandRegistry::default)
+            .register(command);
+        self
+    }
+}
+
+/// Runs `line` (without the leading `/`) as `sender`, returning the output.
+pub fn dispatch(
+    world: &mut World,
+    sender: CommandSender,
+    line: &str,
+) -> Result<Vec<ChatComponent>, CommandError> {
+    let line = line.trim();
+    let name = line.split(' ').next().unwrap_or_default();
+    let op = sender.is_op(world);
+
+    let mut arguments = Arguments::default();
+    let handler = match world.resource::<CommandRegistry>().get(name) {
+        Some(command) if op || !command.op_only => {
+            command.parse(line, name.len(), op, &mut arguments)?
+        }
+        // commands the sender can't run are hidden, like they don't exist
+        _ => {
+            return Err(CommandError::Unknown {
+                name: name.to_owned(),
+            })
+        }
+    };
+
+    let mut cx = CommandContext {
+        world,
+        sender,
+        output: vec![],
+    };
+    debug!(sender = ?cx.sender, %line, "running a command");
+    handler(&mut cx, &arguments)?;
+
+    Ok(cx.output)
+}
+
+/// Suggests how to complete the last argument of `line`, and returns where in `line` the suggestions start.
+pub fn suggest(world: &mut World, sender: CommandSender, line: &str) -> (usize, Vec<String>) {
+    let op = sender.is_op(world);
+
+    world.resource_scope(|world, registry: Mut<CommandRegistry>| {
+        let Some((name, _)) = line.split_once(' ') else {
+            let names = registry
+                .iter(op)
+                .map(CommandNode::name)
+                .filter(|name| name.starts_with(line))
+                .map(str::to_owned)
+                .collect();
+            return (0, names);
+        };
+
+        match registry.get(name) {
+            Some(command) if op || !command.op_only => command.suggest(world, line, name.len(), op),
+            _ => (line.len(), vec![]),
+        }
+    })
+}
+
+/// Sets up the [`CommandRegistry`] with the built-in commands.
+pub struct CommandPlugin;
+
+impl Plugin for CommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CommandRegistry>();
+        builtin::register(app);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oxcr_protocol::model::command::StringKind;
+
+    use super::*;
+
+    fn registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::default();
+        registry
+            .register(
+                literal("kick").requires_op().then(
+                    argument(
+                        "targets",
+                        ArgumentParser::Entity {
+                            single: false,
+                            players_only: true,
+                        },
+                    )
+                    .executes(|_, _| Ok(()))
+                    .then(argument("reason", ArgumentParser::Message).executes(|_, _| Ok(()))),
+                ),
+            )
+            .register(
+                literal("say").then(
+                    argument("message", ArgumentParser::String(StringKind::Greedy))
+                        .executes(|_, _| Ok(())),
+                ),
+            );
+        registry
+    }
+
+    #[test]
+    fn parses_arguments() {
+        let registry = registry();
+        let kick = registry.get("kick").unwrap();
+
+        let mut arguments = Arguments::default();
+        kick.parse("kick Steve being mean", 4, true, &mut arguments)
+            .unwrap();
+        assert_eq!(
+            arguments.entity("targets"),
+            Some(EntitySelector::Name(String::from("Steve")))
+        );
+        assert_eq!(arguments.string("reason").as_deref(), Some("being mean"));
+
+        assert!(matches!(
+            kick.parse("kick", 4, true, &mut Arguments::default()),
+            Err(CommandError::Incomplete { .. })
+        ));
+        assert_eq!(kick.usage(true), "kick <targets> [<reason>]");
+    }
+
+    #[test]
+    fn graph() {
+        let registry = registry();
+
+        let graph = registry.graph(true);
+        assert_eq!(graph.nodes.len(), 6);
+        assert_eq!(graph.nodes[0].children, [1, 4]);
+        assert_eq!(
+            graph.nodes[1].kind,
+            graph::CommandNodeKind::Literal(String::from("kick"))
+        );
+        assert_eq!(graph.nodes[2].children, [3]);
+        assert!(graph.nodes[2].executable);
+
+        // non-operators don't see kick
+        assert_eq!(registry.graph(false).nodes.len(), 3);
+    }
+}
@@ -0,0 +1,388 @@
+//! Parsing arguments, the same way the client does for highlighting.
+
+use std::collections::HashMap;
+
+use oxcr_protocol::model::{
+    command::{ArgumentParser, StringKind},
+    packets::play::GameMode,
+};
+
+use super::{CommandError, GAME_MODES};
+
+/// A parsed argument.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentValue {
+    Bool(bool),
+    Float(f32),
+    Double(f64),
+    Integer(i32),
+    Long(i64),
+    String(String),
+    Entity(EntitySelector),
+    GameProfile(String),
+    BlockPos(Coordinates<i32>),
+    ColumnPos([Coordinate<i32>; 2]),
+    Vec3(Coordinates<f64>),
+    Vec2([Coordinate<f64>; 2]),
+    GameMode(GameMode),
+    Uuid(oxcr_protocol::uuid::Uuid),
+}
+
+/// `@a`, `@p` and so on, or a player name. Selector arguments in brackets aren't supported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntitySelector {
+    Name(String),
+    NearestPlayer,
+    RandomPlayer,
+    AllPlayers,
+    AllEntities,
+    Sender,
+}
+
+/// A coordinate that can be relative to where the command is run from, with `~`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Coordinate<T> {
+    Absolute(T),
+    Relative(T),
+}
+
+pub type Coordinates<T> = [Coordinate<T>; 3];
+
+/// The arguments of a command, by name.
+#[derive(Debug, Clone, Default)]
+pub struct Arguments(pub(super) HashMap<&'static str, ArgumentValue>);
+
+macro getter($name:ident, $variant:ident, $ty:ty) {
+    pub fn $name(&self, name: &str) -> Option<$ty> {
+        match self.0.get(name)? {
+            ArgumentValue::$variant(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Arguments {
+    getter!(string, String, String);
+    getter!(entity, Entity, EntitySelector);
+    getter!(game_mode, GameMode, GameMode);
+}
+
+/// Reads `input` from a position, one argument at a time.
+pub struct Reader<'a> {
+    pub input: &'a str,
+    pub at: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(input: &'a str, at: usize) -> Self {
+        Self { input, at }
+    }
+
+    pub fn rest(&self) -> &'a str {
+        &self.input[self.at..]
+    }
+
+    /// Reads up to the next space.
+    pub fn word(&mut self) -> &'a str {
+        let rest = self.rest();
+        let word = rest.split(' ').next().unwrap_or_default();
+        self.at += word.len();
+        word
+    }
+
+    fn invalid(what: &'static str, actual: &str) -> CommandError {
+        CommandError::InvalidArgument {
+            what,
+            actual: actual.to_owned(),
+            help: None,
+        }
+    }
+
+    fn number<T: std::str::FromStr + PartialOrd + std::fmt::Display + Copy>(
+        &mut self,
+        what: &'static str,
+        min: Option<T>,
+        max: Option<T>,
+    ) -> Result<T, CommandError> {
+        let word = self.word();
+        let number = word.parse::<T>().map_err(|_| Self::invalid(what, word))?;
+
+        if min.is_some_and(|min| number < min) || max.is_some_and(|max| number > max) {
+            return Err(CommandError::InvalidArgument {
+                what,
+                actual: word.to_owned(),
+                help: Some(match (min, max) {
+                    (Some(min), Some(max)) => format!("it has to be between {min} and {max}"),
+                    (Some(min), None) => format!("it can't be less than {min}"),
+                    (None, _) => format!("it can't be more than {}", max.unwrap()),
+                }),
+            });
+        }
+
+        Ok(number)
+    }
+
+    fn coordinate<T: std::str::FromStr + Default>(
+        &mut self,
+        what: &'static str,
+    ) -> Result<Coordinate<T>, CommandError> {
+        let word = self.word();
+
+        match word.strip_prefix('~') {
+            Some("") => Ok(Coordinate::Relative(T::default())),
+            Some(offset) => offset
+                .parse()
+                .map(Coordinate::Relative)
+                .map_err(|_| Self::invalid(what, word)),
+            None => word
+                .parse()
+                .map(Coordinate::Absolute)
+                .map_err(|_| Self::invalid(what, word)),
+        }
+    }
+
+    fn coordinates<T: std::str::FromStr + Default, const N: usize>(
+        &mut self,
+        what: &'static str,
+    ) -> Result<[Coordinate<T>; N], CommandError> {
+        let mut coordinates = Vec::with_capacity(N);
+
+        for i in 0..N {
+            if i > 0 && !self.rest().starts_with(' ') {
+                return Err(CommandError::InvalidArgument {
+                    what,
+                    actual: self.input.to_owned(),
+                    help: Some(format!("{what} needs {N} coordinates")),
+                });
+            }
+            self.at += usize::from(i > 0);
+            coordinates.push(self.coordinate(what)?);
+        }
+
+        Ok(coordinates
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("there are exactly N coordinates")))
+    }
+
+    fn string(&mut self, kind: StringKind) -> Result<String, CommandError> {
+        match kind {
+            StringKind::Word => Ok(self.word().to_owned()),
+            StringKind::Greedy => {
+                let rest = self.rest();
+                self.at = self.input.len();
+                Ok(rest.to_owned())
+            }
+            StringKind::Quotable => {
+                let Some(quoted) = self.rest().strip_prefix('"') else {
+                    return Ok(self.word().to_owned());
+                };
+
+                let mut string = String::new();
+                let mut chars = quoted.char_indices();
+                while let Some((i, ch)) = chars.next() {
+                    match ch {
+                        '"' => {
+                            // the quotes on both sides
+                            self.at += i + 2;
+                            return Ok(string);
+                        }
+                        '\\' => match chars.next() {
+                            Some((_, escaped @ ('"' | '\\'))) => string.push(escaped),
+                            _ => return Err(Self::invalid("escape sequence", quoted)),
+                        },
+                        ch => string.push(ch),
+                    }
+                }
+
+                Err(Self::invalid("string, missing the closing quote", quoted))
+            }
+        }
+    }
+
+    fn entity(&mut self, single: bool, players_only: bool) -> Result<EntitySelector, CommandError> {
+        let word = self.word();
+
+        let selector = match word {
+            "@p" => EntitySelector::NearestPlayer,
+            "@r" => EntitySelector::RandomPlayer,
+            "@a" => EntitySelector::AllPlayers,
+            "@e" => EntitySelector::AllEntities,
+            "@s" => EntitySelector::Sender,
+            selector if selector.starts_with('@') => {
+                return Err(CommandError::InvalidArgument {
+                    what: "selector",
+                    actual: selector.to_owned(),
+                    help: Some(String::from(
+                        "selectors can be @p, @r, @a, @e or @s, without arguments in brackets",
+                    )),
+                })
+            }
+            name if (1..=16).contains(&name.len()) => EntitySelector::Name(name.to_owned()),
+            name => return Err(Self::invalid("player name", name)),
+        };
+
+        if single
+            && matches!(
+                selector,
+                EntitySelector::AllPlayers | EntitySelector::AllEntities
+            )
+        {
+            return Err(CommandError::InvalidArgument {
+                what: "selector",
+                actual: word.to_owned(),
+                help: Some(String::from("only one entity is allowed here")),
+            });
+        }
+
+        if players_only && selector == EntitySelector::AllEntities {
+            return Err(CommandError::InvalidArgument {
+                what: "selector",
+                actual: word.to_owned(),
+                help: Some(String::from("only players are allowed here")),
+            });
+        }
+
+        Ok(selector)
+    }
+
+    /// Reads an argument with `parser`, leaving the reader right after it.
+    pub fn parse(&mut self, parser: &ArgumentParser) -> Result<ArgumentValue, CommandError> {
+        Ok(match parser {
+            ArgumentParser::Bool => match self.word() {
+                "true" => ArgumentValue::Bool(true),
+                "false" => ArgumentValue::Bool(false),
+                word => return Err(Self::invalid("boolean", word)),
+            },
+            ArgumentParser::Float { min, max } => {
+                ArgumentValue::Float(self.number("number", *min, *max)?)
+            }
+            ArgumentParser::Double { min, max } => {
+                ArgumentValue::Double(self.number("number", *min, *max)?)
+            }
+            ArgumentParser::Integer { min, max } => {
+                ArgumentValue::Integer(self.number("integer", *min, *max)?)
+            }
+            ArgumentParser::Long { min, max } => {
+                ArgumentValue::Long(self.number("integer", *min, *max)?)
+            }
+            ArgumentParser::String(kind) => ArgumentValue::String(self.string(*kind)?),
+            ArgumentParser::Message => ArgumentValue::String(self.string(StringKind::Greedy)?),
+            ArgumentParser::Entity {
+                single,
+                players_only,
+            } => ArgumentValue::Entity(self.entity(*single, *players_only)?),
+            ArgumentParser::GameProfile => match self.word() {
+                name if (1..=16).contains(&name.len()) => {
+                    ArgumentValue::GameProfile(name.to_owned())
+                }
+                name => return Err(Self::invalid("player name", name)),
+            },
+            ArgumentParser::BlockPos => ArgumentValue::BlockPos(self.coordinates("position")?),
+            ArgumentParser::ColumnPos => ArgumentValue::ColumnPos(self.coordinates("position")?),
+            ArgumentParser::Vec3 => ArgumentValue::Vec3(self.coordinates("position")?),
+            ArgumentParser::Vec2 => ArgumentValue::Vec2(self.coordinates("position")?),
+            ArgumentParser::GameMode => {
+                let word = self.word();
+                match GAME_MODES.iter().find(|(name, _)| *name == word) {
+                    Some(&(_, game_mode)) => ArgumentValue::GameMode(game_mode),
+                    None => return Err(Self::invalid("game mode", word)),
+                }
+            }
+            ArgumentParser::Uuid => {
+                let word = self.word();
+                ArgumentValue::Uuid(word.parse().map_err(|_| Self::invalid("UUID", word))?)
+            }
+        })
+    }
+}
+
+/// The suggestions every argument of this kind has, like `true` and `false` for booleans.
+pub fn default_suggestions(
+    parser: &ArgumentParser,
+    players: impl FnOnce() -> Vec<String>,
+) -> Vec<String> {
+    match parser {
+        ArgumentParser::Bool => vec![String::from("true"), String::from("false")],
+        ArgumentParser::GameMode => GAME_MODES
+            .iter()
+            .map(|(name, _)| (*name).to_owned())
+            .collect(),
+        ArgumentParser::Entity { single, .. } => {
+            let mut suggestions = players();
+            suggestions.extend(["@p", "@r", "@s"].map(str::to_owned));
+            if !single {
+                suggestions.extend(["@a", "@e"].map(str::to_owned));
+            }
+            suggestions
+        }
+        ArgumentParser::GameProfile => players(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str, parser: ArgumentParser) -> Result<(ArgumentValue, usize), CommandError> {
+        let mut reader = Reader::new(input, 0);
+        reader.parse(&parser).map(|value| (value, reader.at))
+    }
+
+    #[test]
+    fn arguments() {
+        assert_eq!(
+            parse(
+                "12 a",
+                ArgumentParser::Integer {
+                    min: Some(0),
+                    max: Some(20)
+                }
+            )
+            .unwrap(),
+            (ArgumentValue::Integer(12), 2)
+        );
+        assert!(parse(
+            "21",
+            ArgumentParser::Integer {
+                min: Some(0),
+                max: Some(20)
+            }
+        )
+        .is_err());
+
+        assert_eq!(
+            parse(
+                r#""a \"b\"" c"#,
+                ArgumentParser::String(StringKind::Quotable)
+            )
+            .unwrap(),
+            (ArgumentValue::String(String::from(r#"a "b""#)), 9)
+        );
+        assert_eq!(
+            parse("a b c", ArgumentParser::Message).unwrap(),
+            (ArgumentValue::String(String::from("a b c")), 5)
+        );
+
+        assert_eq!(
+            parse("~ 64 ~-2", ArgumentParser::BlockPos).unwrap(),
+            (
+                ArgumentValue::BlockPos([
+                    Coordinate::Relative(0),
+                    Coordinate::Absolute(64),
+                    Coordinate::Relative(-2)
+                ]),
+                8
+            )
+        );
+
+        assert!(parse(
+            "@a",
+            ArgumentParser::Entity {
+                single: true,
+                players_only: true
+            }
+        )
+        .is_err());
+    }
+}
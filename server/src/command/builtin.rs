@@ -0,0 +1,301 @@
+use bevy::prelude::*;
+use oxcr_protocol::{
+    executor::TokioTasksRuntime,
+    model::{
+        chat::ChatComponent,
+        command::{ArgumentParser, StringKind},
+        packets::play::{DisconnectPlay, GameEvent, GameMode, SystemChatMessage},
+        Difficulty,
+    },
+    ser::Json,
+    PlayerN,
+};
+
+use super::{
+    argument, literal, player_names, AddCommand, Arguments, CommandContext, CommandError,
+    CommandRegistry, EntitySelector,
+};
+use crate::{
+    model::{CustomName, DifficultySetting, Player, PlayerGameMode, PlayerName},
+    send_to,
+    shutdown::request_shutdown,
+};
+
+const DIFFICULTIES: [(&str, Difficulty); 4] = [
+    ("peaceful", Difficulty::Peaceful),
+    ("easy", Difficulty::Easy),
+    ("normal", Difficulty::Normal),
+    ("hard", Difficulty::Hard),
+];
+
+pub(crate) const GAME_MODES: [(&str, GameMode); 4] = [
+    ("survival", GameMode::Survival),
+    ("creative", GameMode::Creative),
+    ("adventure", GameMode::Adventure),
+    ("spectator", GameMode::Spectator),
+];
+
+const PLAYERS: ArgumentParser = ArgumentParser::Entity {
+    single: false,
+    players_only: true,
+};
+
+pub(super) fn register(app: &mut App) {
+    app.add_command(
+        literal("help")
+            .describe("Lists all commands")
+            .executes(help),
+    )
+    .add_command(
+        literal("list")
+            .describe("Lists the players online")
+            .executes(list),
+    )
+    .add_command(
+        literal("stop")
+            .describe("Stops the server")
+            .requires_op()
+            .executes(stop),
+    )
+    .add_command(
+        literal("kick")
+            .describe("Disconnects players")
+            .requires_op()
+            .then(
+                argument("targets", PLAYERS)
+                    .executes(kick)
+                    .then(argument("reason", ArgumentParser::Message).executes(kick)),
+            ),
+    )
+    .add_command(
+        literal("say")
+            .describe("Sends a message to every player")
+            .requires_op()
+            .then(argument("message", ArgumentParser::Message).executes(say)),
+    )
+    .add_command(
+        literal("difficulty")
+            .describe("Shows or changes the difficulty")
+            .executes(difficulty)
+            .then(
+                argument("difficulty", ArgumentParser::String(StringKind::Word))
+                    .suggests(|_| names(&DIFFICULTIES))
+                    .requires_op()
+                    .executes(difficulty),
+            ),
+    )
+    .add_command(
+        literal("gamemode")
+            .describe("Changes the game mode of players")
+            .requires_op()
+            .then(
+                argument("gamemode", ArgumentParser::GameMode)
+                    .executes(gamemode)
+                    .then(argument("target", PLAYERS).executes(gamemode)),
+            ),
+    )
+    .add_command(
+        literal("name")
+            .describe("Sets or removes the custom name of players")
+            .requires_op()
+            .then(
+                argument("targets", PLAYERS)
+                    .executes(name)
+                    .then(argument("name", ArgumentParser::Message).executes(name)),
+            ),
+    );
+}
+
+fn names<T>(values: &[(&str, T)]) -> Vec<String> {
+    values.iter().map(|(name, _)| (*name).to_owned()).collect()
+}
+
+/// The names of the players an entity argument refers to, for the output.
+fn describe(cx: &mut CommandContext, players: &[Entity]) -> String {
+    players
+        .iter()
+        .filter_map(|player| cx.world.get::<PlayerName>(*player))
+        .map(|name| name.0.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn help(cx: &mut CommandContext, _: &Arguments) -> Result<(), CommandError> {
+    let op = cx.sender.is_op(cx.world);
+    let lines = cx
+        .world
+        .resource::<CommandRegistry>()
+        .iter(op)
+        .map(|command| format!("{} - {}", command.usage(op), command.description))
+        .collect::<Vec<_>>();
+
+    for line in lines {
+        cx.reply(line);
+    }
+
+    Ok(())
+}
+
+fn list(cx: &mut CommandContext, _: &Arguments) -> Result<(), CommandError> {
+    let names = player_names(cx.world);
+
+    cx.reply(format!(
+        "There are {} players online: {}",
+        names.len(),
+        names.join(", ")
+    ));
+
+    Ok(())
+}
+
+fn stop(cx: &mut CommandContext, _: &Arguments) -> Result<(), CommandError> {
+    info!(sender = ?cx.sender, "stopping the server");
+    cx.reply("Stopping the server");
+    request_shutdown(cx.world);
+
+    Ok(())
+}
+
+fn kick(cx: &mut CommandContext, args: &Arguments) -> Result<(), CommandError> {
+    let targets = cx.players(&args.entity("targets").unwrap_or(EntitySelector::Sender))?;
+    let reason = match args.string("reason") {
+        Some(reason) => ChatComponent::text(reason),
+        None => ChatComponent::translatable("multiplayer.disconnect.kicked", vec![]),
+    };
+    let names = describe(cx, &targets);
+
+    info!(sender = ?cx.sender, players = %names, reason = %reason.to_plain(), "kicking players");
+
+    let players = targets
+        .iter()
+        .filter_map(|player| cx.world.get::<PlayerN>(*player))
+        .map(|player| player.0.clone())
+        .collect::<Vec<_>>();
+
+    cx.world
+        .resource::<TokioTasksRuntime>()
+        .spawn_background_task(move |_| async move {
+            for player in players {
+                let _ = player
+                    .send_packet(DisconnectPlay {
+                        reason: Json(reason.clone()),
+                    })
+                    .await;
+                player.cancellator.cancel();
+            }
+        });
+
+    cx.reply(format!("Kicked {names}"));
+
+    Ok(())
+}
+
+fn say(cx: &mut CommandContext, args: &Arguments) -> Result<(), CommandError> {
+    let message = args.string("message").unwrap_or_default();
+    let sender = cx.sender.name(cx.world);
+
+    info!("[{sender}] {message}");
+
+    let players = cx
+        .world
+        .query_filtered::<&PlayerN, With<Player>>()
+        .iter(cx.world)
+        .map(|player| player.0.clone())
+        .collect();
+
+    send_to(
+        cx.world.resource::<TokioTasksRuntime>(),
+        players,
+        SystemChatMessage {
+            content: Json(ChatComponent::translatable(
+                "chat.type.announcement",
+                vec![ChatComponent::text(sender), ChatComponent::text(message)],
+            )),
+            overlay: false,
+        },
+    );
+
+    Ok(())
+}
+
+fn difficulty(cx: &mut CommandContext, args: &Arguments) -> Result<(), CommandError> {
+    let Some(new) = args.string("difficulty") else {
+        let current = cx.world.resource::<DifficultySetting>().difficulty;
+        let name = DIFFICULTIES
+            .iter()
+            .find(|(_, difficulty)| *difficulty == current)
+            .map_or("unknown", |(name, _)| *name);
+        cx.reply(format!("The difficulty is {name}"));
+
+        return Ok(());
+    };
+
+    let Some(&(name, difficulty)) = DIFFICULTIES.iter().find(|(name, _)| *name == new) else {
+        return Err(CommandError::InvalidArgument {
+            what: "difficulty",
+            actual: new,
+            help: Some(names(&DIFFICULTIES).join(", ")),
+        });
+    };
+
+    // broadcast_difficulty tells the players
+    cx.world.resource_mut::<DifficultySetting>().difficulty = difficulty;
+    cx.reply(format!("The difficulty is now {name}"));
+
+    Ok(())
+}
+
+fn gamemode(cx: &mut CommandContext, args: &Arguments) -> Result<(), CommandError> {
+    let game_mode = args.game_mode("gamemode").unwrap_or(GameMode::Survival);
+    let mode_name = GAME_MODES
+        .iter()
+        .find(|(_, mode)| *mode == game_mode)
+        .map_or("unknown", |(name, _)| *name);
+
+    // without a target, the game mode of whoever ran the command changes
+    let targets = cx.players(&args.entity("target").unwrap_or(EntitySelector::Sender))?;
+
+    let mut players = vec![];
+    for &target in &targets {
+        let mut player = cx.world.entity_mut(target);
+        player.insert(PlayerGameMode(game_mode));
+        if let Some(net) = player.get::<PlayerN>() {
+            players.push(net.0.clone());
+        }
+    }
+
+    send_to(
+        cx.world.resource::<TokioTasksRuntime>(),
+        players,
+        GameEvent {
+            event: GameEvent::CHANGE_GAME_MODE,
+            value: game_mode as u8 as f32,
+        },
+    );
+
+    let names = describe(cx, &targets);
+    cx.reply(format!("Set the game mode of {names} to {mode_name}"));
+
+    Ok(())
+}
+
+fn name(cx: &mut CommandContext, args: &Arguments) -> Result<(), CommandError> {
+    let targets = cx.players(&args.entity("targets").unwrap_or(EntitySelector::Sender))?;
+    // markup, like the motd
+    let name = args.string("name").map(|name| {
+        ChatComponent::from_markup(&name).unwrap_or_else(|_| ChatComponent::text(&name))
+    });
+
+    // sync_metadata tells the players
+    for &target in &targets {
+        cx.world.entity_mut(target).insert(CustomName(name.clone()));
+    }
+
+    let names = describe(cx, &targets);
+    match name {
+        Some(name) => cx.reply(format!("Named {names} {}", name.to_plain())),
+        None => cx.reply(format!("Removed the name of {names}")),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,463 @@
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::Resource;
+use oxcr_protocol::{
+    miette::SourceSpan,
+    model::{chat::ChatComponent, packets::play::GameMode, Difficulty},
+    net::{NetConfig, RecvPolicy, SendPolicy},
+    ser::Zlib,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{ConfigProblem, Error},
+    generator::FlatPreset,
+};
+
+/// The contents of `server.toml`. Every value is optional, missing ones are filled in with the defaults.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub network: NetworkConfig,
+    pub world: WorldConfig,
+    pub status: StatusConfig,
+    pub whitelist: WhitelistConfig,
+    pub query: QueryConfig,
+    pub rcon: RconConfig,
+    pub shutdown: ShutdownConfig,
+    pub permissions: PermissionsConfig,
+    pub tab_list: TabListConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    pub bind: IpAddr,
+    pub port: u16,
+    /// Packets at least this long are compressed, `-1` disables compression.
+    pub compression_threshold: i32,
+    /// The zlib compression level, from 0 (fastest) to 9 (smallest).
+    pub compression_level: u32,
+    pub online_mode: bool,
+    /// How many packets can wait to be sent to each player.
+    pub send_queue: usize,
+    /// How many packets from each player can wait to be handled.
+    pub recv_queue: usize,
+    /// What happens when a player doesn't read packets as fast as they're sent: `block` waits for room in the queue,
+    /// `drop_non_essential` drops the packets the client can do without, and `disconnect` kicks the player.
+    pub send_policy: SendPolicy,
+    /// What happens when a player sends packets faster than they're handled: `block` stops reading from them
+    /// until there's room in the queue, and `disconnect` kicks the player.
+    pub recv_policy: RecvPolicy,
+    /// If enabled, the packets of a tick are sent together at the end of it instead of one by one.
+    pub flush_on_tick: bool,
+    /// How many bytes of packets are sent at once at most, even if the tick isn't over yet.
+    pub max_batch_bytes: usize,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        let net = NetConfig::default();
+        Self {
+            bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 25565,
+            compression_threshold: 256,
+            compression_level: 6,
+            online_mode: false,
+            send_queue: net.send_capacity,
+            recv_queue: net.recv_capacity,
+            send_policy: net.send_policy,
+            recv_policy: net.recv_policy,
+            flush_on_tick: true,
+            max_batch_bytes: net.max_batch_bytes,
+        }
+    }
+}
+
+impl NetworkConfig {
+    pub fn compression_threshold(&self) -> Option<usize> {
+        self.compression_threshold.try_into().ok()
+    }
+
+    /// The settings every player's connection is made with.
+    pub fn net_config(&self) -> NetConfig {
+        NetConfig {
+            send_capacity: self.send_queue,
+            recv_capacity: self.recv_queue,
+            send_policy: self.send_policy,
+            recv_policy: self.recv_policy,
+            flush_on_tick: self.flush_on_tick,
+            max_batch_bytes: self.max_batch_bytes,
+            compression: Zlib::new(self.compression_level),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldConfig {
+    pub difficulty: Difficulty,
+    pub difficulty_locked: bool,
+    /// The game mode new players join with.
+    pub game_mode: GameMode,
+    /// How many chunks around a player are sent to the player, from 2 to 32.
+    /// Players who asked for less in their settings get that many.
+    pub view_distance: u8,
+    /// How many chunks around a player are ticked, from 2 to 32.
+    pub simulation_distance: u8,
+    /// How new chunks are made. Flat by default, `noise` generates terrain from the seed.
+    pub generator: WorldGenerator,
+    /// The layers and biome of flat worlds, written like vanilla's superflat presets,
+    /// e.g. `minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains`.
+    pub flat_preset: String,
+    /// What the terrain is generated from. Numbers are used as they are and any other text is hashed, like vanilla.
+    /// Left empty, a random seed is picked. Only used for new worlds, which keep their seed in the world directory.
+    pub seed: String,
+    /// Where changed chunks are saved. Relative paths are resolved from the directory of the config file.
+    pub directory: PathBuf,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            difficulty: Difficulty::Easy,
+            difficulty_locked: false,
+            game_mode: GameMode::Survival,
+            view_distance: 10,
+            simulation_distance: 10,
+            generator: WorldGenerator::Flat,
+            flat_preset: String::from(
+                "minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains",
+            ),
+            seed: String::new(),
+            directory: PathBuf::from("world"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorldGenerator {
+    /// Hills, mountains, oceans and caves made from noise, depending on `seed`.
+    Noise,
+    /// The layers of `flat_preset`.
+    Flat,
+    /// Nothing but air.
+    Void,
+}
+
+/// What the server list shows about the server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusConfig {
+    /// The message of the day, in the [markup](oxcr_protocol::model::chat::markup) format.
+    pub motd: String,
+    pub max_players: u32,
+    /// A 64×64 PNG image. Relative paths are resolved from the directory of the config file.
+    pub favicon: Option<PathBuf>,
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        Self {
+            motd: String::from("<aqua>An Oxcraft server"),
+            max_players: 20,
+            favicon: None,
+        }
+    }
+}
+
+/// Who is allowed to join the server.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WhitelistConfig {
+    /// If disabled, anyone can join.
+    pub enabled: bool,
+    /// Names of the players that can join, compared case-insensitively.
+    pub players: Vec<String>,
+}
+
+impl WhitelistConfig {
+    pub fn allows(&self, name: &str) -> bool {
+        !self.enabled
+            || self
+                .players
+                .iter()
+                .any(|player| player.eq_ignore_ascii_case(name))
+    }
+}
+
+/// The UDP query protocol, used by monitoring tools.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueryConfig {
+    pub enabled: bool,
+    /// The UDP port to listen on, on the same address as the server.
+    pub port: u16,
+}
+
+impl Default for QueryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 25565,
+        }
+    }
+}
+
+/// Remote console access over the RCON protocol.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RconConfig {
+    pub enabled: bool,
+    /// The TCP port to listen on, on the same address as the server.
+    pub port: u16,
+    /// Required to run any command, so RCON can't be enabled without one.
+    pub password: String,
+}
+
+impl Default for RconConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 25575,
+            password: String::new(),
+        }
+    }
+}
+
+/// What happens when the server stops.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    /// The disconnect message players see, in the [markup](oxcr_protocol::model::chat::markup) format.
+    pub message: String,
+    /// How long to wait for the disconnect messages to be sent, in milliseconds.
+    pub timeout: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            message: String::from("Server closed"),
+            timeout: 5000,
+        }
+    }
+}
+
+/// Who can run the commands that change the server, like `stop` and `kick`.
+/// The console and RCON always can.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PermissionsConfig {
+    /// Names of the operators, compared case-insensitively.
+    pub ops: Vec<String>,
+}
+
+impl PermissionsConfig {
+    pub fn is_op(&self, name: &str) -> bool {
+        self.ops.iter().any(|op| op.eq_ignore_ascii_case(name))
+    }
+}
+
+/// The text above and below the player list, in the [markup](oxcr_protocol::model::chat::markup) format.
+/// Empty texts aren't shown.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TabListConfig {
+    pub header: String,
+    pub footer: String,
+}
+
+/// The view and simulation distances the client supports, in chunks.
+pub const DISTANCES: std::ops::RangeInclusive<u8> = 2..=32;
+
+impl Config {
+    /// Reads and validates the config at `path`. If there is no such file, it's created with the default config.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        match Self::read(path) {
+            Err(Error::ConfigIo { error, .. }) if error.kind() == std::io::ErrorKind::NotFound => {
+                let config = Self::default();
+                let source = toml::to_string_pretty(&config).expect("the default config is valid");
+                std::fs::write(path, source).map_err(|error| Error::ConfigIo {
+                    path: path.to_owned(),
+                    error,
+                })?;
+                tracing::info!(path = %path.display(), "wrote the default config");
+
+                Ok(config)
+            }
+            result => result,
+        }
+    }
+
+    /// Reads and validates the config at `path`.
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let source = std::fs::read_to_string(path).map_err(|error| Error::ConfigIo {
+            path: path.to_owned(),
+            error,
+        })?;
+
+        let mut config: Self = toml::from_str(&source).map_err(|error| Error::InvalidConfig {
+            origin: path.display().to_string(),
+            problems: vec![ConfigProblem {
+                message: error.message().to_owned(),
+                src: source.clone(),
+                at: error.span().map(Into::into),
+                help: None,
+            }],
+        })?;
+
+        config.validate(Some((path, &source)))?;
+
+        if let Some(dir) = path.parent() {
+            if let Some(favicon) = &mut config.status.favicon {
+                *favicon = dir.join(&*favicon);
+            }
+            config.world.directory = dir.join(&config.world.directory);
+        }
+
+        Ok(config)
+    }
+
+    /// Checks the values that parse fine, but don't make sense.
+    ///
+    /// `source` is the config file the values came from, used to point at the offending values.
+    /// Without it (e.g. after applying command line flags) the problems are reported without a location.
+    pub fn validate(&self, source: Option<(&Path, &str)>) -> Result<(), Error> {
+        let mut problems = vec![];
+        let document = source.and_then(|(_, source)| source.parse::<toml_edit::Document>().ok());
+
+        let mut problem = |section: &str, key: &str, message: String, help: Option<&str>| {
+            problems.push(ConfigProblem {
+                message: format!("{section}.{key}: {message}"),
+                src: source.map_or_else(String::new, |(_, source)| source.to_owned()),
+                at: document
+                    .as_ref()
+                    .and_then(|document| document.get(section)?.get(key)?.span())
+                    .map(SourceSpan::from),
+                help: help.map(str::to_owned),
+            })
+        };
+
+        let NetworkConfig {
+            compression_threshold,
+            compression_level,
+            online_mode,
+            send_queue,
+            recv_queue,
+            ..
+        } = self.network;
+
+        if compression_threshold < -1 {
+            problem(
+                "network",
+                "compression_threshold",
+                format!("{compression_threshold} is not a valid threshold"),
+                Some("use -1 to disable compression"),
+            );
+        }
+
+        if compression_level > 9 {
+            problem(
+                "network",
+                "compression_level",
+                format!("{compression_level} is not a zlib compression level"),
+                Some("the compression level goes from 0 (fastest) to 9 (smallest)"),
+            );
+        }
+
+        for (key, size) in [("send_queue", send_queue), ("recv_queue", recv_queue)] {
+            if size == 0 {
+                problem(
+                    "network",
+                    key,
+                    String::from("the queue can't be empty"),
+                    Some("at least 1 packet has to fit"),
+                );
+            }
+        }
+
+        if online_mode {
+            problem(
+                "network",
+                "online_mode",
+                String::from("online mode is not supported yet"),
+                Some("authenticating players requires encryption, which isn't implemented"),
+            );
+        }
+
+        for (key, distance) in [
+            ("view_distance", self.world.view_distance),
+            ("simulation_distance", self.world.simulation_distance),
+        ] {
+            if !DISTANCES.contains(&distance) {
+                problem(
+                    "world",
+                    key,
+                    format!("{distance} is out of range"),
+                    Some("the distance has to be between 2 and 32 chunks"),
+                );
+            }
+        }
+
+        if let Err(error) = self.world.flat_preset.parse::<FlatPreset>() {
+            problem(
+                "world",
+                "flat_preset",
+                error.to_string(),
+                Some("layers go from the bottom up, like `minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains`"),
+            );
+        }
+
+        if self.rcon.enabled && self.rcon.password.is_empty() {
+            problem(
+                "rcon",
+                "password",
+                String::from("RCON is enabled without a password"),
+                Some("set a password, or disable RCON"),
+            );
+        }
+
+        if i32::try_from(self.status.max_players).is_err() {
+            problem(
+                "status",
+                "max_players",
+                format!("{} is too many players", self.status.max_players),
+                None,
+            );
+        }
+
+        if let Err(error) = ChatComponent::from_markup(&self.status.motd) {
+            problem("status", "motd", error.to_string(), None);
+        }
+
+        if let Err(error) = ChatComponent::from_markup(&self.shutdown.message) {
+            problem("shutdown", "message", error.to_string(), None);
+        }
+
+        for (key, text) in [
+            ("header", &self.tab_list.header),
+            ("footer", &self.tab_list.footer),
+        ] {
+            if let Err(error) = ChatComponent::from_markup(text) {
+                problem("tab_list", key, error.to_string(), None);
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidConfig {
+                origin: source.map_or_else(
+                    || String::from("command line flags"),
+                    |(path, _)| path.display().to_string(),
+                ),
+                problems,
+            })
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! The interactive console on stdin, which runs commands through the same dispatcher as everything else.
+
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use oxcr_protocol::{
+    executor::{TaskContext, TokioTasksRuntime},
+    logging::CraftLayer,
+};
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Context, Editor, ExternalPrinter, Helper,
+};
+use tokio::runtime::Handle;
+
+use crate::command::{dispatch, suggest, CommandSender};
+
+const PROMPT: &str = "> ";
+
+/// Completes commands by asking the dispatcher on the main thread.
+struct ConsoleHelper {
+    cx: Arc<TaskContext>,
+    handle: Handle,
+}
+
+impl Completer for ConsoleHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = line[..pos].to_owned();
+
+        Ok(self.handle.block_on(
+            self.cx
+                .run_on_main_thread(move |w| suggest(w.world, CommandSender::Console, &line)),
+        ))
+    }
+}
+
+impl Hinter for ConsoleHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ConsoleHelper {}
+
+impl Validator for ConsoleHelper {}
+
+impl Helper for ConsoleHelper {}
+
+/// Starts reading commands from stdin.
+pub fn console(rt: Res<TokioTasksRuntime>) {
+    rt.spawn_background_task(|cx| async move {
+        let cx = Arc::new(cx);
+        let handle = Handle::current();
+
+        // a thread of its own, since the editor blocks while waiting for input,
+        // and the runtime would wait for a blocking task like that before exiting
+        std::thread::spawn(move || match read_commands(cx, handle) {
+            Ok(()) => debug!("stopped reading commands from the console"),
+            Err(error) => error!(?error, "the console failed"),
+        });
+    });
+}
+
+fn read_commands(cx: Arc<TaskContext>, handle: Handle) -> rustyline::Result<()> {
+    let mut editor = Editor::<ConsoleHelper, DefaultHistory>::new()?;
+    editor.set_helper(Some(ConsoleHelper {
+        cx: cx.clone(),
+        handle: handle.clone(),
+    }));
+
+    // log lines are printed above the prompt, instead of over whatever is being typed
+    if let Ok(mut printer) = editor.create_external_printer() {
+        CraftLayer::set_printer(move |line| {
+            if printer.print(line.clone()).is_err() {
+                println!("{line}");
+            }
+        });
+    }
+
+    let result = loop {
+        let line = match editor.readline(PROMPT) {
+            Ok(line) => line,
+            // rustyline catches Ctrl-C, so it doesn't stop the server on its own
+            Err(ReadlineError::Interrupted) => String::from("stop"),
+            Err(ReadlineError::Eof) => break Ok(()),
+            Err(error) => break Err(error),
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(&line);
+
+        let output = handle.block_on(
+            cx.run_on_main_thread(move |w| dispatch(w.world, CommandSender::Console, &line)),
+        );
+
+        match output {
+            Ok(output) => {
+                for line in output {
+                    info!("{}", line.to_plain());
+                }
+            }
+            Err(error) => warn!("{error}"),
+        }
+    };
+
+    CraftLayer::remove_printer();
+
+    result
+}
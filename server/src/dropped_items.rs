@@ -0,0 +1,219 @@
+//! Items lying around in the world, dropped by players or out of broken containers.
+//!
+//! There's no physics on the server, so items are put where they'd come to rest right away
+//! instead of being thrown, and players pick them up by walking over them.
+//! They aren't saved with the world, like vanilla they only last a few minutes anyway.
+
+use std::time::{Duration, Instant};
+
+use bevy::{math::DVec3, prelude::*};
+use oxcr_protocol::{
+    model::{
+        item::ItemStack,
+        packets::play::{GameMode, PickupItem},
+        VarInt,
+    },
+    PlayerN,
+};
+
+use crate::{
+    inventory::Inventory,
+    model::{EntityKind, Joined, PlayerGameMode, Position, Rotation},
+    tracker::{network_id, TrackedEntities},
+    world::{Chunks, MIN_Y},
+};
+
+/// How long it takes until an item a player dropped can be picked up, so that they don't pick it up again right away.
+const PLAYER_PICKUP_DELAY: Duration = Duration::from_secs(2);
+/// How long it takes until the items out of a broken container can be picked up.
+pub const BLOCK_PICKUP_DELAY: Duration = Duration::from_millis(500);
+/// How long items lie around until they disappear.
+const DESPAWN_DELAY: Duration = Duration::from_secs(5 * 60);
+/// How far in front of a player items land when the player looks straight ahead.
+const THROW_DISTANCE: f64 = 1.5;
+/// How far sideways from a player's feet items can be picked up: half the width of a player,
+/// a block more like vanilla, and half the width of an item.
+const PICKUP_RANGE: f64 = 0.3 + 1.0 + 0.125;
+const PLAYER_HEIGHT: f64 = 1.8;
+const ITEM_HEIGHT: f64 = 0.25;
+
+/// An item entity, and what's in it.
+#[derive(Component, Debug, Clone)]
+pub struct DroppedItem {
+    pub stack: ItemStack,
+    pickup_at: Instant,
+    despawn_at: Instant,
+}
+
+/// Spawns an item entity at `position`.
+pub fn spawn_item(world: &mut World, position: DVec3, stack: ItemStack, pickup_delay: Duration) {
+    let position = settle(world.resource::<Chunks>(), position);
+    let now = Instant::now();
+    world.spawn((
+        EntityKind::ITEM,
+        Position(position),
+        Rotation::default(),
+        DroppedItem {
+            stack,
+            pickup_at: now + pickup_delay,
+            despawn_at: now + DESPAWN_DELAY,
+        },
+    ));
+}
+
+/// Drops an item in front of a player, or at their feet if there's a wall in front of them.
+pub fn drop_item(world: &mut World, player: Entity, stack: ItemStack) {
+    let (Some(feet), Some(rotation)) = (
+        world.get::<Position>(player).copied(),
+        world.get::<Rotation>(player).copied(),
+    ) else {
+        return;
+    };
+
+    let (yaw, pitch) = (
+        f64::from(rotation.yaw).to_radians(),
+        f64::from(rotation.pitch).to_radians(),
+    );
+    // looking down drops it closer
+    let ahead =
+        feet.0 + DVec3::new(-yaw.sin(), 0.0, yaw.cos()) * THROW_DISTANCE * pitch.cos().max(0.0);
+    let position = if world
+        .resource::<Chunks>()
+        .block(ahead.floor().as_ivec3())
+        .is_some_and(|state| state.is_replaceable())
+    {
+        ahead
+    } else {
+        feet.0
+    };
+
+    spawn_item(world, position, stack, PLAYER_PICKUP_DELAY);
+}
+
+/// Moves a position down onto the first block below it that isn't air.
+fn settle(chunks: &Chunks, mut position: DVec3) -> DVec3 {
+    position.y = position.y.floor();
+    while position.y > f64::from(MIN_Y)
+        && chunks
+            .block((position - DVec3::Y).floor().as_ivec3())
+            .is_some_and(|state| state.is_air())
+    {
+        position.y -= 1.0;
+    }
+    position
+}
+
+/// Whether a player standing at `feet` can pick up an item lying at `item`, which vanilla allows if
+/// the item touches the player grown by a block sideways and half a block up and down.
+fn in_pickup_range(feet: DVec3, item: DVec3) -> bool {
+    (item.x - feet.x).abs() < PICKUP_RANGE
+        && (item.z - feet.z).abs() < PICKUP_RANGE
+        && item.y + ITEM_HEIGHT > feet.y - 0.5
+        && item.y < feet.y + PLAYER_HEIGHT + 0.5
+}
+
+/// Lets players pick up the items around them, and despawns items that have been lying around for too long.
+pub fn pick_up_items(
+    mut commands: Commands,
+    mut items: Query<(Entity, &Position, &mut DroppedItem)>,
+    mut players: Query<
+        (Entity, &Position, &PlayerN, &PlayerGameMode, &mut Inventory),
+        With<Joined>,
+    >,
+    viewers: Query<(&PlayerN, &TrackedEntities)>,
+) {
+    let now = Instant::now();
+
+    for (item, position, mut dropped) in &mut items {
+        if now >= dropped.despawn_at {
+            commands.entity(item).despawn();
+            continue;
+        }
+        if now < dropped.pickup_at {
+            continue;
+        }
+
+        for (player, feet, net, game_mode, mut inventory) in &mut players {
+            if game_mode.0 == GameMode::Spectator || !in_pickup_range(feet.0, position.0) {
+                continue;
+            }
+
+            let before = inventory.slots.clone();
+            let rest = inventory.insert(dropped.stack.clone());
+            let count = dropped.stack.count - rest.as_ref().map_or(0, |rest| rest.count);
+            if count == 0 {
+                continue;
+            }
+
+            for index in 0..before.len() {
+                if before[index] != inventory.slots[index] {
+                    net.0.queue(inventory.slot_packet(index));
+                }
+            }
+            let packet = PickupItem {
+                collected_entity_id: network_id(item),
+                collector_entity_id: network_id(player),
+                count: VarInt(count.into()),
+            };
+            for (net, tracked) in &viewers {
+                if tracked.contains(item) {
+                    net.0.queue(packet.clone());
+                }
+            }
+
+            // sync_metadata sends the new count
+            match rest {
+                Some(rest) => dropped.stack = rest,
+                None => {
+                    commands.entity(item).despawn();
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use oxcr_protocol::model::{block::BlockState, registry::Registry};
+
+    use super::*;
+    use crate::generator::FlatGenerator;
+
+    #[test]
+    fn settling() {
+        let generator = FlatGenerator::new(&Default::default(), &Registry::default());
+        let mut chunks = Chunks::new(Arc::new(generator), None);
+        chunks.get_or_generate((0, 0));
+
+        // bedrock, two dirt and grass from the bottom of the world
+        let ground = f64::from(MIN_Y + 4);
+        assert_eq!(
+            settle(&chunks, DVec3::new(3.5, ground + 10.7, 2.5)),
+            DVec3::new(3.5, ground, 2.5)
+        );
+
+        let position = IVec3::new(3, MIN_Y + 3, 2);
+        chunks.set_block(position, BlockState::AIR);
+        assert_eq!(
+            settle(&chunks, DVec3::new(3.5, ground, 2.5)),
+            DVec3::new(3.5, ground - 1.0, 2.5)
+        );
+        // the next chunk isn't loaded
+        assert_eq!(
+            settle(&chunks, DVec3::new(20.0, ground + 1.0, 2.0)),
+            DVec3::new(20.0, ground + 1.0, 2.0)
+        );
+    }
+
+    #[test]
+    fn pickup_range() {
+        let feet = DVec3::new(0.0, 64.0, 0.0);
+        assert!(in_pickup_range(feet, DVec3::new(1.0, 64.0, -1.0)));
+        assert!(in_pickup_range(feet, DVec3::new(0.0, 66.0, 0.0)));
+        assert!(!in_pickup_range(feet, DVec3::new(1.5, 64.0, 0.0)));
+        assert!(!in_pickup_range(feet, DVec3::new(0.0, 63.0, 0.0)));
+    }
+}
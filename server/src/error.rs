@@ -1,5 +1,7 @@
+use std::path::PathBuf;
+
 use oxcr_protocol::error::Error as NetError;
-use oxcr_protocol::miette;
+use oxcr_protocol::miette::{self, SourceSpan};
 
 #[derive(oxcr_protocol::thiserror::Error, miette::Diagnostic, Debug)]
 pub enum Error {
@@ -8,8 +10,39 @@ pub enum Error {
     Net(NetError),
     #[error("Incorrect protocol version: {_0}")]
     #[diagnostic(code(server::error::incorrect_protocol_version))]
-    IncorrectVersion(i32), // #[error("duplicate player IP")]
-                           // DupePlayer,
+    IncorrectVersion(i32),
+    // #[error("duplicate player IP")]
+    // DupePlayer,
+    #[error("Failed to read {}", path.display())]
+    #[diagnostic(code(server::error::config_io))]
+    ConfigIo {
+        path: PathBuf,
+        #[source]
+        error: std::io::Error,
+    },
+    #[error("Invalid config in {origin}")]
+    #[diagnostic(code(server::error::invalid_config))]
+    InvalidConfig {
+        /// The config file, or the command line.
+        origin: String,
+        #[related]
+        problems: Vec<ConfigProblem>,
+    },
+    #[error("Invalid favicon {}: {reason}", path.display())]
+    #[diagnostic(
+        code(server::error::invalid_favicon),
+        help("the favicon has to be a 64x64 PNG image")
+    )]
+    InvalidFavicon { path: PathBuf, reason: String },
+    #[error("Invalid RCON packet length: {_0}")]
+    #[diagnostic(code(server::error::invalid_rcon_packet))]
+    InvalidRconPacket(i32),
+    #[error("Not every player was disconnected before the shutdown timeout")]
+    #[diagnostic(
+        code(server::error::shutdown_timeout),
+        help("players with slow connections might need a longer `shutdown.timeout`")
+    )]
+    ShutdownTimeout,
 }
 
 impl<T: Into<NetError>> From<T> for Error {
@@ -17,3 +50,17 @@ impl<T: Into<NetError>> From<T> for Error {
         Self::Net(value.into())
     }
 }
+
+/// A single problem with a config file, see [`Error::InvalidConfig`].
+#[derive(oxcr_protocol::thiserror::Error, miette::Diagnostic, Debug)]
+#[error("{message}")]
+#[diagnostic(code(server::config::invalid_value))]
+pub struct ConfigProblem {
+    pub message: String,
+    #[source_code]
+    pub src: String,
+    #[label = "here"]
+    pub at: Option<SourceSpan>,
+    #[help]
+    pub help: Option<String>,
+}
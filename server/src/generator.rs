@@ -0,0 +1,297 @@
+//! How new chunks are made, see [`ChunkGenerator`].
+//!
+//! The generator is picked with `world.generator` in the config, and runs the first time a chunk is needed.
+
+mod noise;
+
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    str::FromStr,
+    sync::Arc,
+};
+
+use bevy::prelude::Resource;
+use oxcr_protocol::{
+    miette,
+    model::{block::BlockState, registry::Registry, WorldgenBiome},
+};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::{
+    config::{WorldConfig, WorldGenerator},
+    world::{Chunk, HEIGHT, MIN_Y},
+};
+
+pub use noise::NoiseGenerator;
+
+/// Fills in new chunks.
+pub trait ChunkGenerator: fmt::Debug + Send + Sync + 'static {
+    /// The chunk at `position` is all air when it's passed in.
+    fn generate(&self, position: (i32, i32), chunk: &mut Chunk);
+
+    /// Whether clients should show the world as flat, with the horizon at the bottom of the world instead of at sea level.
+    fn is_flat(&self) -> bool {
+        false
+    }
+}
+
+/// Builds the generator the config asks for.
+pub fn from_config(
+    config: &WorldConfig,
+    seed: WorldSeed,
+    biomes: &Registry<WorldgenBiome>,
+) -> Arc<dyn ChunkGenerator> {
+    match config.generator {
+        WorldGenerator::Noise => Arc::new(NoiseGenerator::new(seed.0, biomes)),
+        WorldGenerator::Flat => {
+            // checked when the config was loaded
+            let preset = config.flat_preset.parse().unwrap_or_default();
+            Arc::new(FlatGenerator::new(&preset, biomes))
+        }
+        WorldGenerator::Void => Arc::new(VoidGenerator {
+            biome: biome_id(biomes, FlatPreset::DEFAULT_BIOME),
+        }),
+    }
+}
+
+/// The seed the world is generated from.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldSeed(pub i64);
+
+impl WorldSeed {
+    /// Reads `world.seed` from the config the way vanilla does: numbers are used as they are,
+    /// other text by its Java string hash, and a random seed is picked if it's empty.
+    pub fn from_config(seed: &str) -> Self {
+        let seed = seed.trim();
+        if seed.is_empty() {
+            return Self(RandomState::new().build_hasher().finish() as i64);
+        }
+
+        Self(seed.parse().unwrap_or_else(|_| {
+            seed.encode_utf16()
+                .fold(0i32, |hash, c| hash.wrapping_mul(31).wrapping_add(c.into()))
+                .into()
+        }))
+    }
+
+    /// What clients are told the seed is, the first 8 bytes of its SHA-256 hash.
+    /// They only use it to pick biome colors, so the seed itself stays secret.
+    pub fn hashed(self) -> i64 {
+        let hash = Sha256::digest(self.0.to_le_bytes());
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&hash[..8]);
+        i64::from_le_bytes(bytes)
+    }
+}
+
+/// Biomes that aren't in the registry fall back to the first one.
+fn biome_id(biomes: &Registry<WorldgenBiome>, name: &str) -> u16 {
+    let name = if name.contains(':') {
+        name.to_owned()
+    } else {
+        format!("minecraft:{name}")
+    };
+
+    match biomes.id(&name) {
+        Some(id) => id as u16,
+        None => {
+            warn!(biome = %name, "unknown biome, using the first one there is");
+            0
+        }
+    }
+}
+
+/// Layers of blocks from the bottom of the world up, and a single biome. Written the same way as vanilla's superflat presets,
+/// like `minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains`, where the biome is optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlatPreset {
+    /// How many blocks high each layer is, and what it's made of.
+    pub layers: Vec<(u32, BlockState)>,
+    pub biome: String,
+}
+
+impl FlatPreset {
+    const DEFAULT_BIOME: &'static str = "minecraft:plains";
+}
+
+/// Vanilla's classic flat preset.
+impl Default for FlatPreset {
+    fn default() -> Self {
+        Self {
+            layers: vec![
+                (1, BlockState::BEDROCK),
+                (2, BlockState::DIRT),
+                (1, BlockState::GRASS_BLOCK),
+            ],
+            biome: Self::DEFAULT_BIOME.to_owned(),
+        }
+    }
+}
+
+impl FromStr for FlatPreset {
+    type Err = InvalidPreset;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // anything after the biome was for structures, which presets don't have anymore
+        let mut parts = s.split(';');
+        let layers = parts.next().unwrap_or_default();
+        let biome = parts
+            .next()
+            .map(str::trim)
+            .filter(|biome| !biome.is_empty())
+            .unwrap_or(Self::DEFAULT_BIOME);
+
+        let layers = layers
+            .split(',')
+            .map(str::trim)
+            .filter(|layer| !layer.is_empty())
+            .map(|layer| {
+                let (height, block) = match layer.split_once('*') {
+                    Some((height, block)) => (
+                        height
+                            .trim()
+                            .parse::<u32>()
+                            .ok()
+                            .filter(|height| *height > 0)
+                            .ok_or_else(|| InvalidPreset::Height(height.to_owned()))?,
+                        block,
+                    ),
+                    None => (1, layer),
+                };
+                let block = BlockState::from_name(block.trim())
+                    .ok_or_else(|| InvalidPreset::UnknownBlock(block.to_owned()))?;
+                Ok((height, block))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let height = layers.iter().map(|(height, _)| *height).sum::<u32>();
+        if height > HEIGHT as u32 {
+            return Err(InvalidPreset::TooHigh(height));
+        }
+
+        Ok(Self {
+            layers,
+            biome: biome.to_owned(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, oxcr_protocol::thiserror::Error, miette::Diagnostic)]
+pub enum InvalidPreset {
+    #[error("unknown block: {_0:?}")]
+    #[diagnostic(code(server::generator::unknown_block))]
+    UnknownBlock(String),
+    #[error("invalid layer height: {_0:?}")]
+    #[diagnostic(
+        code(server::generator::invalid_height),
+        help("a layer is a block, optionally preceded by how high it is, like `2*minecraft:dirt`")
+    )]
+    Height(String),
+    #[error("the layers are {_0} blocks high, more than the world")]
+    #[diagnostic(code(server::generator::too_high))]
+    TooHigh(u32),
+}
+
+/// The same layers everywhere.
+#[derive(Debug, Clone)]
+pub struct FlatGenerator {
+    /// From the bottom of the world up.
+    layers: Vec<BlockState>,
+    biome: u16,
+}
+
+impl FlatGenerator {
+    pub fn new(preset: &FlatPreset, biomes: &Registry<WorldgenBiome>) -> Self {
+        Self {
+            layers: preset
+                .layers
+                .iter()
+                .flat_map(|(height, state)| std::iter::repeat(*state).take(*height as usize))
+                .collect(),
+            biome: biome_id(biomes, &preset.biome),
+        }
+    }
+}
+
+impl ChunkGenerator for FlatGenerator {
+    fn generate(&self, _: (i32, i32), chunk: &mut Chunk) {
+        chunk.fill_biome(self.biome);
+        for (y, state) in (MIN_Y..).zip(&self.layers) {
+            if state.is_air() {
+                continue;
+            }
+            for x in 0..16 {
+                for z in 0..16 {
+                    chunk.set_block(x, y, z, *state);
+                }
+            }
+        }
+    }
+
+    fn is_flat(&self) -> bool {
+        true
+    }
+}
+
+/// Nothing but air, like vanilla's void preset, which is a flat world without layers.
+#[derive(Debug, Clone)]
+pub struct VoidGenerator {
+    biome: u16,
+}
+
+impl ChunkGenerator for VoidGenerator {
+    fn generate(&self, _: (i32, i32), chunk: &mut Chunk) {
+        chunk.fill_biome(self.biome);
+    }
+
+    fn is_flat(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets() {
+        let classic = "minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains";
+        assert_eq!(classic.parse(), Ok(FlatPreset::default()));
+
+        let preset = "minecraft:bedrock,3*stone, 52*minecraft:sandstone;desert;village"
+            .parse::<FlatPreset>()
+            .unwrap();
+        assert_eq!(preset.layers[1], (3, BlockState::STONE));
+        assert_eq!(preset.layers[2].0, 52);
+        assert_eq!(preset.biome, "desert");
+
+        assert_eq!(
+            "2*minecraft:dirt,minecraft:dirtt".parse::<FlatPreset>(),
+            Err(InvalidPreset::UnknownBlock("minecraft:dirtt".to_owned()))
+        );
+        assert_eq!(
+            "0*minecraft:dirt".parse::<FlatPreset>(),
+            Err(InvalidPreset::Height("0".to_owned()))
+        );
+        assert_eq!(
+            "400*minecraft:dirt".parse::<FlatPreset>(),
+            Err(InvalidPreset::TooHigh(400))
+        );
+    }
+
+    #[test]
+    fn seeds() {
+        assert_eq!(WorldSeed::from_config("-42"), WorldSeed(-42));
+        assert_eq!(WorldSeed::from_config(" 123 "), WorldSeed(123));
+        // "hello".hashCode() in Java
+        assert_eq!(WorldSeed::from_config("hello"), WorldSeed(99162322));
+        assert_eq!(
+            WorldSeed::from_config("hello").hashed(),
+            WorldSeed(99162322).hashed()
+        );
+        assert_ne!(WorldSeed(1).hashed(), WorldSeed(2).hashed());
+    }
+}
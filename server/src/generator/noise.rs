@@ -0,0 +1,625 @@
+//! Terrain made from noise, see [`NoiseGenerator`].
+
+use oxcr_protocol::model::{block::BlockState, registry::Registry, WorldgenBiome};
+
+use super::ChunkGenerator;
+use crate::world::{Chunk, HEIGHT, MIN_Y};
+
+/// Where oceans are filled up to, the same as vanilla.
+const SEA_LEVEL: i32 = 63;
+/// Caves below this are filled with lava.
+const LAVA_LEVEL: i32 = -54;
+/// Ground this high is covered in snow.
+const SNOW_LINE: i32 = 170;
+/// Density is worked out at the corners of cells this big and interpolated in between.
+const CELL: i32 = 4;
+/// How many corners there are along the height of the world.
+const LEVELS: usize = (HEIGHT / CELL) as usize + 1;
+/// How many corners there are along each side of a chunk.
+const CORNERS: usize = (16 / CELL) as usize + 1;
+
+/// xoroshiro128++, seeded with splitmix64 like vanilla's.
+#[derive(Debug, Clone)]
+struct Random {
+    low: u64,
+    high: u64,
+}
+
+impl Random {
+    /// A different stream for each name, so adding a noise doesn't change the others.
+    fn named(seed: i64, name: &str) -> Self {
+        // FNV-1a
+        let name = name.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3)
+        });
+        let seed = seed as u64 ^ name;
+        Self {
+            low: splitmix(seed),
+            high: splitmix(seed.wrapping_add(0x9e37_79b9_7f4a_7c15)),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let (low, high) = (self.low, self.high);
+        let result = low.wrapping_add(high).rotate_left(17).wrapping_add(low);
+        let high = high ^ low;
+        self.low = low.rotate_left(49) ^ high ^ (high << 21);
+        self.high = high.rotate_left(28);
+        result
+    }
+
+    /// From 0 up to but not including 1.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn next_below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+fn splitmix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// From 0 up to but not including 1, the same for the same block every time.
+fn positional(seed: i64, x: i32, y: i32, z: i32) -> f64 {
+    let hash = splitmix(
+        seed as u64
+            ^ (x as u64).wrapping_mul(0x2f0f_3ab5)
+            ^ ((y as u64).wrapping_mul(0x1b87_3593) << 20)
+            ^ ((z as u64).wrapping_mul(0x5bd1_e995) << 40),
+    );
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+const GRADIENTS: [[f64; 3]; 16] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+    [1.0, 1.0, 0.0],
+    [0.0, -1.0, 1.0],
+    [-1.0, 1.0, 0.0],
+    [0.0, -1.0, -1.0],
+];
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+/// Ken Perlin's improved noise, shuffled and moved by the seed.
+#[derive(Debug, Clone)]
+struct Perlin {
+    permutation: [u8; 256],
+    offset: [f64; 3],
+}
+
+impl Perlin {
+    fn new(random: &mut Random) -> Self {
+        let offset = [(); 3].map(|_| random.next_f64() * 256.0);
+        let mut permutation = [0; 256];
+        for (i, p) in permutation.iter_mut().enumerate() {
+            *p = i as u8;
+        }
+        for i in (1..permutation.len()).rev() {
+            permutation.swap(i, random.next_below(i + 1));
+        }
+        Self {
+            permutation,
+            offset,
+        }
+    }
+
+    fn hash(&self, i: i32) -> i32 {
+        self.permutation[(i & 255) as usize].into()
+    }
+
+    /// About -1 to 1.
+    fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (x, y, z) = (x + self.offset[0], y + self.offset[1], z + self.offset[2]);
+        let (floor_x, floor_y, floor_z) = (x.floor(), y.floor(), z.floor());
+        let (xi, yi, zi) = (floor_x as i32, floor_y as i32, floor_z as i32);
+        let (x, y, z) = (x - floor_x, y - floor_y, z - floor_z);
+
+        let grad = |hash: i32, x: f64, y: f64, z: f64| {
+            let gradient = GRADIENTS[(hash & 15) as usize];
+            gradient[0] * x + gradient[1] * y + gradient[2] * z
+        };
+        let (a, b) = (self.hash(xi), self.hash(xi + 1));
+        let (aa, ab) = (self.hash(a + yi), self.hash(a + yi + 1));
+        let (ba, bb) = (self.hash(b + yi), self.hash(b + yi + 1));
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let near = lerp(
+            v,
+            lerp(
+                u,
+                grad(self.hash(aa + zi), x, y, z),
+                grad(self.hash(ba + zi), x - 1.0, y, z),
+            ),
+            lerp(
+                u,
+                grad(self.hash(ab + zi), x, y - 1.0, z),
+                grad(self.hash(bb + zi), x - 1.0, y - 1.0, z),
+            ),
+        );
+        let far = lerp(
+            v,
+            lerp(
+                u,
+                grad(self.hash(aa + zi + 1), x, y, z - 1.0),
+                grad(self.hash(ba + zi + 1), x - 1.0, y, z - 1.0),
+            ),
+            lerp(
+                u,
+                grad(self.hash(ab + zi + 1), x, y - 1.0, z - 1.0),
+                grad(self.hash(bb + zi + 1), x - 1.0, y - 1.0, z - 1.0),
+            ),
+        );
+        lerp(w, near, far)
+    }
+}
+
+/// Layers of noise, each twice as detailed and half as strong as the one before.
+#[derive(Debug, Clone)]
+struct Octaves {
+    octaves: Vec<Perlin>,
+    /// Of the first octave, per block.
+    frequency: f64,
+}
+
+impl Octaves {
+    fn new(seed: i64, name: &str, count: usize, frequency: f64) -> Self {
+        let mut random = Random::named(seed, name);
+        Self {
+            octaves: (0..count).map(|_| Perlin::new(&mut random)).collect(),
+            frequency,
+        }
+    }
+
+    /// About -1 to 1, though mostly much closer to 0.
+    fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (mut frequency, mut amplitude) = (self.frequency, 1.0);
+        let (mut total, mut max) = (0.0, 0.0);
+        for octave in &self.octaves {
+            total += octave.sample(x * frequency, y * frequency, z * frequency) * amplitude;
+            max += amplitude;
+            frequency *= 2.0;
+            amplitude /= 2.0;
+        }
+        total / max
+    }
+}
+
+/// The large scale shape of the world at a column, each from -1 to 1.
+#[derive(Debug, Clone, Copy)]
+struct Climate {
+    temperature: f64,
+    humidity: f64,
+    /// How far inland it is, oceans are below 0.
+    continentalness: f64,
+    /// Low where mountains are, high where it's flat.
+    erosion: f64,
+    /// Peaks rise along where this is close to 0.
+    ridges: f64,
+}
+
+impl Climate {
+    /// How high the ground is, before it's made rougher.
+    fn height(&self) -> f64 {
+        const COAST: [(f64, f64); 7] = [
+            (-1.0, 20.0),
+            (-0.5, 35.0),
+            (-0.2, 50.0),
+            (-0.05, 61.0),
+            (0.05, 65.0),
+            (0.4, 78.0),
+            (1.0, 95.0),
+        ];
+
+        let c = self.continentalness;
+        let coast = COAST.windows(2).find(|points| c <= points[1].0).map_or(
+            COAST[COAST.len() - 1].1,
+            |points| {
+                let ((x0, y0), (x1, y1)) = (points[0], points[1]);
+                lerp(((c - x0) / (x1 - x0)).clamp(0.0, 1.0), y0, y1)
+            },
+        );
+
+        // mountains only rise inland, where the erosion is low
+        let inland = ((c - 0.05) / 0.5).clamp(0.0, 1.0);
+        let peaks = 1.0 - self.ridges.abs();
+        coast + inland * (self.ruggedness() * 140.0 * peaks * peaks + 10.0 * self.ridges)
+    }
+
+    /// From 0 where it's flat to 1 where it's most mountainous.
+    fn ruggedness(&self) -> f64 {
+        (0.3 - self.erosion).clamp(0.0, 1.0)
+    }
+}
+
+/// What the top of the ground is made of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Surface {
+    Grass,
+    /// Grass covered in snow, with frozen water.
+    Snow,
+    Sand,
+    Stone,
+}
+
+/// Where biomes are placed, by the temperature, humidity, continentalness and erosion they fit best.
+/// Only the ones in the biome registry are placed, and the closest one to the climate of a column wins.
+const BIOMES: [(&str, [f64; 4], Surface); 8] = [
+    (
+        "minecraft:deep_ocean",
+        [0.0, 0.0, -0.8, 0.0],
+        Surface::Grass,
+    ),
+    ("minecraft:ocean", [0.0, 0.0, -0.35, 0.0], Surface::Grass),
+    ("minecraft:beach", [0.2, 0.0, -0.05, 0.3], Surface::Sand),
+    ("minecraft:plains", [0.2, -0.2, 0.25, 0.3], Surface::Grass),
+    ("minecraft:forest", [0.1, 0.4, 0.3, 0.2], Surface::Grass),
+    ("minecraft:desert", [0.8, -0.6, 0.3, 0.3], Surface::Sand),
+    (
+        "minecraft:snowy_plains",
+        [-0.8, 0.0, 0.3, 0.3],
+        Surface::Snow,
+    ),
+    (
+        "minecraft:stony_peaks",
+        [0.3, 0.0, 0.7, -0.8],
+        Surface::Stone,
+    ),
+];
+
+/// The blocks terrain is made of, looked up once.
+#[derive(Debug, Clone)]
+struct Palette {
+    stone: BlockState,
+    deepslate: BlockState,
+    bedrock: BlockState,
+    water: BlockState,
+    lava: BlockState,
+    ice: BlockState,
+    grass: BlockState,
+    snowy_grass: BlockState,
+    dirt: BlockState,
+    sand: BlockState,
+    sandstone: BlockState,
+    gravel: BlockState,
+    snow: BlockState,
+    snow_block: BlockState,
+}
+
+impl Palette {
+    fn new() -> Self {
+        let block = |name| BlockState::from_name(name).unwrap_or(BlockState::STONE);
+        let grass = BlockState::GRASS_BLOCK;
+        Self {
+            stone: BlockState::STONE,
+            deepslate: block("minecraft:deepslate"),
+            bedrock: BlockState::BEDROCK,
+            water: block("minecraft:water"),
+            lava: block("minecraft:lava"),
+            ice: block("minecraft:ice"),
+            grass,
+            snowy_grass: grass.with("snowy", true).unwrap_or(grass),
+            dirt: BlockState::DIRT,
+            sand: block("minecraft:sand"),
+            sandstone: block("minecraft:sandstone"),
+            gravel: block("minecraft:gravel"),
+            snow: block("minecraft:snow"),
+            snow_block: block("minecraft:snow_block"),
+        }
+    }
+}
+
+/// Vanilla-like terrain: oceans, beaches, plains and mountains, with caves underneath and biomes placed by climate,
+/// all worked out from the seed so the same seed always gives the same world.
+#[derive(Debug, Clone)]
+pub struct NoiseGenerator {
+    seed: i64,
+    temperature: Octaves,
+    humidity: Octaves,
+    continentalness: Octaves,
+    erosion: Octaves,
+    ridges: Octaves,
+    /// Roughens the ground, making overhangs and floating bits in mountains.
+    terrain: Octaves,
+    /// Long, winding tunnels where both are close to 0.
+    spaghetti: [Octaves; 2],
+    /// Big caverns deep down.
+    cheese: Octaves,
+    /// The biomes from [`BIOMES`] that are registered, by their id.
+    biomes: Vec<(u16, [f64; 4], Surface)>,
+    palette: Palette,
+}
+
+impl NoiseGenerator {
+    pub fn new(seed: i64, biomes: &Registry<WorldgenBiome>) -> Self {
+        let noise = |name, count, frequency| Octaves::new(seed, name, count, frequency);
+        Self {
+            seed,
+            temperature: noise("minecraft:temperature", 4, 1.0 / 1024.0),
+            humidity: noise("minecraft:vegetation", 4, 1.0 / 768.0),
+            continentalness: noise("minecraft:continentalness", 5, 1.0 / 1024.0),
+            erosion: noise("minecraft:erosion", 4, 1.0 / 768.0),
+            ridges: noise("minecraft:ridge", 4, 1.0 / 384.0),
+            terrain: noise("minecraft:terrain", 3, 1.0 / 96.0),
+            spaghetti: [
+                noise("minecraft:spaghetti_3d_1", 2, 1.0 / 64.0),
+                noise("minecraft:spaghetti_3d_2", 2, 1.0 / 64.0),
+            ],
+            cheese: noise("minecraft:cave_cheese", 2, 1.0 / 80.0),
+            biomes: BIOMES
+                .iter()
+                .filter_map(|(name, climate, surface)| {
+                    Some((biomes.id(name)? as u16, *climate, *surface))
+                })
+                .collect(),
+            palette: Palette::new(),
+        }
+    }
+
+    fn climate(&self, x: f64, z: f64) -> Climate {
+        // stretched out so the whole range is actually reached
+        let sample = |noise: &Octaves| (noise.sample(x, 0.0, z) * 2.0).clamp(-1.0, 1.0);
+        Climate {
+            temperature: sample(&self.temperature),
+            humidity: sample(&self.humidity),
+            continentalness: sample(&self.continentalness),
+            erosion: sample(&self.erosion),
+            ridges: sample(&self.ridges),
+        }
+    }
+
+    /// Without any registered biomes to choose from, it's the first biome with grass.
+    fn biome(&self, climate: &Climate) -> (u16, Surface) {
+        let point = [
+            climate.temperature,
+            climate.humidity,
+            // whether it's ocean or land matters most
+            climate.continentalness * 2.0,
+            climate.erosion,
+        ];
+        self.biomes
+            .iter()
+            .map(|(id, target, surface)| {
+                let distance = point
+                    .iter()
+                    .zip([target[0], target[1], target[2] * 2.0, target[3]])
+                    .map(|(a, b)| (a - b) * (a - b))
+                    .sum::<f64>();
+                (distance, *id, *surface)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map_or((0, Surface::Grass), |(_, id, surface)| (id, surface))
+    }
+
+    /// Above 0 inside the ground, below 0 in the air and in caves.
+    fn density(&self, x: f64, y: f64, z: f64, climate: &Climate, height: f64) -> f64 {
+        let roughness = 0.3 + 0.5 * climate.ruggedness();
+        let mut density = (height - y) / 24.0 + self.terrain.sample(x, y, z) * roughness;
+
+        // keep the sea floor and shores closed, but let caves open up inland
+        let depth = height - y;
+        if y > f64::from(MIN_Y + 4) && (depth > 12.0 || height > f64::from(SEA_LEVEL + 2)) {
+            let tunnel = self
+                .spaghetti
+                .iter()
+                .map(|noise| noise.sample(x, y * 1.5, z).abs())
+                .fold(0.0, f64::max)
+                - 0.04;
+            let cheese = if y < 30.0 {
+                0.35 - self.cheese.sample(x, y * 2.0, z)
+            } else {
+                f64::INFINITY
+            };
+            density = density.min(tunnel.min(cheese) * 10.0);
+        }
+        density
+    }
+
+    fn bedrock(&self, x: i32, y: i32, z: i32) -> bool {
+        y == MIN_Y
+            || (y < MIN_Y + 5 && positional(self.seed, x, y, z) < f64::from(MIN_Y + 5 - y) / 5.0)
+    }
+
+    /// Stone turns into deepslate gradually from 8 down to 0.
+    fn stone(&self, x: i32, y: i32, z: i32) -> BlockState {
+        if y < 0 || (y < 8 && positional(self.seed ^ 1, x, y, z) < f64::from(8 - y) / 8.0) {
+            self.palette.deepslate
+        } else {
+            self.palette.stone
+        }
+    }
+
+    /// The block `depth` blocks below the top of the ground at `top`, or `None` if it's just stone.
+    fn surface(&self, surface: Surface, top: i32, depth: i32) -> Option<BlockState> {
+        let palette = &self.palette;
+        if top < SEA_LEVEL - 1 {
+            return (depth < 3).then_some(if top < SEA_LEVEL - 12 {
+                palette.gravel
+            } else {
+                palette.sand
+            });
+        }
+
+        match surface {
+            Surface::Stone => (depth == 0 && top >= SNOW_LINE).then_some(palette.snow_block),
+            Surface::Sand => match depth {
+                0..=3 => Some(palette.sand),
+                4..=6 => Some(palette.sandstone),
+                _ => None,
+            },
+            // beaches wherever grass meets the water
+            Surface::Grass | Surface::Snow if top <= SEA_LEVEL + 1 => {
+                (depth < 4).then_some(palette.sand)
+            }
+            Surface::Grass | Surface::Snow => match depth {
+                0 if top >= SNOW_LINE => Some(palette.snow_block),
+                0 if surface == Surface::Snow => Some(palette.snowy_grass),
+                0 => Some(palette.grass),
+                1..=3 => Some(palette.dirt),
+                _ => None,
+            },
+        }
+    }
+
+    /// Density at the corners of the cells in the chunk, by x, then z, then height.
+    fn corners(&self, base_x: i32, base_z: i32) -> Vec<f64> {
+        let mut corners = Vec::with_capacity(CORNERS * CORNERS * LEVELS);
+        for corner_x in 0..CORNERS as i32 {
+            for corner_z in 0..CORNERS as i32 {
+                let x = f64::from(base_x + corner_x * CELL);
+                let z = f64::from(base_z + corner_z * CELL);
+                let climate = self.climate(x, z);
+                let height = climate.height();
+                corners.extend((0..LEVELS as i32).map(|level| {
+                    self.density(x, f64::from(MIN_Y + level * CELL), z, &climate, height)
+                }));
+            }
+        }
+        corners
+    }
+
+    /// The density at a block of the chunk, from the corners of the cell it's in.
+    fn interpolate(corners: &[f64], x: i32, y: i32, z: i32) -> f64 {
+        let y = y - MIN_Y;
+        let (cell_x, cell_y, cell_z) = (x / CELL, y / CELL, z / CELL);
+        let cell = |t: i32| f64::from(t % CELL) / f64::from(CELL);
+        let (tx, ty, tz) = (cell(x), cell(y), cell(z));
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            let column = (cell_x + dx) as usize * CORNERS + (cell_z + dz) as usize;
+            corners[column * LEVELS + (cell_y + dy) as usize]
+        };
+
+        let bottom = lerp(
+            tz,
+            lerp(tx, corner(0, 0, 0), corner(1, 0, 0)),
+            lerp(tx, corner(0, 0, 1), corner(1, 0, 1)),
+        );
+        let top = lerp(
+            tz,
+            lerp(tx, corner(0, 1, 0), corner(1, 1, 0)),
+            lerp(tx, corner(0, 1, 1), corner(1, 1, 1)),
+        );
+        lerp(ty, bottom, top)
+    }
+
+    /// Fills a column from the top down, so it knows how deep into the ground each block is.
+    fn fill_column(
+        &self,
+        chunk: &mut Chunk,
+        corners: &[f64],
+        (x, z): (i32, i32),
+        (world_x, world_z): (i32, i32),
+        surface: Surface,
+    ) {
+        let palette = &self.palette;
+        // the first block of ground from the top
+        let mut top = None;
+        // how many blocks of ground there are above, until the first gap
+        let mut depth = Some(0);
+
+        for y in (MIN_Y..MIN_Y + HEIGHT).rev() {
+            let solid = y == MIN_Y || Self::interpolate(corners, x, y, z) > 0.0;
+            let state = if self.bedrock(world_x, y, world_z) {
+                palette.bedrock
+            } else if solid {
+                let top = *top.get_or_insert_with(|| {
+                    if surface == Surface::Snow && y + 1 >= SEA_LEVEL {
+                        chunk.set_block(x, y + 1, z, palette.snow);
+                    }
+                    y
+                });
+                let block = depth.and_then(|depth| self.surface(surface, top, depth));
+                depth = depth.map(|depth| depth + 1);
+                block.unwrap_or_else(|| self.stone(world_x, y, world_z))
+            } else {
+                if top.is_some() {
+                    depth = None;
+                }
+                match top {
+                    None if y == SEA_LEVEL - 1 && surface == Surface::Snow => palette.ice,
+                    None if y < SEA_LEVEL => palette.water,
+                    Some(_) if y < LAVA_LEVEL => palette.lava,
+                    _ => continue,
+                }
+            };
+            chunk.set_block(x, y, z, state);
+        }
+    }
+}
+
+impl ChunkGenerator for NoiseGenerator {
+    fn generate(&self, (chunk_x, chunk_z): (i32, i32), chunk: &mut Chunk) {
+        let (base_x, base_z) = (chunk_x * 16, chunk_z * 16);
+
+        // a biome for each column of 4×4 cells, at its middle
+        let mut surfaces = [[Surface::Grass; 4]; 4];
+        for (cell_x, row) in surfaces.iter_mut().enumerate() {
+            for (cell_z, surface) in row.iter_mut().enumerate() {
+                let x = cell_x as i32 * 4;
+                let z = cell_z as i32 * 4;
+                let climate = self.climate(f64::from(base_x + x + 2), f64::from(base_z + z + 2));
+                let (biome, biome_surface) = self.biome(&climate);
+                *surface = biome_surface;
+                for y in (MIN_Y..MIN_Y + HEIGHT).step_by(4) {
+                    chunk.set_biome(x, y, z, biome);
+                }
+            }
+        }
+
+        let corners = self.corners(base_x, base_z);
+        for x in 0..16 {
+            for z in 0..16 {
+                let surface = surfaces[(x / 4) as usize][(z / 4) as usize];
+                self.fill_column(chunk, &corners, (x, z), (base_x + x, base_z + z), surface);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terrain() {
+        let generate = |seed, position| {
+            let mut chunk = Chunk::empty();
+            NoiseGenerator::new(seed, &Registry::default()).generate(position, &mut chunk);
+            chunk
+        };
+
+        let chunk = generate(7, (3, -2));
+        assert_eq!(chunk.data(1), generate(7, (3, -2)).data(1));
+        assert_ne!(chunk.data(1), generate(8, (3, -2)).data(1));
+
+        for (x, z) in [(0, 0), (5, 11), (15, 15)] {
+            assert_eq!(chunk.block(x, MIN_Y, z), BlockState::BEDROCK);
+            // water or ground up to sea level
+            let top = chunk.top(x, z).unwrap();
+            assert!(top >= SEA_LEVEL - 1);
+            assert!(top < MIN_Y + HEIGHT);
+        }
+    }
+}
@@ -0,0 +1,457 @@
+//! Players breaking and placing blocks, and opening the ones with containers.
+//!
+//! The client predicts what happens and numbers its predictions. The server checks each one,
+//! sends the actual blocks back if it disagrees, and then acknowledges the sequence number,
+//! so players can't reach too far, break blocks too fast, or build in adventure mode.
+
+use std::time::{Duration, Instant};
+
+use bevy::{math::DVec3, prelude::*};
+use oxcr_protocol::{
+    model::{
+        block::{BlockState, Tool},
+        item::ItemStack,
+        packets::play::{
+            AcknowledgeBlockChange, BlockUpdate, GameMode, PlayerAction, SetBlockDestroyStage,
+            UseItemOn,
+        },
+        VarInt,
+    },
+    PlayerN, PlayerNet,
+};
+
+use crate::{
+    inventory::{self, Inventory},
+    model::{Joined, OnGround, PlayerGameMode, Position, Sneaking},
+    tracker::network_id,
+    world::{block_pos, chunk_of, location, Chunks, LoadedChunks},
+};
+
+/// How far players can reach, squared, from their eyes to the middle of a block. The same as vanilla.
+const MAX_REACH_SQUARED: f64 = 6.0 * 6.0;
+const EYE_HEIGHT: f64 = 1.62;
+/// The width and height of players.
+const PLAYER_SIZE: (f64, f64) = (0.6, 1.8);
+
+const TICK: Duration = Duration::from_millis(50);
+/// Breaking a block can be finished once it's this far along, since the client and server don't tick in step.
+const FINISH_TOLERANCE: f32 = 0.7;
+/// Sent as the destroy stage to remove the cracks.
+const NO_CRACKS: u8 = 255;
+
+/// The block a player in survival is breaking.
+#[derive(Component, Debug, Clone)]
+pub struct Digging {
+    pub position: IVec3,
+    started: Instant,
+    /// How far along breaking the block is after each tick, from 0 to 1.
+    progress_per_tick: f32,
+    /// The cracks others were last sent.
+    stage: Option<u8>,
+}
+
+impl Digging {
+    fn progress(&self) -> f32 {
+        let ticks = self.started.elapsed().as_millis() / TICK.as_millis();
+        self.progress_per_tick * (ticks + 1) as f32
+    }
+}
+
+/// The kind and speed of a tool, by the name of the item.
+fn tool(item: &ItemStack) -> Option<(Tool, f32)> {
+    let name = item.name()?;
+    let name = name.strip_prefix("minecraft:")?;
+
+    let (tier, kind) = name.split_once('_')?;
+    let speed = match tier {
+        "wooden" => 2.0,
+        "stone" => 4.0,
+        "iron" => 6.0,
+        "diamond" => 8.0,
+        "netherite" => 9.0,
+        "golden" => 12.0,
+        _ => return None,
+    };
+    let tool = match kind {
+        "pickaxe" => Tool::Pickaxe,
+        "shovel" => Tool::Shovel,
+        "axe" => Tool::Axe,
+        "hoe" => Tool::Hoe,
+        _ => return None,
+    };
+
+    Some((tool, speed))
+}
+
+/// How much of the block is broken each tick, the same way vanilla works it out,
+/// except for enchantments and effects.
+fn progress_per_tick(state: BlockState, held: Option<&ItemStack>, on_ground: bool) -> f32 {
+    let hardness = state.hardness();
+    if hardness < 0.0 {
+        return 0.0;
+    }
+    if hardness == 0.0 {
+        return 1.0;
+    }
+
+    let right_tool = held
+        .and_then(tool)
+        .filter(|(tool, _)| state.tool() == Some(*tool));
+    let mut speed = right_tool.map_or(1.0, |(_, speed)| speed);
+    if !on_ground {
+        speed /= 5.0;
+    }
+
+    // blocks that need a tool take longer without one
+    let penalty = if state.needs_tool() && right_tool.is_none() {
+        100.0
+    } else {
+        30.0
+    };
+    speed / hardness / penalty
+}
+
+/// Whether the player can reach the block, and has the chunk it's in.
+fn in_reach(world: &World, player: Entity, position: IVec3) -> bool {
+    let Some(feet) = world.get::<Position>(player) else {
+        return false;
+    };
+    let eyes = feet.0 + DVec3::new(0.0, EYE_HEIGHT, 0.0);
+    let center = position.as_dvec3() + DVec3::splat(0.5);
+    if eyes.distance_squared(center) > MAX_REACH_SQUARED {
+        return false;
+    }
+
+    world
+        .get::<LoadedChunks>(player)
+        .is_some_and(|loaded| loaded.0.contains(&chunk_of(position)))
+}
+
+/// Checks that the player can change the block, and returns their game mode.
+fn check_edit(world: &World, player: Entity, position: IVec3) -> Option<GameMode> {
+    let game_mode = world.get::<PlayerGameMode>(player)?.0;
+    if matches!(game_mode, GameMode::Adventure | GameMode::Spectator) {
+        return None;
+    }
+
+    in_reach(world, player, position).then_some(game_mode)
+}
+
+/// Sends the actual blocks, so that the client undoes what it predicted.
+fn resync(world: &World, net: &PlayerNet, positions: &[IVec3]) {
+    let chunks = world.resource::<Chunks>();
+    for position in positions {
+        if let (Some(state), Some(location)) = (chunks.block(*position), location(*position)) {
+            net.queue(BlockUpdate {
+                location,
+                block_id: state.into(),
+            });
+        }
+    }
+}
+
+fn send_destroy_stage(world: &mut World, player: Entity, position: IVec3, stage: u8) {
+    let Some(location) = location(position) else {
+        return;
+    };
+    let packet = SetBlockDestroyStage {
+        entity_id: network_id(player),
+        location,
+        stage,
+    };
+
+    let mut viewers = world.query_filtered::<(Entity, &PlayerN, &LoadedChunks), With<Joined>>();
+    for (viewer, net, loaded) in viewers.iter(world) {
+        if viewer != player && loaded.0.contains(&chunk_of(position)) {
+            net.0.queue(packet.clone());
+        }
+    }
+}
+
+fn stop_digging(world: &mut World, player: Entity) -> Option<Digging> {
+    let digging = world.entity_mut(player).take::<Digging>()?;
+    if digging.stage.is_some() {
+        send_destroy_stage(world, player, digging.position, NO_CRACKS);
+    }
+    Some(digging)
+}
+
+/// Starting, cancelling and finishing breaking a block, and dropping items.
+pub fn player_action(world: &mut World, player: Entity, action: PlayerAction) {
+    let Some(net) = world.get::<PlayerN>(player).map(|net| net.0.clone()) else {
+        return;
+    };
+    let position = block_pos(&action.location);
+
+    match action.status {
+        PlayerAction::START_DIGGING => {
+            stop_digging(world, player);
+            if !start_digging(world, player, position) {
+                resync(world, &net, &[position]);
+            }
+        }
+        PlayerAction::CANCEL_DIGGING => {
+            stop_digging(world, player);
+        }
+        PlayerAction::FINISH_DIGGING => {
+            let finished = stop_digging(world, player).is_some_and(|digging| {
+                digging.position == position && digging.progress() >= FINISH_TOLERANCE
+            });
+            if !(finished
+                && check_edit(world, player, position).is_some()
+                && break_block(world, position))
+            {
+                debug!(addr = %net.peer_addr, ?position, "finished breaking a block too early");
+                resync(world, &net, &[position]);
+            }
+        }
+        PlayerAction::DROP_ITEM | PlayerAction::DROP_ITEM_STACK => {
+            inventory::drop_held(
+                world,
+                player,
+                action.status == PlayerAction::DROP_ITEM_STACK,
+            );
+            return;
+        }
+        // no acknowledgement for eating, shooting and swapping hands, which don't change blocks
+        _ => return,
+    }
+
+    net.queue(AcknowledgeBlockChange {
+        sequence: action.sequence,
+    });
+}
+
+/// Returns whether the player may start breaking the block, and breaks it right away if they're in creative
+/// or it breaks in a single tick.
+fn start_digging(world: &mut World, player: Entity, position: IVec3) -> bool {
+    let Some(game_mode) = check_edit(world, player, position) else {
+        return false;
+    };
+    let Some(state) = world.resource::<Chunks>().block(position) else {
+        return false;
+    };
+    let held = world
+        .get::<Inventory>(player)
+        .and_then(|inventory| inventory.held().cloned());
+
+    if game_mode == GameMode::Creative {
+        // swords don't break blocks in creative
+        let sword = held
+            .as_ref()
+            .and_then(|item| item.name())
+            .is_some_and(|name| name.ends_with("_sword"));
+        return !sword && break_block(world, position);
+    }
+
+    let on_ground = world
+        .get::<OnGround>(player)
+        .is_some_and(|on_ground| on_ground.0);
+    let progress_per_tick = progress_per_tick(state, held.as_ref(), on_ground);
+    if progress_per_tick >= 1.0 {
+        return break_block(world, position);
+    }
+    if progress_per_tick <= 0.0 {
+        return false;
+    }
+
+    world.entity_mut(player).insert(Digging {
+        position,
+        started: Instant::now(),
+        progress_per_tick,
+        stage: None,
+    });
+    true
+}
+
+/// Returns whether there was a block to break.
+fn break_block(world: &mut World, position: IVec3) -> bool {
+    if !inventory::remove_block_container(world, position) {
+        return false;
+    }
+    world
+        .resource_mut::<Chunks>()
+        .set_block(position, BlockState::AIR)
+        .is_some_and(|old| !old.is_air())
+}
+
+/// Places the block of the held item against the clicked face, or into the clicked block if it can be replaced.
+pub fn use_item_on(world: &mut World, player: Entity, packet: UseItemOn) {
+    let Some(net) = world.get::<PlayerN>(player).map(|net| net.0.clone()) else {
+        return;
+    };
+    let clicked = block_pos(&packet.location);
+    let Some(face) = usize::try_from(packet.face.0)
+        .ok()
+        .and_then(|face| UseItemOn::FACES.get(face))
+    else {
+        debug!(addr = %net.peer_addr, face = packet.face.0, "clicked a face that doesn't exist");
+        return;
+    };
+
+    if open_block(world, player, clicked) {
+        net.queue(AcknowledgeBlockChange {
+            sequence: packet.sequence,
+        });
+        return;
+    }
+
+    let placed = place_block(world, player, clicked, IVec3::from(*face), packet.hand);
+    match placed {
+        Ok(()) => {}
+        // nothing to place, the client doesn't predict anything then
+        Err(None) => {}
+        Err(Some(target)) => {
+            resync(world, &net, &[clicked, target]);
+            if let Some(mut inventory) = world.get_mut::<Inventory>(player) {
+                let index = inventory.held_slot(packet.hand == UseItemOn::OFF_HAND);
+                net.queue(inventory.slot_packet(index));
+            }
+        }
+    }
+
+    net.queue(AcknowledgeBlockChange {
+        sequence: packet.sequence,
+    });
+}
+
+/// Opens the container of the clicked block, unless the player is sneaking with an item in their hands,
+/// in which case they place the item against it instead.
+fn open_block(world: &mut World, player: Entity, clicked: IVec3) -> bool {
+    let sneaking = world
+        .get::<Sneaking>(player)
+        .is_some_and(|sneaking| sneaking.0);
+    let holding = world.get::<Inventory>(player).is_some_and(|inventory| {
+        [false, true]
+            .into_iter()
+            .any(|off_hand| inventory.slots[inventory.held_slot(off_hand)].is_some())
+    });
+    let spectator = world
+        .get::<PlayerGameMode>(player)
+        .map_or(true, |game_mode| game_mode.0 == GameMode::Spectator);
+
+    !(sneaking && holding)
+        && !spectator
+        && in_reach(world, player, clicked)
+        && inventory::open_block(world, player, clicked)
+}
+
+/// On failure, returns where the block would have been placed if the client could have predicted it.
+fn place_block(
+    world: &mut World,
+    player: Entity,
+    clicked: IVec3,
+    face: IVec3,
+    hand: VarInt,
+) -> Result<(), Option<IVec3>> {
+    let off_hand = hand == UseItemOn::OFF_HAND;
+    let (index, stack) = world
+        .get::<Inventory>(player)
+        .and_then(|inventory| {
+            let index = inventory.held_slot(off_hand);
+            Some((index, inventory.slots[index].clone()?))
+        })
+        .ok_or(None)?;
+    let state = BlockState::from_item(stack.item).ok_or(None)?;
+
+    let chunks = world.resource::<Chunks>();
+    let target = if chunks
+        .block(clicked)
+        .is_some_and(BlockState::is_replaceable)
+    {
+        clicked
+    } else {
+        clicked + face
+    };
+
+    let game_mode = check_edit(world, player, clicked).ok_or(Some(target))?;
+    check_edit(world, player, target).ok_or(Some(target))?;
+    if !chunks.block(target).is_some_and(BlockState::is_replaceable) {
+        return Err(Some(target));
+    }
+    if intersects_player(world, target) {
+        return Err(Some(target));
+    }
+
+    world
+        .resource_mut::<Chunks>()
+        .set_block(target, state)
+        .ok_or(Some(target))?;
+
+    if game_mode != GameMode::Creative {
+        if let Some(mut inventory) = world.get_mut::<Inventory>(player) {
+            inventory.slots[index] = (stack.count > 1).then(|| ItemStack {
+                count: stack.count - 1,
+                ..stack
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Whether a player is standing where the block would go.
+fn intersects_player(world: &mut World, position: IVec3) -> bool {
+    let (min, max) = (position.as_dvec3(), position.as_dvec3() + DVec3::ONE);
+    let (width, height) = PLAYER_SIZE;
+
+    let mut players = world.query_filtered::<(&Position, &PlayerGameMode), With<Joined>>();
+    players.iter(world).any(|(feet, game_mode)| {
+        let feet = feet.0;
+        game_mode.0 != GameMode::Spectator
+            && feet.x - width / 2.0 < max.x
+            && feet.x + width / 2.0 > min.x
+            && feet.y < max.y
+            && feet.y + height > min.y
+            && feet.z - width / 2.0 < max.z
+            && feet.z + width / 2.0 > min.z
+    })
+}
+
+/// Shows everyone else the cracks of the blocks players are breaking.
+pub fn broadcast_destroy_stages(
+    mut diggers: Query<(Entity, &mut Digging)>,
+    viewers: Query<(Entity, &PlayerN, &LoadedChunks), With<Joined>>,
+) {
+    for (player, mut digging) in &mut diggers {
+        let stage = (digging.progress() * 10.0).clamp(0.0, 9.0) as u8;
+        if digging.stage == Some(stage) {
+            continue;
+        }
+        digging.stage = Some(stage);
+
+        let Some(location) = location(digging.position) else {
+            continue;
+        };
+        for (viewer, net, loaded) in &viewers {
+            if viewer != player && loaded.0.contains(&chunk_of(digging.position)) {
+                net.0.queue(SetBlockDestroyStage {
+                    entity_id: network_id(player),
+                    location: location.clone(),
+                    stage,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn break_time() {
+        let ticks = |state, item: Option<&str>, on_ground| {
+            let held = item.and_then(|name| ItemStack::new(name, 1));
+            (1.0 / progress_per_tick(state, held.as_ref(), on_ground)).ceil() as u32
+        };
+
+        // the same as the wiki's breaking time table
+        assert_eq!(ticks(BlockState::STONE, None, true), 150);
+        assert_eq!(ticks(BlockState::STONE, Some("wooden_pickaxe"), true), 23);
+        assert_eq!(ticks(BlockState::STONE, Some("diamond_shovel"), true), 150);
+        assert_eq!(ticks(BlockState::DIRT, None, true), 15);
+        assert_eq!(ticks(BlockState::DIRT, Some("iron_shovel"), true), 3);
+        assert_eq!(ticks(BlockState::DIRT, None, false), 75);
+        assert_eq!(progress_per_tick(BlockState::BEDROCK, None, true), 0.0);
+    }
+}
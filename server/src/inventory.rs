@@ -0,0 +1,1078 @@
+//! Player inventories, containers like chests, and clicking around in their windows.
+//!
+//! Clicks are worked out on the server the same way the client predicts them. Whatever the client
+//! got wrong is corrected with slot updates, and everything is sent again if it clicked on outdated contents.
+//! Items thrown out of a window are dropped in front of the player.
+//!
+//! Blocks like chests get a [`Container`] the first time they're opened, which is saved with the world.
+
+use std::collections::HashMap;
+
+use bevy::{math::DVec3, prelude::*};
+use oxcr_protocol::{
+    model::{
+        block::BlockState,
+        chat::ChatComponent,
+        item::{ItemStack, Slot},
+        packets::play::{
+            ClickContainer, CloseContainer, GameMode, OpenScreen, SetContainerContent,
+            SetContainerSlot, SetCreativeModeSlot,
+        },
+        VarInt,
+    },
+    ser::{Array, Json},
+    PlayerN,
+};
+
+use crate::{
+    config::Config,
+    dropped_items::{drop_item, spawn_item, BLOCK_PICKUP_DELAY},
+    model::PlayerGameMode,
+    storage,
+    world::Chunks,
+};
+
+/// The slots of the player's inventory window, in the order the client numbers them.
+pub const INVENTORY_SIZE: usize = 46;
+const CRAFTING_RESULT: usize = 0;
+const CRAFTING_GRID: std::ops::Range<usize> = 1..5;
+const MAIN: std::ops::Range<usize> = 9..36;
+const HOTBAR: std::ops::Range<usize> = 36..45;
+const OFFHAND: usize = 45;
+
+/// The id of the player's inventory window, which is always open as far as the client is concerned.
+const INVENTORY_WINDOW: u8 = 0;
+/// Container windows get ids from 1 up to this, then start over.
+const MAX_WINDOW_ID: u8 = 100;
+
+#[derive(Component, Debug)]
+pub struct Inventory {
+    /// Numbered like the slots of the inventory window: the crafting result and grid, armor,
+    /// the main inventory, the hotbar and the offhand.
+    pub slots: Vec<Slot>,
+    /// Which hotbar slot is selected, from 0 to 8.
+    pub selected: u8,
+    /// The item on the cursor.
+    pub carried: Slot,
+    state_id: i32,
+    drag: Option<Drag>,
+    last_window_id: u8,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            slots: vec![None; INVENTORY_SIZE],
+            selected: 0,
+            carried: None,
+            state_id: 0,
+            drag: None,
+            last_window_id: 0,
+        }
+    }
+}
+
+impl Inventory {
+    /// Puts the stack into the hotbar and main inventory, topping up stacks of the same item first.
+    /// Returns what didn't fit.
+    pub fn insert(&mut self, mut stack: ItemStack) -> Slot {
+        let slots = HOTBAR.chain(MAIN).collect::<Vec<_>>();
+        merge(&mut self.slots, &slots, &mut stack);
+        (stack.count > 0).then_some(stack)
+    }
+
+    /// Everything in the inventory window, for when the player joins or the client has to be corrected.
+    pub fn content_packet(&self) -> SetContainerContent {
+        SetContainerContent {
+            window_id: INVENTORY_WINDOW,
+            state_id: VarInt(self.state_id),
+            slots: Array::new(&self.slots),
+            carried: self.carried.clone(),
+        }
+    }
+
+    /// The slot of the item in the main hand, or in the offhand.
+    pub fn held_slot(&self, off_hand: bool) -> usize {
+        if off_hand {
+            OFFHAND
+        } else {
+            HOTBAR.start + usize::from(self.selected)
+        }
+    }
+
+    pub fn held(&self) -> Option<&ItemStack> {
+        self.slots[self.held_slot(false)].as_ref()
+    }
+
+    /// Tells the client what's in the slot after the server changed it.
+    pub fn slot_packet(&mut self, index: usize) -> SetContainerSlot {
+        self.state_id = next_state_id(self.state_id);
+        SetContainerSlot {
+            window_id: INVENTORY_WINDOW as i8,
+            state_id: VarInt(self.state_id),
+            slot: index as i16,
+            item: self.slots[index].clone(),
+        }
+    }
+}
+
+/// Something with slots that players can open, like a chest.
+#[derive(Component, Debug)]
+pub struct Container {
+    /// Which window the client shows for it, like [`OpenScreen::GENERIC_9X3`].
+    pub kind: VarInt,
+    pub title: ChatComponent,
+    pub slots: Vec<Slot>,
+}
+
+impl Container {
+    /// A chest with 3 rows of 9 slots.
+    pub fn chest() -> Self {
+        Self {
+            kind: OpenScreen::GENERIC_9X3,
+            title: ChatComponent::translatable("container.chest", vec![]),
+            slots: vec![None; 27],
+        }
+    }
+
+    /// The container of a block, if it has one. Chests are always single ones, since they aren't connected when placed.
+    fn of_block(state: BlockState) -> Option<Self> {
+        match state.name()? {
+            "chest" | "trapped_chest" => Some(Self::chest()),
+            _ => None,
+        }
+    }
+}
+
+/// The containers of blocks, by where the block is.
+#[derive(Resource, Debug, Default)]
+pub struct BlockContainers(pub HashMap<IVec3, Entity>);
+
+/// The container a player has open, on the player.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct OpenContainer {
+    pub window_id: u8,
+    pub container: Entity,
+    state_id: i32,
+}
+
+/// A drag across slots that's still going on.
+#[derive(Debug, Clone)]
+struct Drag {
+    kind: DragKind,
+    slots: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DragKind {
+    /// Splits the stack evenly.
+    Split,
+    /// Puts one item into each slot.
+    One,
+    /// Fills every slot with a full stack, only in creative.
+    Fill,
+}
+
+/// The click made no sense for the window, so the client is sent everything again.
+#[derive(Debug)]
+struct InvalidClick;
+
+/// The slots of a window taken out of the inventory and container, so that clicks work the same on both.
+///
+/// In container windows the container's slots come first, then the main inventory and the hotbar.
+/// The offhand isn't shown there, but it's at the end anyway since the client can swap with it.
+#[derive(Debug)]
+struct Window {
+    slots: Vec<Slot>,
+    carried: Slot,
+    drag: Option<Drag>,
+    /// How many of the slots belong to the container, `None` for the inventory window.
+    container: Option<usize>,
+    /// What was thrown out of the window, for the caller to drop.
+    dropped: Vec<ItemStack>,
+}
+
+impl Window {
+    fn new(inventory: &mut Inventory, container: Option<Vec<Slot>>) -> Self {
+        let (slots, len) = match container {
+            Some(mut slots) => {
+                let len = slots.len();
+                slots.extend_from_slice(&inventory.slots[MAIN.start..]);
+                (slots, Some(len))
+            }
+            None => (inventory.slots.clone(), None),
+        };
+
+        Self {
+            slots,
+            carried: inventory.carried.take(),
+            drag: inventory.drag.take(),
+            container: len,
+            dropped: vec![],
+        }
+    }
+
+    /// Puts the slots back, returns the container's.
+    fn write_back(self, inventory: &mut Inventory) -> Option<Vec<Slot>> {
+        inventory.carried = self.carried;
+        inventory.drag = self.drag;
+
+        match self.container {
+            Some(len) => {
+                let mut slots = self.slots;
+                inventory.slots[MAIN.start..].clone_from_slice(&slots.split_off(len));
+                Some(slots)
+            }
+            None => {
+                inventory.slots = self.slots;
+                None
+            }
+        }
+    }
+
+    /// How many slots the client shows.
+    fn visible(&self) -> usize {
+        match self.container {
+            Some(len) => len + MAIN.len() + HOTBAR.len(),
+            None => INVENTORY_SIZE,
+        }
+    }
+
+    fn hotbar(&self, index: usize) -> usize {
+        match self.container {
+            Some(len) => len + MAIN.len() + index,
+            None => HOTBAR.start + index,
+        }
+    }
+
+    fn offhand(&self) -> usize {
+        match self.container {
+            Some(len) => len + MAIN.len() + HOTBAR.len(),
+            None => OFFHAND,
+        }
+    }
+
+    /// Items can only be taken out of the crafting result.
+    fn placeable(&self, index: usize) -> bool {
+        self.container.is_some() || index != CRAFTING_RESULT
+    }
+
+    fn index(&self, slot: i16) -> Result<usize, InvalidClick> {
+        usize::try_from(slot)
+            .ok()
+            .filter(|index| *index < self.visible())
+            .ok_or(InvalidClick)
+    }
+
+    /// Applies a click.
+    fn click(
+        &mut self,
+        slot: i16,
+        button: i8,
+        mode: VarInt,
+        creative: bool,
+    ) -> Result<(), InvalidClick> {
+        if mode != ClickContainer::QUICK_CRAFT {
+            self.drag = None;
+        }
+
+        match mode {
+            ClickContainer::PICKUP if slot == ClickContainer::OUTSIDE => {
+                if !matches!(button, 0 | 1) {
+                    return Err(InvalidClick);
+                }
+                // left clicks drop the whole stack, right clicks one item
+                if let Some(carried) = self.carried.take() {
+                    self.carried = self.throw(carried, button == 0);
+                }
+            }
+            ClickContainer::PICKUP => match button {
+                0 | 1 => self.pickup(self.index(slot)?, button == 1),
+                _ => return Err(InvalidClick),
+            },
+            ClickContainer::QUICK_MOVE => self.quick_move(self.index(slot)?),
+            ClickContainer::SWAP => {
+                let index = self.index(slot)?;
+                let other = match button {
+                    0..=8 => self.hotbar(button as usize),
+                    40 => self.offhand(),
+                    _ => return Err(InvalidClick),
+                };
+                if self.placeable(index) || self.slots[other].is_none() {
+                    self.slots.swap(index, other);
+                }
+            }
+            ClickContainer::CLONE => {
+                let index = self.index(slot)?;
+                if creative && self.carried.is_none() {
+                    self.carried = self.slots[index]
+                        .as_ref()
+                        .map(|stack| with_count(stack, stack.max_stack_size()));
+                }
+            }
+            ClickContainer::THROW if slot == ClickContainer::OUTSIDE => {}
+            ClickContainer::THROW => {
+                let index = self.index(slot)?;
+                if !matches!(button, 0 | 1) {
+                    return Err(InvalidClick);
+                }
+                // the drop key throws one item, with control the whole stack
+                if self.carried.is_none() {
+                    if let Some(stack) = self.slots[index].take() {
+                        self.slots[index] = self.throw(stack, button == 1);
+                    }
+                }
+            }
+            ClickContainer::QUICK_CRAFT => self.quick_craft(slot, button, creative)?,
+            ClickContainer::PICKUP_ALL => {
+                self.index(slot)?;
+                self.pickup_all();
+            }
+            _ => return Err(InvalidClick),
+        }
+
+        Ok(())
+    }
+
+    /// Drops the whole stack or one item of it, returns what's left.
+    fn throw(&mut self, mut stack: ItemStack, all: bool) -> Slot {
+        if all || stack.count <= 1 {
+            self.dropped.push(stack);
+            return None;
+        }
+        self.dropped.push(with_count(&stack, 1));
+        stack.count -= 1;
+        Some(stack)
+    }
+
+    /// Left clicks swap the slot with the cursor or merge them, right clicks take half or put one.
+    fn pickup(&mut self, index: usize, right: bool) {
+        let placeable = self.placeable(index);
+
+        match (self.slots[index].take(), self.carried.take()) {
+            (Some(stack), None) => {
+                // half, rounded up
+                let count = if right {
+                    (stack.count + 1) / 2
+                } else {
+                    stack.count
+                };
+                self.carried = Some(with_count(&stack, count));
+                self.slots[index] =
+                    (stack.count > count).then(|| with_count(&stack, stack.count - count));
+            }
+            (None, Some(held)) if placeable => {
+                let count = if right { 1 } else { held.count }.min(held.max_stack_size());
+                self.slots[index] = Some(with_count(&held, count));
+                self.carried = (held.count > count).then(|| with_count(&held, held.count - count));
+            }
+            (Some(stack), Some(held)) if placeable && stack.stacks_with(&held) => {
+                let room = (stack.max_stack_size() - stack.count).max(0);
+                let count = if right { 1 } else { held.count }.min(room);
+                self.slots[index] = Some(with_count(&stack, stack.count + count));
+                self.carried = (held.count > count).then(|| with_count(&held, held.count - count));
+            }
+            (Some(stack), Some(held)) if placeable && held.count <= held.max_stack_size() => {
+                self.slots[index] = Some(held);
+                self.carried = Some(stack);
+            }
+            (stack, held) => {
+                self.slots[index] = stack;
+                self.carried = held;
+            }
+        }
+    }
+
+    /// Shift clicks move the stack between the container and the player's inventory,
+    /// or between the hotbar and the main inventory if there's no container.
+    fn quick_move(&mut self, index: usize) {
+        let Some(mut stack) = self.slots[index].take() else {
+            return;
+        };
+
+        let targets = match self.container {
+            // into the hotbar first, like vanilla
+            Some(len) if index < len => (len..self.offhand()).rev().collect::<Vec<_>>(),
+            Some(len) => (0..len).collect(),
+            None if MAIN.contains(&index) => HOTBAR.collect(),
+            None if HOTBAR.contains(&index) => MAIN.collect(),
+            None => MAIN.chain(HOTBAR).collect(),
+        };
+        merge(&mut self.slots, &targets, &mut stack);
+
+        if stack.count > 0 {
+            self.slots[index] = Some(stack);
+        }
+    }
+
+    /// Dragging with a stack: starting, adding a slot, and ending, in that order.
+    fn quick_craft(&mut self, slot: i16, button: i8, creative: bool) -> Result<(), InvalidClick> {
+        let kind = match button >> 2 {
+            0 => DragKind::Split,
+            1 => DragKind::One,
+            2 if creative => DragKind::Fill,
+            _ => return Err(InvalidClick),
+        };
+
+        match button & 3 {
+            0 => {
+                self.drag =
+                    (slot == ClickContainer::OUTSIDE && self.carried.is_some()).then(|| Drag {
+                        kind,
+                        slots: vec![],
+                    });
+            }
+            1 => {
+                let index = self.index(slot)?;
+                let placeable = self.placeable(index);
+                let (Some(drag), Some(carried)) = (&mut self.drag, &self.carried) else {
+                    return Ok(());
+                };
+                let fits = self.slots[index]
+                    .as_ref()
+                    .map_or(true, |stack| stack.stacks_with(carried));
+                let enough = kind == DragKind::Fill || drag.slots.len() < carried.count as usize;
+
+                if drag.kind == kind && fits && enough && placeable && !drag.slots.contains(&index)
+                {
+                    drag.slots.push(index);
+                }
+            }
+            2 => {
+                let Some(drag) = self.drag.take().filter(|drag| drag.kind == kind) else {
+                    return Ok(());
+                };
+                let Some(mut held) = self.carried.take() else {
+                    return Ok(());
+                };
+
+                // a drag over a single slot is a click
+                if let ([index], DragKind::Split | DragKind::One) = (&drag.slots[..], kind) {
+                    self.carried = Some(held);
+                    self.pickup(*index, kind == DragKind::One);
+                    return Ok(());
+                }
+
+                let max = held.max_stack_size();
+                let each = match kind {
+                    DragKind::Split => held.count / drag.slots.len().max(1) as i8,
+                    DragKind::One => 1,
+                    DragKind::Fill => max,
+                };
+
+                for index in drag.slots {
+                    let existing = self.slots[index].as_ref().map_or(0, |stack| stack.count);
+                    let mut count = each.min(max - existing).max(0);
+                    if kind != DragKind::Fill {
+                        count = count.min(held.count);
+                        held.count -= count;
+                    }
+                    if count > 0 {
+                        self.slots[index] = Some(with_count(&held, existing + count));
+                    }
+                }
+
+                self.carried = (held.count > 0).then_some(held);
+            }
+            _ => return Err(InvalidClick),
+        }
+
+        Ok(())
+    }
+
+    /// Double clicks collect as much of the carried item as fits on the cursor, from stacks that aren't full first.
+    fn pickup_all(&mut self) {
+        let Some(held) = &mut self.carried else {
+            return;
+        };
+        let max = held.max_stack_size();
+        let visible = self.visible();
+
+        for full in [false, true] {
+            for index in 0..visible {
+                if held.count >= max {
+                    return;
+                }
+                // the crafting result can't be collected from
+                if self.container.is_none() && index == CRAFTING_RESULT {
+                    continue;
+                }
+                let Some(stack) = &mut self.slots[index] else {
+                    continue;
+                };
+                if !stack.stacks_with(held) || (!full && stack.count >= stack.max_stack_size()) {
+                    continue;
+                }
+
+                let count = (max - held.count).min(stack.count);
+                held.count += count;
+                stack.count -= count;
+                if stack.count == 0 {
+                    self.slots[index] = None;
+                }
+            }
+        }
+    }
+}
+
+fn with_count(stack: &ItemStack, count: i8) -> ItemStack {
+    ItemStack {
+        count,
+        ..stack.clone()
+    }
+}
+
+/// Moves as much of the stack as fits into the slots at `targets`, topping up stacks of the same item before using empty slots.
+fn merge(slots: &mut [Slot], targets: &[usize], stack: &mut ItemStack) {
+    let max = stack.max_stack_size();
+
+    for target in targets {
+        if let Some(existing) = &mut slots[*target] {
+            if existing.stacks_with(stack) {
+                let count = (max - existing.count).max(0).min(stack.count);
+                existing.count += count;
+                stack.count -= count;
+            }
+        }
+    }
+
+    for target in targets {
+        if stack.count > 0 && slots[*target].is_none() {
+            let count = stack.count.min(max);
+            slots[*target] = Some(with_count(stack, count));
+            stack.count -= count;
+        }
+    }
+}
+
+/// State ids only go up to 15 bits in vanilla.
+fn next_state_id(state_id: i32) -> i32 {
+    (state_id + 1) & 0x7fff
+}
+
+fn is_creative(world: &World, player: Entity) -> bool {
+    world
+        .get::<PlayerGameMode>(player)
+        .is_some_and(|mode| mode.0 == GameMode::Creative)
+}
+
+/// Opens the container's window for the player, instead of any other container they had open.
+pub fn open_container(world: &mut World, player: Entity, container: Entity) {
+    let Some(Container { kind, title, slots }) = world.get::<Container>(container) else {
+        return;
+    };
+    let (kind, title, slots) = (*kind, title.clone(), slots.clone());
+    let Some(net) = world.get::<PlayerN>(player).map(|net| net.0.clone()) else {
+        return;
+    };
+    let Some(mut inventory) = world.get_mut::<Inventory>(player) else {
+        return;
+    };
+
+    let window_id = inventory.last_window_id % MAX_WINDOW_ID + 1;
+    inventory.last_window_id = window_id;
+    let window = Window::new(&mut inventory, Some(slots));
+    let content = SetContainerContent {
+        window_id,
+        state_id: VarInt(0),
+        slots: Array::new(&window.slots[..window.visible()]),
+        carried: window.carried.clone(),
+    };
+    window.write_back(&mut inventory);
+
+    world.entity_mut(player).insert(OpenContainer {
+        window_id,
+        container,
+        state_id: 0,
+    });
+    net.queue(OpenScreen {
+        window_id: VarInt(window_id.into()),
+        kind,
+        title: Json(title),
+    });
+    net.queue(content);
+}
+
+/// Closes the window on the server's side, like when the container is gone.
+pub fn force_close_container(world: &mut World, player: Entity) {
+    let Some(open) = world.get::<OpenContainer>(player).copied() else {
+        return;
+    };
+    close_container(world, player, open.window_id);
+
+    if let Some(net) = world.get::<PlayerN>(player) {
+        net.0.queue(CloseContainer {
+            window_id: open.window_id,
+        });
+    }
+}
+
+/// Opens the container of the block for the player, which is made the first time it's opened.
+/// Returns whether the block has a container.
+pub fn open_block(world: &mut World, player: Entity, position: IVec3) -> bool {
+    let Some(new) = world
+        .resource::<Chunks>()
+        .block(position)
+        .and_then(Container::of_block)
+    else {
+        return false;
+    };
+    let Some(containers) = world.get_resource::<BlockContainers>() else {
+        return false;
+    };
+    let container = match containers.0.get(&position) {
+        Some(container) => *container,
+        None => {
+            let container = world.spawn(new).id();
+            world
+                .resource_mut::<BlockContainers>()
+                .0
+                .insert(position, container);
+            container
+        }
+    };
+
+    open_container(world, player, container);
+    true
+}
+
+/// Removes the container of a block that's being broken, closes it for everyone who has it open and drops what was in it.
+/// Returns `false` if containers couldn't be loaded, so it isn't known what's in it and the block has to stay.
+pub fn remove_block_container(world: &mut World, position: IVec3) -> bool {
+    let has_container = world
+        .resource::<Chunks>()
+        .block(position)
+        .and_then(Container::of_block)
+        .is_some();
+    if !has_container {
+        return true;
+    }
+    let Some(containers) = world.get_resource::<BlockContainers>() else {
+        debug!(
+            ?position,
+            "containers weren't loaded, so they can't be broken"
+        );
+        return false;
+    };
+    // never opened
+    let Some(container) = containers.0.get(&position).copied() else {
+        return true;
+    };
+
+    let viewers = world
+        .query::<(Entity, &OpenContainer)>()
+        .iter(world)
+        .filter(|(_, open)| open.container == container)
+        .map(|(viewer, _)| viewer)
+        .collect::<Vec<_>>();
+    for viewer in viewers {
+        force_close_container(world, viewer);
+    }
+    world.resource_mut::<BlockContainers>().0.remove(&position);
+    let slots = world
+        .get_mut::<Container>(container)
+        .map(|mut container| std::mem::take(&mut container.slots))
+        .unwrap_or_default();
+    world.despawn(container);
+
+    let center = position.as_dvec3() + DVec3::new(0.5, 0.0, 0.5);
+    for stack in slots.into_iter().flatten() {
+        spawn_item(world, center, stack, BLOCK_PICKUP_DELAY);
+    }
+    true
+}
+
+/// Spawns the containers of blocks that were saved with the world, and inserts [`BlockContainers`].
+pub fn load_containers(mut commands: Commands, config: Res<Config>) {
+    let saved = match storage::load_containers(&config.world.directory) {
+        Ok(saved) => saved,
+        Err(error) => {
+            // and they aren't overwritten either, until the file is fixed
+            error!(%error, "failed to load containers, so they can't be used");
+            return;
+        }
+    };
+
+    let mut containers = BlockContainers::default();
+    for (position, slots) in saved {
+        let container = commands.spawn(Container {
+            slots,
+            ..Container::chest()
+        });
+        containers.0.insert(position, container.id());
+    }
+    commands.insert_resource(containers);
+}
+
+/// Saves the items in the containers of blocks.
+pub fn save_containers(
+    config: Res<Config>,
+    block_containers: Option<Res<BlockContainers>>,
+    containers: Query<&Container>,
+) {
+    let Some(block_containers) = block_containers else {
+        return;
+    };
+    let saved = block_containers
+        .0
+        .iter()
+        .filter_map(|(position, container)| {
+            Some((*position, &containers.get(*container).ok()?.slots[..]))
+        })
+        .collect::<Vec<_>>();
+    match storage::save_containers(&config.world.directory, &saved) {
+        Ok(()) => info!(count = saved.len(), "saved containers"),
+        Err(error) => error!(%error, "failed to save containers"),
+    }
+}
+
+/// The player closed a window. Whatever was on the cursor, or in the crafting grid, goes back into the inventory,
+/// and what doesn't fit is dropped.
+pub fn close_container(world: &mut World, player: Entity, window_id: u8) {
+    let Some(mut entity) = world.get_entity_mut(player) else {
+        return;
+    };
+    if window_id != INVENTORY_WINDOW {
+        if entity.get::<OpenContainer>().map(|open| open.window_id) != Some(window_id) {
+            return;
+        }
+        entity.remove::<OpenContainer>();
+    }
+    let Some(net) = entity.get::<PlayerN>().map(|net| net.0.clone()) else {
+        return;
+    };
+    let Some(mut inventory) = entity.get_mut::<Inventory>() else {
+        return;
+    };
+
+    inventory.drag = None;
+    let has_items = |inventory: &Inventory| {
+        inventory.carried.is_some()
+            || (window_id == INVENTORY_WINDOW
+                && inventory.slots[CRAFTING_GRID].iter().any(Option::is_some))
+    };
+    if !has_items(&*inventory) {
+        return;
+    }
+
+    let mut dropped = vec![];
+    if let Some(carried) = inventory.carried.take() {
+        dropped.extend(inventory.insert(carried));
+    }
+    if window_id == INVENTORY_WINDOW {
+        for slot in CRAFTING_GRID {
+            if let Some(item) = inventory.slots[slot].take() {
+                dropped.extend(inventory.insert(item));
+            }
+        }
+    }
+
+    inventory.state_id = next_state_id(inventory.state_id);
+    net.queue(inventory.content_packet());
+    for stack in dropped {
+        drop_item(world, player, stack);
+    }
+}
+
+/// Applies a click, then corrects what the client predicted wrong and tells everyone else looking at the container.
+pub fn click_container(world: &mut World, player: Entity, click: ClickContainer) {
+    let Some(net) = world.get::<PlayerN>(player).map(|net| net.0.clone()) else {
+        return;
+    };
+    let creative = is_creative(world, player);
+
+    let open = match (click.window_id, world.get::<OpenContainer>(player).copied()) {
+        (INVENTORY_WINDOW, _) => None,
+        (window_id, Some(open)) if window_id == open.window_id => Some(open),
+        (window_id, _) => {
+            debug!(window_id, addr = %net.peer_addr, "clicked in a window that isn't open");
+            return;
+        }
+    };
+    let container_slots = match open {
+        Some(open) => match world.get::<Container>(open.container) {
+            Some(container) => Some(container.slots.clone()),
+            None => {
+                force_close_container(world, player);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let Some(mut inventory) = world.get_mut::<Inventory>(player) else {
+        return;
+    };
+    let mut window = Window::new(&mut inventory, container_slots);
+    let before = window.slots.clone();
+
+    let valid = window
+        .click(click.slot, click.button, click.mode, creative)
+        .is_ok();
+    if !valid {
+        debug!(?click, addr = %net.peer_addr, "invalid click");
+    }
+
+    // what the client thinks is in the slots now, unless it clicked on something outdated
+    let mut state_id = open.map_or(inventory.state_id, |open| open.state_id);
+    let outdated = !valid || click.state_id.0 != state_id;
+    let predicted = click
+        .changed_slots
+        .iter()
+        .filter_map(|changed| Some((usize::try_from(changed.slot).ok()?, &changed.item)))
+        .collect::<HashMap<_, _>>();
+    let wrong = (0..window.visible())
+        .filter(|index| {
+            predicted.get(index).copied().unwrap_or(&before[*index]) != &window.slots[*index]
+        })
+        .collect::<Vec<_>>();
+    let carried_wrong = click.carried != window.carried;
+
+    if outdated || !wrong.is_empty() || carried_wrong {
+        state_id = next_state_id(state_id);
+    }
+    if outdated {
+        net.queue(SetContainerContent {
+            window_id: click.window_id,
+            state_id: VarInt(state_id),
+            slots: Array::new(&window.slots[..window.visible()]),
+            carried: window.carried.clone(),
+        });
+    } else {
+        for index in wrong {
+            net.queue(SetContainerSlot {
+                window_id: click.window_id as i8,
+                state_id: VarInt(state_id),
+                slot: index as i16,
+                item: window.slots[index].clone(),
+            });
+        }
+        if carried_wrong {
+            net.queue(SetContainerSlot {
+                window_id: -1,
+                state_id: VarInt(state_id),
+                slot: -1,
+                item: window.carried.clone(),
+            });
+        }
+    }
+
+    let offhand = window.offhand();
+    let offhand_changed = open.is_some() && before[offhand] != window.slots[offhand];
+    let changed = match window.container {
+        Some(len) => (0..len)
+            .filter(|index| before[*index] != window.slots[*index])
+            .map(|index| (index, window.slots[index].clone()))
+            .collect(),
+        None => vec![],
+    };
+
+    let dropped = std::mem::take(&mut window.dropped);
+    let container_slots = window.write_back(&mut inventory);
+    if open.is_none() {
+        inventory.state_id = state_id;
+    }
+    if offhand_changed {
+        // the offhand isn't in container windows, so the inventory window is updated
+        net.queue(inventory.slot_packet(OFFHAND));
+    }
+    for stack in dropped {
+        drop_item(world, player, stack);
+    }
+    let Some(open) = open else {
+        return;
+    };
+
+    if let Some(mut open) = world.get_mut::<OpenContainer>(player) {
+        open.state_id = state_id;
+    }
+    if changed.is_empty() {
+        return;
+    }
+    if let (Some(mut container), Some(slots)) =
+        (world.get_mut::<Container>(open.container), container_slots)
+    {
+        container.slots = slots;
+    }
+
+    let mut viewers = world.query::<(Entity, &PlayerN, &mut OpenContainer)>();
+    for (viewer, net, mut other) in viewers.iter_mut(world) {
+        if viewer == player || other.container != open.container {
+            continue;
+        }
+
+        other.state_id = next_state_id(other.state_id);
+        for (index, item) in &changed {
+            net.0.queue(SetContainerSlot {
+                window_id: other.window_id as i8,
+                state_id: VarInt(other.state_id),
+                slot: *index as i16,
+                item: item.clone(),
+            });
+        }
+    }
+}
+
+/// Creative players can put any item anywhere in their inventory, which isn't checked beyond the stack size.
+pub fn set_creative_slot(world: &mut World, player: Entity, packet: SetCreativeModeSlot) {
+    if !is_creative(world, player) {
+        debug!(?player, "set a slot without being in creative");
+        return;
+    }
+
+    let item = packet
+        .item
+        .filter(|item| item.count > 0)
+        .map(|item| with_count(&item, item.count.min(item.max_stack_size())));
+    // dropped out of the creative inventory
+    if packet.slot == -1 {
+        if let Some(item) = item {
+            drop_item(world, player, item);
+        }
+        return;
+    }
+
+    let Some(index) = usize::try_from(packet.slot)
+        .ok()
+        .filter(|index| (1..INVENTORY_SIZE).contains(index))
+    else {
+        debug!(slot = packet.slot, "set a slot that doesn't exist");
+        return;
+    };
+    if let Some(mut inventory) = world.get_mut::<Inventory>(player) {
+        inventory.slots[index] = item;
+    }
+}
+
+/// Drops one of the items in the player's main hand, or all of them.
+/// The client already took them out of its hotbar, so nothing is sent back.
+pub fn drop_held(world: &mut World, player: Entity, all: bool) {
+    let Some(mut inventory) = world.get_mut::<Inventory>(player) else {
+        return;
+    };
+    let index = inventory.held_slot(false);
+    let Some(held) = inventory.slots[index].take() else {
+        return;
+    };
+    let dropped = if all || held.count <= 1 {
+        held
+    } else {
+        inventory.slots[index] = Some(with_count(&held, held.count - 1));
+        with_count(&held, 1)
+    };
+    drop_item(world, player, dropped);
+}
+
+pub fn set_held_item(world: &mut World, player: Entity, slot: i16) {
+    let Ok(slot) = u8::try_from(slot) else {
+        return;
+    };
+    if let Some(mut inventory) = world.get_mut::<Inventory>(player).filter(|_| slot < 9) {
+        inventory.selected = slot;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stone(count: i8) -> Slot {
+        ItemStack::new("stone", count)
+    }
+
+    fn chest() -> Window {
+        let mut inventory = Inventory::default();
+        inventory.slots[HOTBAR.start] = stone(10);
+        Window::new(&mut inventory, Some(vec![None; 27]))
+    }
+
+    #[test]
+    fn pickup() {
+        let mut window = chest();
+        let hotbar = window.hotbar(0) as i16;
+
+        window
+            .click(hotbar, 1, ClickContainer::PICKUP, false)
+            .unwrap();
+        assert_eq!(
+            (&window.slots[hotbar as usize], &window.carried),
+            (&stone(5), &stone(5))
+        );
+
+        window.click(0, 0, ClickContainer::PICKUP, false).unwrap();
+        assert_eq!((&window.slots[0], &window.carried), (&stone(5), &None));
+
+        assert!(window.click(100, 0, ClickContainer::PICKUP, false).is_err());
+    }
+
+    #[test]
+    fn quick_move() {
+        let mut window = chest();
+        window.slots[1] = stone(60);
+
+        window
+            .click(
+                window.hotbar(0) as i16,
+                0,
+                ClickContainer::QUICK_MOVE,
+                false,
+            )
+            .unwrap();
+        assert_eq!(window.slots[1], stone(64));
+        assert_eq!(window.slots[0], stone(6));
+        assert_eq!(window.slots[window.hotbar(0)], None);
+    }
+
+    #[test]
+    fn dropping() {
+        let mut window = chest();
+        let hotbar = window.hotbar(0) as i16;
+
+        window
+            .click(hotbar, 0, ClickContainer::THROW, false)
+            .unwrap();
+        assert_eq!(window.slots[hotbar as usize], stone(9));
+        // nothing to throw
+        window.click(0, 1, ClickContainer::THROW, false).unwrap();
+
+        window
+            .click(hotbar, 0, ClickContainer::PICKUP, false)
+            .unwrap();
+        window
+            .click(ClickContainer::OUTSIDE, 1, ClickContainer::PICKUP, false)
+            .unwrap();
+        assert_eq!(window.carried, stone(8));
+        window
+            .click(ClickContainer::OUTSIDE, 0, ClickContainer::PICKUP, false)
+            .unwrap();
+        assert_eq!(window.carried, None);
+        assert_eq!(
+            window.dropped,
+            [stone(1), stone(1), stone(8)].map(Option::unwrap)
+        );
+
+        window.slots[0] = stone(5);
+        window.click(0, 1, ClickContainer::THROW, false).unwrap();
+        assert_eq!(window.slots[0], None);
+        assert_eq!(window.dropped.last(), stone(5).as_ref());
+    }
+
+    #[test]
+    fn drag() {
+        let mut window = chest();
+        window.carried = stone(10);
+
+        for (slot, button) in [(ClickContainer::OUTSIDE, 0), (0, 1), (1, 1), (2, 1), (0, 2)] {
+            window
+                .click(slot, button, ClickContainer::QUICK_CRAFT, false)
+                .unwrap();
+        }
+        assert_eq!(window.slots[..3], [stone(3), stone(3), stone(3)]);
+        assert_eq!(window.carried, stone(1));
+
+        window
+            .click(5, 0, ClickContainer::PICKUP_ALL, false)
+            .unwrap();
+        assert_eq!(window.carried, stone(20));
+    }
+}
@@ -0,0 +1,493 @@
+//! Sky light and block light, spread block by block from the sky and from blocks that give off light.
+//!
+//! New chunks are lit on their own where they're generated, then light spreads across the borders with the chunks
+//! next to them once they're loaded. When a block changes, the light that went through it is taken away and spread again.
+
+use std::collections::{HashMap, VecDeque};
+
+use bevy::prelude::IVec3;
+use oxcr_protocol::model::{
+    block::BlockState,
+    chunk::{LightData, LIGHT_BYTES},
+};
+
+use crate::world::{chunk_of, section_index, Chunk, HEIGHT, MIN_Y};
+
+/// Light is kept for one more section below and above the world, which the client needs to light the edges.
+pub const LIGHT_SECTIONS: usize = (HEIGHT / 16) as usize + 2;
+/// Every light section, for [`ChunkLight::data`].
+pub const ALL_SECTIONS: u32 = (1 << LIGHT_SECTIONS) - 1;
+/// The bottom of the light sections.
+const BOTTOM: i32 = MIN_Y - 16;
+/// Just above the top of the light sections.
+const TOP: i32 = MIN_Y + HEIGHT + 16;
+
+const DIRECTIONS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightKind {
+    Sky,
+    Block,
+}
+
+const KINDS: [LightKind; 2] = [LightKind::Sky, LightKind::Block];
+
+/// The light of a section, 4 bits per block.
+#[derive(Debug, Clone, PartialEq)]
+enum Nibbles {
+    Uniform(u8),
+    Mixed(Box<[u8; LIGHT_BYTES]>),
+}
+
+impl Nibbles {
+    fn get(&self, index: usize) -> u8 {
+        match self {
+            Self::Uniform(level) => *level,
+            Self::Mixed(bytes) => (bytes[index >> 1] >> ((index & 1) * 4)) & 15,
+        }
+    }
+
+    fn set(&mut self, index: usize, level: u8) {
+        if let Self::Uniform(uniform) = *self {
+            if uniform == level {
+                return;
+            }
+            *self = Self::Mixed(Box::new([uniform | (uniform << 4); LIGHT_BYTES]));
+        }
+        if let Self::Mixed(bytes) = self {
+            let shift = (index & 1) * 4;
+            let byte = &mut bytes[index >> 1];
+            *byte = (*byte & !(15 << shift)) | (level << shift);
+        }
+    }
+
+    /// Goes back to being uniform if every block has the same light.
+    fn compact(&mut self) {
+        if let Self::Mixed(bytes) = self {
+            let first = bytes[0];
+            if first & 15 == first >> 4 && bytes.iter().all(|byte| *byte == first) {
+                *self = Self::Uniform(first & 15);
+            }
+        }
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Uniform(level) => vec![level | (level << 4); LIGHT_BYTES],
+            Self::Mixed(bytes) => bytes.to_vec(),
+        }
+    }
+}
+
+/// The light of a chunk, by section from the one below the world up.
+#[derive(Debug, Clone)]
+pub struct ChunkLight {
+    sky: Vec<Nibbles>,
+    block: Vec<Nibbles>,
+}
+
+/// The light of a chunk of nothing but air, lit by the sky everywhere.
+impl Default for ChunkLight {
+    fn default() -> Self {
+        Self {
+            sky: vec![Nibbles::Uniform(15); LIGHT_SECTIONS],
+            block: vec![Nibbles::Uniform(0); LIGHT_SECTIONS],
+        }
+    }
+}
+
+impl ChunkLight {
+    fn section(y: i32) -> Option<usize> {
+        (BOTTOM..TOP)
+            .contains(&y)
+            .then(|| ((y - BOTTOM) >> 4) as usize)
+    }
+
+    fn sections(&self, kind: LightKind) -> &[Nibbles] {
+        match kind {
+            LightKind::Sky => &self.sky,
+            LightKind::Block => &self.block,
+        }
+    }
+
+    /// The light at `x` and `z` within the chunk, and at height `y`.
+    /// Above the light sections is always lit by the sky, and below them is dark.
+    pub fn get(&self, kind: LightKind, x: i32, y: i32, z: i32) -> u8 {
+        match Self::section(y) {
+            Some(section) => self.sections(kind)[section].get(section_index(x, y, z)),
+            None if y >= TOP && kind == LightKind::Sky => 15,
+            None => 0,
+        }
+    }
+
+    fn set(&mut self, kind: LightKind, x: i32, y: i32, z: i32, level: u8) {
+        let Some(section) = Self::section(y) else {
+            return;
+        };
+        let sections = match kind {
+            LightKind::Sky => &mut self.sky,
+            LightKind::Block => &mut self.block,
+        };
+        sections[section].set(section_index(x, y, z), level);
+    }
+
+    fn compact(&mut self) {
+        for nibbles in self.sky.iter_mut().chain(&mut self.block) {
+            nibbles.compact();
+        }
+    }
+
+    /// The light of the sections whose bits are set in `sections`, starting with the one below the world.
+    pub fn data(&self, sections: u32) -> LightData {
+        let mut light = LightData::default();
+        for section in (0..LIGHT_SECTIONS).filter(|section| sections & (1 << section) != 0) {
+            for (nibbles, mask, empty_mask, arrays) in [
+                (
+                    &self.sky,
+                    &mut light.sky_light_mask,
+                    &mut light.empty_sky_light_mask,
+                    &mut light.sky_light,
+                ),
+                (
+                    &self.block,
+                    &mut light.block_light_mask,
+                    &mut light.empty_block_light_mask,
+                    &mut light.block_light,
+                ),
+            ] {
+                match &nibbles[section] {
+                    Nibbles::Uniform(0) => empty_mask.set(section),
+                    nibbles => {
+                        mask.set(section);
+                        arrays.push(nibbles.bytes());
+                    }
+                }
+            }
+        }
+        light
+    }
+}
+
+/// Blocks and light that light spreads through.
+trait Area {
+    /// `None` where light can't spread to, outside of the area or above or below the light sections.
+    fn block_at(&self, position: IVec3) -> Option<BlockState>;
+    fn light_at(&self, kind: LightKind, position: IVec3) -> u8;
+    fn set_light_at(&mut self, kind: LightKind, position: IVec3, level: u8);
+}
+
+/// A single chunk, by the position of blocks within it.
+impl Area for Chunk {
+    fn block_at(&self, position: IVec3) -> Option<BlockState> {
+        let IVec3 { x, y, z } = position;
+        ((0..16).contains(&x) && (0..16).contains(&z) && (BOTTOM..TOP).contains(&y))
+            .then(|| self.block(x, y, z))
+    }
+
+    fn light_at(&self, kind: LightKind, position: IVec3) -> u8 {
+        self.light().get(kind, position.x, position.y, position.z)
+    }
+
+    fn set_light_at(&mut self, kind: LightKind, position: IVec3, level: u8) {
+        self.light_mut()
+            .set(kind, position.x, position.y, position.z, level);
+    }
+}
+
+/// Every loaded chunk, by the position of blocks in the world, keeping track of the sections whose light changed.
+pub struct Loaded<'a> {
+    pub chunks: &'a mut HashMap<(i32, i32), Chunk>,
+    /// The sections of each chunk, as bits like for [`ChunkLight::data`].
+    pub changed: &'a mut HashMap<(i32, i32), u32>,
+}
+
+impl Area for Loaded<'_> {
+    fn block_at(&self, position: IVec3) -> Option<BlockState> {
+        if !(BOTTOM..TOP).contains(&position.y) {
+            return None;
+        }
+        let chunk = self.chunks.get(&chunk_of(position))?;
+        Some(chunk.block(position.x & 15, position.y, position.z & 15))
+    }
+
+    fn light_at(&self, kind: LightKind, position: IVec3) -> u8 {
+        self.chunks.get(&chunk_of(position)).map_or(0, |chunk| {
+            chunk
+                .light()
+                .get(kind, position.x & 15, position.y, position.z & 15)
+        })
+    }
+
+    fn set_light_at(&mut self, kind: LightKind, position: IVec3, level: u8) {
+        let chunk_position = chunk_of(position);
+        let (Some(chunk), Some(section)) = (
+            self.chunks.get_mut(&chunk_position),
+            ChunkLight::section(position.y),
+        ) else {
+            return;
+        };
+
+        chunk
+            .light_mut()
+            .set(kind, position.x & 15, position.y, position.z & 15, level);
+        *self.changed.entry(chunk_position).or_default() |= 1 << section;
+    }
+}
+
+/// How much light gets from a block with `level` to the next one in `direction`.
+fn spread_to(kind: LightKind, level: u8, direction: IVec3, block: BlockState) -> u8 {
+    let opacity = block.light_opacity();
+    // sunlight goes straight down through the air without getting dimmer
+    if kind == LightKind::Sky && direction == IVec3::NEG_Y && level == 15 && opacity == 0 {
+        15
+    } else {
+        level.saturating_sub(opacity.max(1))
+    }
+}
+
+/// Spreads light from the queued blocks to the blocks around them, for as long as it makes them brighter.
+fn spread(area: &mut impl Area, kind: LightKind, queue: &mut VecDeque<IVec3>) {
+    while let Some(position) = queue.pop_front() {
+        let level = area.light_at(kind, position);
+        if level <= 1 {
+            continue;
+        }
+
+        for direction in DIRECTIONS {
+            let neighbor = position + direction;
+            let Some(block) = area.block_at(neighbor) else {
+                continue;
+            };
+            let new = spread_to(kind, level, direction, block);
+            if new > area.light_at(kind, neighbor) {
+                area.set_light_at(kind, neighbor, new);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+}
+
+/// Takes away the light that spread from the queued blocks, which are queued with the light they had,
+/// and queues the blocks around it that are lit some other way to spread their light back.
+fn unspread(
+    area: &mut impl Area,
+    kind: LightKind,
+    mut queue: VecDeque<(IVec3, u8)>,
+    relight: &mut VecDeque<IVec3>,
+) {
+    while let Some((position, level)) = queue.pop_front() {
+        for direction in DIRECTIONS {
+            let neighbor = position + direction;
+            let Some(block) = area.block_at(neighbor) else {
+                continue;
+            };
+            let light = area.light_at(kind, neighbor);
+            if light == 0 {
+                continue;
+            }
+
+            let lit_from_here = light < level
+                || (kind == LightKind::Sky
+                    && direction == IVec3::NEG_Y
+                    && level == 15
+                    && light == 15);
+            if !lit_from_here {
+                relight.push_back(neighbor);
+                continue;
+            }
+
+            area.set_light_at(kind, neighbor, 0);
+            queue.push_back((neighbor, light));
+            let emission = block.light_emission();
+            if kind == LightKind::Block && emission > 0 {
+                area.set_light_at(kind, neighbor, emission);
+                relight.push_back(neighbor);
+            }
+        }
+    }
+}
+
+/// Lights a new chunk on its own, as if there was nothing around it.
+pub fn light_chunk(chunk: &mut Chunk) {
+    // sky light straight down from above the world, until something dims it
+    for x in 0..16 {
+        for z in 0..16 {
+            let mut level = 15;
+            for y in (BOTTOM..MIN_Y + HEIGHT).rev() {
+                level = spread_to(LightKind::Sky, level, IVec3::NEG_Y, chunk.block(x, y, z));
+                chunk.light_mut().set(LightKind::Sky, x, y, z, level);
+            }
+        }
+    }
+
+    // then sideways, from wherever it's brighter than next to it
+    let mut queue = VecDeque::new();
+    for y in BOTTOM..MIN_Y + HEIGHT {
+        for x in 0..16 {
+            for z in 0..16 {
+                let position = IVec3::new(x, y, z);
+                let level = chunk.light_at(LightKind::Sky, position);
+                let brighter = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z]
+                    .into_iter()
+                    .map(|direction| position + direction)
+                    .filter(|neighbor| chunk.block_at(*neighbor).is_some())
+                    .any(|neighbor| chunk.light_at(LightKind::Sky, neighbor) + 1 < level);
+                if brighter {
+                    queue.push_back(position);
+                }
+            }
+        }
+    }
+    spread(chunk, LightKind::Sky, &mut queue);
+
+    for y in MIN_Y..MIN_Y + HEIGHT {
+        for x in 0..16 {
+            for z in 0..16 {
+                let emission = chunk.block(x, y, z).light_emission();
+                if emission > 0 {
+                    chunk.light_mut().set(LightKind::Block, x, y, z, emission);
+                    queue.push_back(IVec3::new(x, y, z));
+                }
+            }
+        }
+    }
+    spread(chunk, LightKind::Block, &mut queue);
+
+    chunk.light_mut().compact();
+}
+
+/// Spreads light across the borders of a chunk that was just loaded and the loaded chunks next to it.
+pub fn join(area: &mut Loaded, (chunk_x, chunk_z): (i32, i32)) {
+    let base = IVec3::new(chunk_x * 16, 0, chunk_z * 16);
+    let mut queues = [VecDeque::new(), VecDeque::new()];
+
+    for direction in [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z] {
+        if !area
+            .chunks
+            .contains_key(&(chunk_x + direction.x, chunk_z + direction.z))
+        {
+            continue;
+        }
+
+        for i in 0..16 {
+            // the edge of the chunk on the side of the neighbor
+            let edge = match (direction.x, direction.z) {
+                (1, _) => IVec3::new(15, 0, i),
+                (-1, _) => IVec3::new(0, 0, i),
+                (_, 1) => IVec3::new(i, 0, 15),
+                _ => IVec3::new(i, 0, 0),
+            };
+            for y in BOTTOM..TOP {
+                let inside = base + edge + IVec3::new(0, y, 0);
+                let outside = inside + direction;
+                for (kind, queue) in KINDS.into_iter().zip(&mut queues) {
+                    let (a, b) = (area.light_at(kind, inside), area.light_at(kind, outside));
+                    if a > b + 1 {
+                        queue.push_back(inside);
+                    } else if b > a + 1 {
+                        queue.push_back(outside);
+                    }
+                }
+            }
+        }
+    }
+
+    for (kind, mut queue) in KINDS.into_iter().zip(queues) {
+        spread(area, kind, &mut queue);
+    }
+}
+
+/// Updates the light around a block that changed from `old` to `new`.
+pub fn block_changed(area: &mut Loaded, position: IVec3, old: BlockState, new: BlockState) {
+    if (old.light_emission(), old.light_opacity()) == (new.light_emission(), new.light_opacity()) {
+        return;
+    }
+
+    for kind in KINDS {
+        let level = area.light_at(kind, position);
+        area.set_light_at(kind, position, 0);
+
+        let mut relight = VecDeque::new();
+        unspread(
+            area,
+            kind,
+            VecDeque::from([(position, level)]),
+            &mut relight,
+        );
+        let emission = new.light_emission();
+        if kind == LightKind::Block && emission > 0 {
+            area.set_light_at(kind, position, emission);
+            relight.push_back(position);
+        }
+        spread(area, kind, &mut relight);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oxcr_protocol::model::registry::Registry;
+
+    use super::*;
+    use crate::{generator::FlatGenerator, world::Chunks};
+
+    #[test]
+    fn spreading() {
+        let generator = FlatGenerator::new(&Default::default(), &Registry::default());
+        let mut chunks = Chunks::new(std::sync::Arc::new(generator), None);
+        chunks.get_or_generate((0, 0));
+        chunks.get_or_generate((1, 0));
+        let light = |chunks: &Chunks, kind, position: IVec3| {
+            chunks.get(chunk_of(position)).unwrap().light().get(
+                kind,
+                position.x & 15,
+                position.y,
+                position.z & 15,
+            )
+        };
+
+        // the ground is on top of the grass, 3 blocks up from the bottom
+        let ground = MIN_Y + 4;
+        assert_eq!(light(&chunks, LightKind::Sky, IVec3::new(3, ground, 3)), 15);
+        assert_eq!(
+            light(&chunks, LightKind::Sky, IVec3::new(3, ground - 1, 3)),
+            0
+        );
+
+        let roof = IVec3::new(5, ground + 3, 5);
+        chunks.set_block(roof, BlockState::STONE);
+        assert_eq!(light(&chunks, LightKind::Sky, roof), 0);
+        assert_eq!(light(&chunks, LightKind::Sky, roof - IVec3::Y), 14);
+        assert_eq!(light(&chunks, LightKind::Sky, IVec3::new(5, ground, 5)), 14);
+
+        // across the border between the chunks
+        let torch = BlockState::from_name("torch").unwrap();
+        chunks.set_block(IVec3::new(18, ground, 3), torch);
+        assert_eq!(
+            light(&chunks, LightKind::Block, IVec3::new(18, ground, 3)),
+            14
+        );
+        assert_eq!(
+            light(&chunks, LightKind::Block, IVec3::new(15, ground, 3)),
+            11
+        );
+        assert_eq!(
+            light(&chunks, LightKind::Block, IVec3::new(10, ground, 1)),
+            4
+        );
+
+        chunks.set_block(roof, BlockState::AIR);
+        chunks.set_block(IVec3::new(18, ground, 3), BlockState::AIR);
+        assert_eq!(light(&chunks, LightKind::Sky, roof - IVec3::Y), 15);
+        assert_eq!(
+            light(&chunks, LightKind::Block, IVec3::new(15, ground, 3)),
+            0
+        );
+    }
+}
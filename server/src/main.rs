@@ -1,7 +1,26 @@
 #![feature(try_blocks, associated_type_defaults, decl_macro, iterator_try_collect)]
 
 mod cli;
+mod command;
+mod config;
+mod console;
+mod dropped_items;
+mod interaction;
+mod inventory;
+mod light;
+mod metadata;
 mod model;
+mod play;
+mod query;
+mod rcon;
+mod reload;
+mod shutdown;
+mod status;
+mod storage;
+mod tab_list;
+mod tracker;
+mod view;
+mod world;
 
 use bevy::prelude::*;
 use model::DifficultySetting;
@@ -11,33 +30,32 @@ use oxcr_protocol::{
     logging::CraftLayer,
     miette::{self, IntoDiagnostic, Report},
     model::{
-        chat::{self, *},
+        chat::*,
         packets::{
             handshake::{Handshake, HandshakeNextState},
             login::{DisconnectLogin, LoginStart, LoginSuccess, SetCompression},
             play::{
-                Abilities, ChangeDifficulty, DisconnectPlay, FeatureFlags, GameMode, LoginPlay,
+                Abilities, ChangeDifficulty, DisconnectPlay, FeatureFlags, LoginPlay,
                 PlayerAbilities, PreviousGameMode, SetDefaultSpawnPosition,
             },
-            status::{
-                self, PingRequest, Players, PongResponse, Sample, StatusRequest, StatusResponse,
-                StatusResponseJson,
-            },
+            Packet,
         },
         registry::Registry,
-        DamageType, Difficulty, DimensionType, State, VarInt, WorldgenBiome, PROTOCOL_VERSION,
+        DamageType, DimensionType, State, VarInt, WorldgenBiome, PROTOCOL_VERSION,
     },
     nbt::{nbt_serde, Nbt, NbtList, NbtTagType},
+    net::NetConfig,
     nsfr::when_the_miette,
-    ser::{Array, Identifier, Json, Namespace, Position},
+    ser::{Array, Identifier, Json, Namespace, Position, Serialize},
     uuid::Uuid,
     AsyncSet, PlayerN, PlayerNet, ProtocolPlugin,
 };
 use std::{
+    fmt::Debug,
     net::SocketAddr,
     sync::{atomic::Ordering, Arc},
 };
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
 use tokio_util::sync::CancellationToken;
 use tracing::instrument;
 use tracing_subscriber::{
@@ -45,11 +63,34 @@ use tracing_subscriber::{
 };
 
 use crate::{
+    command::CommandPlugin,
+    config::Config,
+    console::console,
+    dropped_items::pick_up_items,
     error::Error,
+    generator::WorldSeed,
+    interaction::broadcast_destroy_stages,
+    inventory::{load_containers, save_containers},
+    metadata::sync_metadata,
     model::{Player, PlayerBundle, PlayerGameMode, PlayerName, PlayerUuid},
+    query::listen_query,
+    rcon::listen_rcon,
+    reload::{broadcast_difficulty, broadcast_distances, watch_config, ConfigSource},
+    shutdown::{handle_signals, Save, Shutdown},
+    status::{ServerStatus, LEGACY_PING},
+    tab_list::{
+        add_to_tab_list, broadcast_tab_list_header, remove_from_tab_list, update_tab_list, TabList,
+    },
+    tracker::{broadcast_movement, track_entities},
+    view::update_views,
+    world::{
+        broadcast_block_changes, broadcast_light_changes, finish_generating, init_world,
+        save_chunks, send_chunks, unload_chunks, Chunks,
+    },
 };
 
 mod error;
+mod generator;
 
 type Result<T, E = error::Error> = ::std::result::Result<T, E>;
 
@@ -63,7 +104,7 @@ async fn lifecycle(net: Arc<PlayerNet>, cx: Arc<TaskContext>, ent_id: Entity) ->
 
     match handshake.next_state {
         HandshakeNextState::Login => login(net, cx, ent_id).await,
-        HandshakeNextState::Status => status(net).await,
+        HandshakeNextState::Status => status::status(net, cx).await,
     }
 }
 
@@ -74,11 +115,33 @@ async fn login(net: Arc<PlayerNet>, cx: Arc<TaskContext>, ent_id: Entity) -> Res
 
     debug!(login.name=?name, login.uuid=?uuid, %net.peer_addr, "Login Start");
 
-    let uuid = uuid.unwrap_or_else(|| {
-        debug!(?name, "Player is in offline mode");
-        let real = format!("OfflinePlayer:{name}");
-        Uuid::new_v3(&Uuid::NAMESPACE_DNS, real.as_bytes())
-    });
+    let config = cx
+        .run_on_main_thread(|w| w.world.resource::<Config>().clone())
+        .await;
+
+    let uuid = match uuid {
+        Some(uuid) if config.network.online_mode => uuid,
+        _ => {
+            debug!(?name, "Player is in offline mode");
+            let real = format!("OfflinePlayer:{name}");
+            Uuid::new_v3(&Uuid::NAMESPACE_DNS, real.as_bytes())
+        }
+    };
+
+    if !config.whitelist.allows(&name) {
+        info!(?name, addr=%net.peer_addr, "Player is not whitelisted");
+
+        net.send_packet(DisconnectLogin {
+            reason: Json(ChatComponent::translatable(
+                "multiplayer.disconnect.not_whitelisted",
+                vec![],
+            )),
+        })
+        .await?;
+        net.cancellator.cancel();
+
+        return Ok(());
+    }
 
     info!(?name, ?uuid, addr=%net.peer_addr, "Player joined");
 
@@ -101,7 +164,7 @@ async fn login(net: Arc<PlayerNet>, cx: Arc<TaskContext>, ent_id: Entity) -> Res
 
     net.state.set(State::Play).await;
 
-    let game_mode = GameMode::Survival;
+    let game_mode = config.world.game_mode;
 
     let player = PlayerBundle {
         name: PlayerName(name.clone()),
@@ -152,6 +215,15 @@ async fn login(net: Arc<PlayerNet>, cx: Arc<TaskContext>, ent_id: Entity) -> Res
 
     debug!("{registry_codec:#?}");
 
+    let (is_flat, hashed_seed) = cx
+        .run_on_main_thread(move |w| {
+            (
+                w.world.resource::<Chunks>().is_flat(),
+                w.world.resource::<WorldSeed>().hashed(),
+            )
+        })
+        .await;
+
     let login_play = LoginPlay {
         entity_id: ent_id.index() as i32,
         game_mode,
@@ -162,14 +234,14 @@ async fn login(net: Arc<PlayerNet>, cx: Arc<TaskContext>, ent_id: Entity) -> Res
         dimension_names: Array::new(&[Identifier::new(Namespace::Minecraft, "overworld")]),
         dimension_name: Identifier::new(Namespace::Minecraft, "overworld"),
         dimension_type: Identifier::new(Namespace::Minecraft, "overworld"),
-        hashed_seed: 0,
+        hashed_seed,
         death_location: None,
         is_debug: false,
-        is_flat: false,
-        max_players: VarInt(1),
+        is_flat,
+        max_players: VarInt(config.status.max_players as i32),
         reduced_debug_info: false,
-        simulation_distance: VarInt(2),
-        view_distance: VarInt(2),
+        simulation_distance: VarInt(config.world.simulation_distance.into()),
+        view_distance: VarInt(config.world.view_distance.into()),
         portal_cooldown: VarInt(20),
     };
 
@@ -210,55 +282,7 @@ async fn login(net: Arc<PlayerNet>, cx: Arc<TaskContext>, ent_id: Entity) -> Res
     })
     .await?;
 
-    Ok(())
-}
-
-async fn status(net: Arc<PlayerNet>) -> Result<()> {
-    net.state.set(State::Status).await;
-
-    let _: StatusRequest = net.recv_packet().await?;
-    net.send_packet(StatusResponse {
-        json_response: Json(StatusResponseJson {
-            enforces_secure_chat: false,
-            version: status::Version {
-                name: String::from("Implodent"),
-                protocol: PROTOCOL_VERSION,
-            },
-            description: chat::ChatComponent::Multi(vec![
-                ChatComponent::String(ChatStringComponent {
-                    text: "help\n".into(),
-                    basic: chat::BasicChatComponent {
-                        bold: true,
-                        color: Some(ChatColor::Named(chat::ChatColorNamed::Aqua)),
-                        ..Default::default()
-                    },
-                }),
-                ChatComponent::String(ChatStringComponent {
-                    text: "help please".into(),
-                    basic: BasicChatComponent {
-                        italic: true,
-                        color: Some(ChatColor::Named(chat::ChatColorNamed::Gold)),
-                        ..Default::default()
-                    },
-                }),
-            ]),
-            players: Players {
-                max: -1,
-                online: 747106,
-                sample: vec![Sample {
-                    id: "66b7b182-6a07-4f27-a726-69c93a06ce84".into(),
-                    name: "NothinGG_".into(),
-                }],
-            },
-            ..Default::default()
-        }),
-    })
-    .await?;
-
-    let PingRequest { payload } = net.recv_packet().await?;
-    net.send_packet(PongResponse { payload }).await?;
-
-    Ok(())
+    play::play(net, cx, ent_id).await
 }
 
 pub struct Network {
@@ -274,15 +298,48 @@ impl Clone for NetNet {
     }
 }
 
+/// Sends `packet` to every player that is in the play state.
+pub fn broadcast<T: Packet + Serialize + Debug + Clone + Send + 'static>(
+    rt: &TokioTasksRuntime,
+    players: &Query<&PlayerN, With<Player>>,
+    packet: T,
+) {
+    send_to(
+        rt,
+        players.iter().map(|player| player.0.clone()).collect(),
+        packet,
+    );
+}
+
+/// Sends `packet` to each of `players` that is in the play state.
+pub fn send_to<T: Packet + Serialize + Debug + Clone + Send + 'static>(
+    rt: &TokioTasksRuntime,
+    players: Vec<Arc<PlayerNet>>,
+    packet: T,
+) {
+    rt.spawn_background_task(move |_| async move {
+        for player in players {
+            if *player.state.read().await != State::Play {
+                continue;
+            }
+
+            if let Err(error) = player.send_packet(packet.clone()).await {
+                debug!(?error, addr=%player.peer_addr, "failed to broadcast a packet");
+            }
+        }
+    });
+}
+
 #[derive(Event, Clone)]
 struct PlayerLoginEvent {
     pub addr: SocketAddr,
     pub entity: Entity,
 }
 
-fn listen(net: Res<NetNet>, rt: Res<TokioTasksRuntime>) {
+fn listen(net: Res<NetNet>, rt: Res<TokioTasksRuntime>, shutdown: Res<Shutdown>) {
     let rt = rt.into_inner();
     let net = (net.into_inner()).0.clone();
+    let shutdown = shutdown.0.clone();
 
     info!("listening");
 
@@ -290,45 +347,66 @@ fn listen(net: Res<NetNet>, rt: Res<TokioTasksRuntime>) {
         let task = Arc::new(_task);
         loop {
             let t = task.clone();
-            let (tcp, addr) = net.tcp.accept().await?;
+            let (tcp, addr) = tokio::select! {
+                accepted = net.tcp.accept() => accepted?,
+                () = shutdown.cancelled() => break,
+            };
             info!(%addr, "accepted");
 
-            let (read, write) = tcp.into_split();
-            let cancellator = CancellationToken::new();
-
-            let CompressionThreshold(compress) = t
-                .run_on_main_thread(|tcx| *tcx.world.resource::<CompressionThreshold>())
-                .await;
-
-            let player = PlayerNet::new(read, write, cancellator.child_token(), compress);
-            let entity = t
-                .clone()
-                .run_on_main_thread(move |cx| {
-                    let entity = cx.world.spawn((PlayerN(Arc::new(player)),)).id();
-                    cx.world.send_event(PlayerLoginEvent { entity, addr });
-                    entity
-                })
-                .await;
-
             tokio::spawn(async move {
-                let taske = t;
-                cancellator.cancelled_owned().await;
-
-                taske
-                    .run_on_main_thread(move |cx| {
-                        if !cx.world.despawn(entity) {
-                            error!(?entity, "despawn failed");
-                        }
-                    })
-                    .await;
+                if let Err(error) = accept(tcp, addr, t).await {
+                    error!(%addr, error=?when_the_miette(Err::<(), _>(error)), "Connection failed");
+                }
             });
         }
 
-        #[allow(unreachable_code)]
+        info!("stopped accepting connections");
         Ok::<(), Error>(())
     });
 }
 
+async fn accept(tcp: TcpStream, addr: SocketAddr, t: Arc<TaskContext>) -> Result<()> {
+    // pre-1.7 clients start with a single 0xFE byte instead of a packet length
+    let mut first = [0u8; 1];
+    if tcp.peek(&mut first).await? == 1 && first[0] == LEGACY_PING {
+        debug!(%addr, "legacy server list ping");
+        return status::legacy_ping(tcp, t).await;
+    }
+
+    let (read, write) = tcp.into_split();
+    let cancellator = CancellationToken::new();
+
+    let (CompressionThreshold(compress), net_config) = t
+        .run_on_main_thread(|tcx| {
+            (
+                *tcx.world.resource::<CompressionThreshold>(),
+                *tcx.world.resource::<NetConfig>(),
+            )
+        })
+        .await;
+
+    let player = PlayerNet::new(read, write, cancellator.child_token(), compress, net_config);
+    let entity = t
+        .clone()
+        .run_on_main_thread(move |cx| {
+            let entity = cx.world.spawn((PlayerN(Arc::new(player)),)).id();
+            cx.world.send_event(PlayerLoginEvent { entity, addr });
+            entity
+        })
+        .await;
+
+    cancellator.cancelled_owned().await;
+
+    t.run_on_main_thread(move |cx| {
+        if !cx.world.despawn(entity) {
+            error!(?entity, "despawn failed");
+        }
+    })
+    .await;
+
+    Ok(())
+}
+
 fn on_login(rt: Res<TokioTasksRuntime>, mut ev: EventReader<PlayerLoginEvent>, q: Query<&PlayerN>) {
     for event in ev.iter().cloned() {
         info!(%event.addr, "Logged in");
@@ -384,9 +462,25 @@ fn init_registries(
         .0
         .extend([("minecraft:overworld".to_string(), DimensionType::OVERWORLD)]);
 
-    worldgen_biomes
-        .0
-        .extend([("minecraft:plains".to_string(), WorldgenBiome::PLAINS)]);
+    worldgen_biomes.0.extend([
+        ("minecraft:plains".to_string(), WorldgenBiome::PLAINS),
+        ("minecraft:desert".to_string(), WorldgenBiome::DESERT),
+        ("minecraft:forest".to_string(), WorldgenBiome::FOREST),
+        (
+            "minecraft:snowy_plains".to_string(),
+            WorldgenBiome::SNOWY_PLAINS,
+        ),
+        ("minecraft:beach".to_string(), WorldgenBiome::BEACH),
+        ("minecraft:ocean".to_string(), WorldgenBiome::OCEAN),
+        (
+            "minecraft:deep_ocean".to_string(),
+            WorldgenBiome::DEEP_OCEAN,
+        ),
+        (
+            "minecraft:stony_peaks".to_string(),
+            WorldgenBiome::STONY_PEAKS,
+        ),
+    ]);
 
     damage_types.0.extend([
         ("minecraft:arrow".to_string(), DamageType::ARROW),
@@ -423,27 +517,92 @@ to something like =debug or =info, your log files might explode.
 That was my warning, now I wish you good luck debugging your issue."#
     );
 
-    let tcp = tokio::net::TcpListener::bind(("127.0.0.1", cli.port))
+    let mut config = Config::load(&cli.config)?;
+    cli.apply(&mut config);
+    config.validate(None)?;
+
+    let config_source = ConfigSource {
+        path: cli.config.clone(),
+        cli,
+    };
+
+    let server_status = ServerStatus::from_config(&config.status)?;
+
+    let tcp = tokio::net::TcpListener::bind((config.network.bind, config.network.port))
         .await
         .into_diagnostic()?;
 
-    info!("Starting server on port {}", cli.port);
+    info!(
+        "Starting server on {}:{}",
+        config.network.bind, config.network.port
+    );
 
+    let shutdown = Shutdown::default();
     App::new()
-        .add_plugins(ProtocolPlugin)
+        .add_plugins((ProtocolPlugin, CommandPlugin))
         .add_event::<PlayerLoginEvent>()
         .insert_resource(NetNet(Arc::new(Network { tcp })))
         .insert_resource(DifficultySetting {
-            difficulty: Difficulty::Hard,
-            is_locked: false,
+            difficulty: config.world.difficulty,
+            is_locked: config.world.difficulty_locked,
         })
         .init_resource::<Registry<DimensionType>>()
         .init_resource::<Registry<WorldgenBiome>>()
         .init_resource::<Registry<DamageType>>()
-        .insert_resource(CompressionThreshold(Some(256)))
-        .add_systems(Startup, (init_registries, listen))
-        .add_systems(Update, on_login)
+        .insert_resource(CompressionThreshold(config.network.compression_threshold()))
+        .insert_resource(config.network.net_config())
+        .insert_resource(server_status)
+        .insert_resource(config)
+        .insert_resource(config_source)
+        .insert_resource(shutdown.clone())
+        .init_resource::<TabList>()
+        .add_systems(
+            Startup,
+            (
+                (init_registries, init_world).chain(),
+                load_containers,
+                listen,
+                listen_query,
+                listen_rcon,
+                watch_config,
+                console,
+                handle_signals,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                on_login,
+                broadcast_difficulty,
+                broadcast_distances,
+                add_to_tab_list,
+                remove_from_tab_list,
+                update_tab_list,
+                broadcast_tab_list_header,
+                (
+                    pick_up_items,
+                    broadcast_movement,
+                    sync_metadata,
+                    track_entities.after(add_to_tab_list),
+                )
+                    .chain(),
+                (
+                    finish_generating,
+                    update_views,
+                    send_chunks,
+                    broadcast_block_changes,
+                    broadcast_light_changes,
+                    unload_chunks,
+                )
+                    .chain(),
+                broadcast_destroy_stages,
+            ),
+        )
+        .add_systems(Save, (save_chunks, save_containers))
         .run();
 
+    if shutdown.timed_out() {
+        return Err(Error::ShutdownTimeout.into());
+    }
     Ok(())
 }
@@ -0,0 +1,154 @@
+//! Keeping the metadata of entities in sync with their components. Only what changed is sent,
+//! everything is sent when a player is sent an entity for the first time.
+
+use bevy::{ecs::query::WorldQuery, prelude::*};
+use oxcr_protocol::{
+    model::{
+        metadata::{EntityMetadata, MetadataValue},
+        packets::play::SetEntityMetadata,
+    },
+    PlayerN,
+};
+
+use crate::{
+    dropped_items::DroppedItem,
+    model::{CustomName, EntityPose, Health, Joined, Sneaking, Sprinting},
+    tracker::{network_id, TrackedEntities},
+};
+
+/// The indices of the entries, these are the same for every entity type that has them.
+const FLAGS: u8 = 0;
+const CUSTOM_NAME: u8 = 2;
+const CUSTOM_NAME_VISIBLE: u8 = 3;
+const POSE: u8 = 6;
+/// Only item entities have this.
+const ITEM: u8 = 8;
+/// Only living entities have this.
+const HEALTH: u8 = 9;
+
+const CROUCHING: i8 = 0x02;
+const SPRINTING: i8 = 0x08;
+
+/// The components that make up the metadata of an entity.
+#[derive(WorldQuery)]
+pub struct MetadataQuery {
+    sneaking: Option<Ref<'static, Sneaking>>,
+    sprinting: Option<Ref<'static, Sprinting>>,
+    pose: Option<Ref<'static, EntityPose>>,
+    custom_name: Option<Ref<'static, CustomName>>,
+    health: Option<Ref<'static, Health>>,
+    item: Option<Ref<'static, DroppedItem>>,
+}
+
+impl MetadataQueryItem<'_> {
+    /// The entries for the components that changed since the system last ran, or for all of them.
+    pub fn metadata(&self, all: bool) -> EntityMetadata {
+        fn changed<T: Component>(component: &Option<Ref<T>>, all: bool) -> bool {
+            component
+                .as_ref()
+                .is_some_and(|component| all || component.is_changed())
+        }
+
+        let mut metadata = EntityMetadata::default();
+
+        if changed(&self.sneaking, all) || changed(&self.sprinting, all) {
+            let mut flags = 0;
+            if self.sneaking.as_ref().is_some_and(|sneaking| sneaking.0) {
+                flags |= CROUCHING;
+            }
+            if self.sprinting.as_ref().is_some_and(|sprinting| sprinting.0) {
+                flags |= SPRINTING;
+            }
+            metadata.set(FLAGS, MetadataValue::Byte(flags));
+        }
+
+        if let Some(pose) = self.pose.as_ref().filter(|_| changed(&self.pose, all)) {
+            metadata.set(POSE, MetadataValue::Pose(pose.0));
+        }
+
+        if let Some(name) = self
+            .custom_name
+            .as_ref()
+            .filter(|_| changed(&self.custom_name, all))
+        {
+            metadata.set(CUSTOM_NAME, MetadataValue::OptChat(name.0.clone()));
+            metadata.set(
+                CUSTOM_NAME_VISIBLE,
+                MetadataValue::Boolean(name.0.is_some()),
+            );
+        }
+
+        if let Some(health) = self.health.as_ref().filter(|_| changed(&self.health, all)) {
+            metadata.set(HEALTH, MetadataValue::Float(health.0));
+        }
+
+        if let Some(item) = self.item.as_ref().filter(|_| changed(&self.item, all)) {
+            metadata.set(ITEM, MetadataValue::Slot(Some(item.stack.clone())));
+        }
+
+        metadata
+    }
+}
+
+/// Sends what changed about entities to the players tracking them, and to the entity itself if it's a player.
+pub fn sync_metadata(
+    changed: Query<
+        (Entity, MetadataQuery),
+        Or<(
+            Changed<Sneaking>,
+            Changed<Sprinting>,
+            Changed<EntityPose>,
+            Changed<CustomName>,
+            Changed<Health>,
+            Changed<DroppedItem>,
+        )>,
+    >,
+    viewers: Query<(Entity, &PlayerN, Option<&TrackedEntities>), With<Joined>>,
+) {
+    for (entity, components) in &changed {
+        let metadata = components.metadata(false);
+        if metadata.is_empty() {
+            continue;
+        }
+
+        let packet = SetEntityMetadata {
+            entity_id: network_id(entity),
+            metadata,
+        };
+
+        for (viewer, net, tracked) in &viewers {
+            if viewer != entity && !tracked.is_some_and(|tracked| tracked.contains(entity)) {
+                continue;
+            }
+
+            net.0.queue(packet.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oxcr_protocol::model::metadata::Pose;
+
+    use super::*;
+
+    #[test]
+    fn only_sends_changes() {
+        let mut world = World::new();
+        let entity = world
+            .spawn((Sneaking(true), EntityPose(Pose::Sneaking), Health(20.0)))
+            .id();
+
+        let mut state = world.query::<MetadataQuery>();
+        let all = state.get(&world, entity).unwrap().metadata(true);
+        assert_eq!(all.0.len(), 3);
+
+        world.clear_trackers();
+        world.entity_mut(entity).insert(Health(15.0));
+
+        let changed = state.get(&world, entity).unwrap().metadata(false);
+        assert!(
+            matches!(&changed.0[..], [(HEALTH, MetadataValue::Float(health))] if *health == 15.0)
+        );
+    }
+}
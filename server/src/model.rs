@@ -1,6 +1,9 @@
-use bevy::prelude::{Bundle, Component, Resource};
+use bevy::{
+    math::DVec3,
+    prelude::{Bundle, Component, Resource},
+};
 use oxcr_protocol::{
-    model::{packets::play::GameMode, Difficulty},
+    model::{chat::ChatComponent, metadata::Pose, packets::play::GameMode, Difficulty},
     ser::*,
     uuid::Uuid,
 };
@@ -17,6 +20,56 @@ pub struct PlayerUuid(pub Uuid);
 #[derive(Component, Debug)]
 pub struct PlayerGameMode(pub GameMode);
 
+/// Added once the player was sent everything it needs to be in the world,
+/// from then on it can be sent anything that happens in the world.
+#[derive(Component, Debug)]
+pub struct Joined;
+
+/// How long the player takes to answer keep alives, in milliseconds, averaged like vanilla does.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerLatency(pub i32);
+
+/// Where an entity is, in blocks. For players, that's where their feet are.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Position(pub DVec3);
+
+/// Which way an entity is looking, in degrees.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rotation {
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OnGround(pub bool);
+
+/// An entity that isn't a player, by its id in the `minecraft:entity_type` registry.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntityKind(pub i32);
+
+impl EntityKind {
+    pub const ITEM: Self = Self(54);
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sneaking(pub bool);
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sprinting(pub bool);
+
+/// How an entity's model is posed, like lying in a bed or crouching.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EntityPose(pub Pose);
+
+/// The name shown above an entity, players always have theirs shown instead.
+/// Changed with the `name` command.
+#[derive(Component, Debug, Clone, Default)]
+pub struct CustomName(pub Option<ChatComponent>);
+
+/// The health of a living entity, 20 is the most a player can have.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Health(pub f32);
+
 #[derive(Bundle, Debug)]
 pub struct PlayerBundle {
     pub player_marker: Player,
@@ -0,0 +1,349 @@
+//! What players send while they're playing. For now that's moving around, sneaking and sprinting, using their inventory,
+//! breaking and placing blocks, commands, asking for command suggestions, their settings, and keep alives.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{math::DVec3, prelude::*};
+use oxcr_protocol::{
+    executor::TaskContext,
+    model::{
+        chat::{BasicChatComponent, ChatColor, ChatColorNamed, ChatComponent, ChatStringComponent},
+        metadata::Pose,
+        packets::{
+            play::{
+                ChatCommand, ClickContainer, ClientInformation, CloseContainerServerbound,
+                CommandSuggestion, CommandSuggestionsRequest, CommandSuggestionsResponse,
+                DisconnectPlay, KeepAlive, KeepAliveResponse, PlayerAction, PlayerCommand,
+                SetCreativeModeSlot, SetHeldItemServerbound, SetPlayerOnGround, SetPlayerPosition,
+                SetPlayerPositionAndRotation, SetPlayerRotation, SynchronizePlayerPosition,
+                SystemChatMessage, UseItemOn,
+            },
+            Packet,
+        },
+        State, VarInt,
+    },
+    ser::{Array, FixedStr, Json},
+    PlayerNet,
+};
+
+use crate::{
+    command::{dispatch, suggest, CommandRegistry, CommandSender},
+    config::Config,
+    interaction,
+    inventory::{self, Inventory},
+    model::{
+        EntityPose, Health, Joined, OnGround, PlayerLatency, Position, Rotation, Sneaking,
+        Sprinting,
+    },
+    tracker::TrackedEntities,
+    view::{self, ViewDistance},
+    world::Chunks,
+    Result,
+};
+
+/// How often keep alives are sent, same as vanilla.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+/// How long the player has to answer a keep alive before being disconnected.
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Puts the player at the spawn and sends the commands they can run, their inventory and the chunks around them,
+/// then handles packets until the connection ends.
+pub async fn play(net: Arc<PlayerNet>, cx: Arc<TaskContext>, player: Entity) -> Result<()> {
+    let (commands, inventory, spawn) = cx
+        .run_on_main_thread(move |w| {
+            let spawn = w.world.resource::<Chunks>().spawn();
+            let view_distance = w.world.resource::<Config>().world.view_distance;
+            w.world.entity_mut(player).insert((
+                Joined,
+                PlayerLatency::default(),
+                Position(spawn),
+                Rotation::default(),
+                OnGround(false),
+                TrackedEntities::default(),
+                Sneaking(false),
+                Sprinting(false),
+                EntityPose::default(),
+                Health(20.0),
+                Inventory::default(),
+                ViewDistance(view_distance),
+            ));
+
+            let op = CommandSender::Player(player).is_op(w.world);
+            let inventory = Inventory::default().content_packet();
+            view::load_spawn_chunks(w.world, player, (0, 0));
+            (
+                w.world.resource::<CommandRegistry>().graph(op),
+                inventory,
+                spawn,
+            )
+        })
+        .await;
+    net.send_packet(commands).await?;
+    net.send_packet(inventory).await?;
+
+    net.send_packet(SynchronizePlayerPosition {
+        x: spawn.x,
+        y: spawn.y,
+        z: spawn.z,
+        yaw: 0.0,
+        pitch: 0.0,
+        flags: 0,
+        teleport_id: VarInt(0),
+    })
+    .await?;
+
+    let mut keep_alive = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+    let mut pending: Option<(i64, Instant)> = None;
+
+    loop {
+        let packet = tokio::select! {
+            packet = net.recv.recv_async() => match packet {
+                Ok(packet) => packet,
+                // the channel is closed once the connection ends
+                Err(_) => return Ok(()),
+            },
+            _ = keep_alive.tick() => {
+                match pending {
+                    Some((_, sent)) if sent.elapsed() >= KEEP_ALIVE_TIMEOUT => {
+                        info!(addr = %net.peer_addr, "timed out");
+                        let reason = ChatComponent::translatable("disconnect.timeout", vec![]);
+                        let _ = net.send_packet(DisconnectPlay { reason: Json(reason) }).await;
+                        net.cancellator.cancel();
+                        return Ok(());
+                    }
+                    // still waiting for the last one
+                    Some(_) => {}
+                    None => {
+                        let id = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map_or(0, |now| now.as_millis() as i64);
+                        pending = Some((id, Instant::now()));
+                        net.send_packet(KeepAlive { id }).await?;
+                    }
+                }
+                continue;
+            }
+        };
+
+        if packet.id == SetPlayerPosition::ID {
+            let SetPlayerPosition { x, y, z, on_ground } = packet.try_deserialize(State::Play)?;
+            move_player(
+                &net,
+                &cx,
+                player,
+                Some(DVec3::new(x, y, z)),
+                None,
+                on_ground,
+            )
+            .await;
+        } else if packet.id == SetPlayerPositionAndRotation::ID {
+            let SetPlayerPositionAndRotation {
+                x,
+                y,
+                z,
+                yaw,
+                pitch,
+                on_ground,
+            } = packet.try_deserialize(State::Play)?;
+            let position = Some(DVec3::new(x, y, z));
+            let rotation = Some(Rotation { yaw, pitch });
+            move_player(&net, &cx, player, position, rotation, on_ground).await;
+        } else if packet.id == SetPlayerRotation::ID {
+            let SetPlayerRotation {
+                yaw,
+                pitch,
+                on_ground,
+            } = packet.try_deserialize(State::Play)?;
+            let rotation = Some(Rotation { yaw, pitch });
+            move_player(&net, &cx, player, None, rotation, on_ground).await;
+        } else if packet.id == SetPlayerOnGround::ID {
+            let SetPlayerOnGround { on_ground } = packet.try_deserialize(State::Play)?;
+            move_player(&net, &cx, player, None, None, on_ground).await;
+        } else if packet.id == PlayerCommand::ID {
+            let PlayerCommand { action, .. } = packet.try_deserialize(State::Play)?;
+
+            cx.run_on_main_thread(move |w| {
+                let Some(mut entity) = w.world.get_entity_mut(player) else {
+                    return;
+                };
+
+                match action {
+                    PlayerCommand::START_SNEAKING => {
+                        entity.insert((Sneaking(true), EntityPose(Pose::Sneaking)));
+                    }
+                    PlayerCommand::STOP_SNEAKING => {
+                        entity.insert((Sneaking(false), EntityPose(Pose::Standing)));
+                    }
+                    PlayerCommand::START_SPRINTING => {
+                        entity.insert(Sprinting(true));
+                    }
+                    PlayerCommand::STOP_SPRINTING => {
+                        entity.insert(Sprinting(false));
+                    }
+                    // there's nothing to ride or fly with yet
+                    _ => {}
+                }
+            })
+            .await;
+        } else if packet.id == ClickContainer::ID {
+            let click: ClickContainer = packet.try_deserialize(State::Play)?;
+            cx.run_on_main_thread(move |w| inventory::click_container(w.world, player, click))
+                .await;
+        } else if packet.id == CloseContainerServerbound::ID {
+            let CloseContainerServerbound { window_id } = packet.try_deserialize(State::Play)?;
+            cx.run_on_main_thread(move |w| inventory::close_container(w.world, player, window_id))
+                .await;
+        } else if packet.id == SetHeldItemServerbound::ID {
+            let SetHeldItemServerbound { slot } = packet.try_deserialize(State::Play)?;
+            cx.run_on_main_thread(move |w| inventory::set_held_item(w.world, player, slot))
+                .await;
+        } else if packet.id == SetCreativeModeSlot::ID {
+            let packet: SetCreativeModeSlot = packet.try_deserialize(State::Play)?;
+            cx.run_on_main_thread(move |w| inventory::set_creative_slot(w.world, player, packet))
+                .await;
+        } else if packet.id == PlayerAction::ID {
+            let action: PlayerAction = packet.try_deserialize(State::Play)?;
+            cx.run_on_main_thread(move |w| interaction::player_action(w.world, player, action))
+                .await;
+        } else if packet.id == UseItemOn::ID {
+            let packet: UseItemOn = packet.try_deserialize(State::Play)?;
+            cx.run_on_main_thread(move |w| interaction::use_item_on(w.world, player, packet))
+                .await;
+        } else if packet.id == ChatCommand::ID {
+            let ChatCommand { command } = packet.try_deserialize(State::Play)?;
+            run_command(&net, &cx, player, command.to_string()).await?;
+        } else if packet.id == CommandSuggestionsRequest::ID {
+            let request: CommandSuggestionsRequest = packet.try_deserialize(State::Play)?;
+            suggest_command(&net, &cx, player, request).await?;
+        } else if packet.id == ClientInformation::ID {
+            let ClientInformation { view_distance, .. } = packet.try_deserialize(State::Play)?;
+            cx.run_on_main_thread(move |w| view::set_view_distance(w.world, player, view_distance))
+                .await;
+        } else if packet.id == KeepAliveResponse::ID {
+            let KeepAliveResponse { id } = packet.try_deserialize(State::Play)?;
+
+            match pending {
+                Some((expected, sent)) if expected == id => {
+                    pending = None;
+                    let latency = sent.elapsed().as_millis().min(i32::MAX as u128) as i32;
+
+                    cx.run_on_main_thread(move |w| {
+                        if let Some(mut average) = w.world.get_mut::<PlayerLatency>(player) {
+                            // the same moving average as vanilla
+                            average.0 = (average.0 * 3 + latency) / 4;
+                        }
+                    })
+                    .await;
+                }
+                _ => debug!(addr = %net.peer_addr, id, "got a keep alive that wasn't sent"),
+            }
+        } else {
+            trace!(id = ?packet.id, addr = %net.peer_addr, "ignoring a packet");
+        }
+    }
+}
+
+/// Updates where the player is, [`broadcast_movement`](crate::tracker::broadcast_movement) tells everyone else.
+async fn move_player(
+    net: &PlayerNet,
+    cx: &TaskContext,
+    player: Entity,
+    position: Option<DVec3>,
+    rotation: Option<Rotation>,
+    on_ground: bool,
+) {
+    if !position.map_or(true, |position| position.is_finite())
+        || !rotation.map_or(true, |rotation| {
+            rotation.yaw.is_finite() && rotation.pitch.is_finite()
+        })
+    {
+        debug!(addr = %net.peer_addr, ?position, ?rotation, "ignoring an invalid move");
+        return;
+    }
+
+    cx.run_on_main_thread(move |w| {
+        let Some(mut entity) = w.world.get_entity_mut(player) else {
+            return;
+        };
+
+        if let Some(position) = position {
+            entity.insert(Position(position));
+        }
+        if let Some(rotation) = rotation {
+            entity.insert(rotation);
+        }
+        // only marked as changed when it did, since it's sent with every move
+        if entity.get::<OnGround>() != Some(&OnGround(on_ground)) {
+            entity.insert(OnGround(on_ground));
+        }
+    })
+    .await;
+}
+
+async fn run_command(
+    net: &PlayerNet,
+    cx: &TaskContext,
+    player: Entity,
+    command: String,
+) -> Result<()> {
+    let output = cx
+        .run_on_main_thread(move |w| dispatch(w.world, CommandSender::Player(player), &command))
+        .await;
+
+    let lines = match output {
+        Ok(lines) => lines,
+        Err(error) => vec![ChatComponent::String(ChatStringComponent {
+            text: error.to_string(),
+            basic: BasicChatComponent {
+                color: Some(ChatColor::Named(ChatColorNamed::Red)),
+                ..Default::default()
+            },
+        })],
+    };
+
+    for line in lines {
+        net.send_packet(SystemChatMessage {
+            content: Json(line),
+            overlay: false,
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn suggest_command(
+    net: &PlayerNet,
+    cx: &TaskContext,
+    player: Entity,
+    request: CommandSuggestionsRequest,
+) -> Result<()> {
+    let text = request.text.to_string();
+    // the suggestions are for the text with the `/`, but the commands are parsed without it
+    let slash = usize::from(text.starts_with('/'));
+    let line = text[slash..].to_owned();
+
+    let (start, matches) = cx
+        .run_on_main_thread(move |w| suggest(w.world, CommandSender::Player(player), &line))
+        .await;
+
+    net.send_packet(CommandSuggestionsResponse {
+        transaction_id: request.transaction_id,
+        start: VarInt((start + slash) as i32),
+        length: VarInt((text.len() - start - slash) as i32),
+        matches: matches
+            .iter()
+            .filter_map(|suggestion| FixedStr::from_string(suggestion))
+            .map(|suggestion| CommandSuggestion {
+                suggestion,
+                tooltip: None,
+            })
+            .collect::<Array<_>>(),
+    })
+    .await?;
+
+    Ok(())
+}
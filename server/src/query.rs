@@ -0,0 +1,307 @@
+//! The GameSpy4 query protocol over UDP, which monitoring tools use to get more details than the server list shows.
+//!
+//! A client first asks for a challenge token with a handshake, then sends the token back with a stat request.
+//! Every packet starts with the magic, the packet type and a session id, which the response echoes.
+
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::BuildHasher,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use oxcr_protocol::{
+    executor::{TaskContext, TokioTasksRuntime},
+    model::MINECRAFT_VERSION,
+    nsfr::when_the_miette,
+};
+use tokio::net::UdpSocket;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::Config,
+    model::{Player, PlayerName},
+    shutdown::Shutdown,
+    status::ServerStatus,
+    Result,
+};
+
+const MAGIC: [u8; 2] = [0xFE, 0xFD];
+const HANDSHAKE: u8 = 9;
+const STAT: u8 = 0;
+
+/// How long a challenge token stays valid, same as vanilla.
+const TOKEN_LIFETIME: Duration = Duration::from_secs(30);
+
+/// Constant bytes before the key-value section of the full stat response.
+const KEY_VALUES_PADDING: &[u8] = b"splitnum\0\x80\0";
+/// Constant bytes before the player list of the full stat response.
+const PLAYERS_PADDING: &[u8] = b"\x01player_\0\0";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Request {
+    Handshake { session: i32 },
+    BasicStat { session: i32, token: i32 },
+    FullStat { session: i32, token: i32 },
+}
+
+impl Request {
+    fn parse(packet: &[u8]) -> Option<Self> {
+        if packet.get(..2)? != MAGIC {
+            return None;
+        }
+
+        let int = |at: usize| Some(i32::from_be_bytes(packet.get(at..at + 4)?.try_into().ok()?));
+        let session = int(3)?;
+
+        match (*packet.get(2)?, packet.len() - 7) {
+            (HANDSHAKE, 0) => Some(Self::Handshake { session }),
+            (STAT, 4) => Some(Self::BasicStat {
+                session,
+                token: int(7)?,
+            }),
+            // the full stat request is padded with 4 more bytes
+            (STAT, 8) => Some(Self::FullStat {
+                session,
+                token: int(7)?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// What the stat responses are made of, read from the world for every request.
+#[derive(Debug, Clone)]
+struct Stats {
+    motd: String,
+    max_players: u32,
+    players: Vec<String>,
+    host: SocketAddr,
+}
+
+impl Stats {
+    fn read(world: &mut World) -> Self {
+        let status = world.resource::<ServerStatus>();
+        let (motd, max_players) = (status.motd.to_legacy(), status.max_players);
+
+        let network = &world.resource::<Config>().network;
+        let host = SocketAddr::new(network.bind, network.port);
+
+        let players = world
+            .query_filtered::<&PlayerName, With<Player>>()
+            .iter(world)
+            .map(|name| name.0.to_string())
+            .collect();
+
+        Self {
+            motd,
+            max_players,
+            players,
+            host,
+        }
+    }
+
+    fn basic(&self, session: i32) -> Vec<u8> {
+        let mut out = header(STAT, session);
+
+        for value in [
+            self.motd.as_str(),
+            "SMP",
+            "world",
+            &self.players.len().to_string(),
+            &self.max_players.to_string(),
+        ] {
+            string(&mut out, value);
+        }
+        out.extend(self.host.port().to_le_bytes());
+        string(&mut out, &self.host.ip().to_string());
+
+        out
+    }
+
+    fn full(&self, session: i32) -> Vec<u8> {
+        let mut out = header(STAT, session);
+
+        out.extend(KEY_VALUES_PADDING);
+        for (key, value) in [
+            ("hostname", self.motd.clone()),
+            ("gametype", String::from("SMP")),
+            ("game_id", String::from("MINECRAFT")),
+            ("version", String::from(MINECRAFT_VERSION)),
+            ("plugins", String::from("Oxcraft")),
+            ("map", String::from("world")),
+            ("numplayers", self.players.len().to_string()),
+            ("maxplayers", self.max_players.to_string()),
+            ("hostport", self.host.port().to_string()),
+            ("hostip", self.host.ip().to_string()),
+        ] {
+            string(&mut out, key);
+            string(&mut out, &value);
+        }
+        out.push(0);
+
+        out.extend(PLAYERS_PADDING);
+        for player in &self.players {
+            string(&mut out, player);
+        }
+        out.push(0);
+
+        out
+    }
+}
+
+fn header(kind: u8, session: i32) -> Vec<u8> {
+    let mut out = vec![kind];
+    out.extend(session.to_be_bytes());
+    out
+}
+
+/// Writes a null-terminated string.
+fn string(out: &mut Vec<u8>, value: &str) {
+    out.extend(value.as_bytes());
+    out.push(0);
+}
+
+/// The challenge tokens handed out to each address.
+#[derive(Debug, Default)]
+struct Tokens {
+    issued: HashMap<SocketAddr, (i32, Instant)>,
+    hasher: RandomState,
+}
+
+impl Tokens {
+    fn issue(&mut self, addr: SocketAddr) -> i32 {
+        let now = Instant::now();
+        self.issued
+            .retain(|_, (_, issued_at)| now.duration_since(*issued_at) < TOKEN_LIFETIME);
+
+        // clients parse the token as a signed integer, keep it positive just in case
+        let token = (self.hasher.hash_one((addr, now)) & 0x7FFF_FFFF) as i32;
+        self.issued.insert(addr, (token, now));
+        token
+    }
+
+    fn check(&self, addr: SocketAddr, token: i32) -> bool {
+        self.issued.get(&addr).is_some_and(|&(issued, issued_at)| {
+            issued == token && issued_at.elapsed() < TOKEN_LIFETIME
+        })
+    }
+}
+
+/// Starts the query listener, if it's enabled in the config.
+pub fn listen_query(config: Res<Config>, rt: Res<TokioTasksRuntime>, shutdown: Res<Shutdown>) {
+    if !config.query.enabled {
+        return;
+    }
+
+    let addr = SocketAddr::new(config.network.bind, config.query.port);
+    let shutdown = shutdown.0.clone();
+
+    rt.spawn_background_task(move |cx| async move {
+        if let Err(error) = serve(addr, cx, shutdown).await {
+            error!(error=?when_the_miette(Err::<(), _>(error)), "the query listener stopped");
+        }
+    });
+}
+
+async fn serve(addr: SocketAddr, cx: TaskContext, shutdown: CancellationToken) -> Result<()> {
+    let socket = UdpSocket::bind(addr).await?;
+    info!(%addr, "listening for queries");
+
+    let mut tokens = Tokens::default();
+    let mut buf = [0u8; 1500];
+
+    loop {
+        let (len, peer) = tokio::select! {
+            received = socket.recv_from(&mut buf) => received?,
+            () = shutdown.cancelled() => return Ok(()),
+        };
+
+        let Some(request) = Request::parse(&buf[..len]) else {
+            trace!(%peer, "ignoring a malformed query");
+            continue;
+        };
+        trace!(%peer, ?request, "query");
+
+        let response = match request {
+            Request::Handshake { session } => {
+                let mut out = header(HANDSHAKE, session);
+                string(&mut out, &tokens.issue(peer).to_string());
+                out
+            }
+            Request::BasicStat { session, token } | Request::FullStat { session, token } => {
+                if !tokens.check(peer, token) {
+                    trace!(%peer, "ignoring a query with an invalid challenge token");
+                    continue;
+                }
+
+                let stats = cx.run_on_main_thread(|w| Stats::read(w.world)).await;
+
+                match request {
+                    Request::FullStat { .. } => stats.full(session),
+                    _ => stats.basic(session),
+                }
+            }
+        };
+
+        if let Err(error) = socket.send_to(&response, peer).await {
+            debug!(%peer, ?error, "failed to answer a query");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_requests() {
+        assert_eq!(
+            Request::parse(&[0xFE, 0xFD, 9, 0, 0, 0, 1]),
+            Some(Request::Handshake { session: 1 })
+        );
+        assert_eq!(
+            Request::parse(&[0xFE, 0xFD, 0, 0, 0, 0, 1, 0, 0x91, 0x29, 0x5B]),
+            Some(Request::BasicStat {
+                session: 1,
+                token: 9513307
+            })
+        );
+        assert_eq!(
+            Request::parse(&[0xFE, 0xFD, 0, 0, 0, 0, 1, 0, 0x91, 0x29, 0x5B, 0, 0, 0, 0]),
+            Some(Request::FullStat {
+                session: 1,
+                token: 9513307
+            })
+        );
+        assert_eq!(Request::parse(&[0xFE, 0xFD, 0, 0, 0]), None);
+        assert_eq!(Request::parse(&[0xFE, 0xFC, 9, 0, 0, 0, 1]), None);
+    }
+
+    #[test]
+    fn basic_stat() {
+        let stats = Stats {
+            motd: String::from("A Minecraft Server"),
+            max_players: 20,
+            players: vec![String::from("Steve")],
+            host: "127.0.0.1:25565".parse().unwrap(),
+        };
+
+        assert_eq!(
+            stats.basic(1),
+            b"\0\0\0\0\x01A Minecraft Server\0SMP\0world\x001\x0020\0\xDDc127.0.0.1\0"
+        );
+    }
+
+    #[test]
+    fn tokens() {
+        let mut tokens = Tokens::default();
+        let addr = "127.0.0.1:1234".parse().unwrap();
+        let token = tokens.issue(addr);
+
+        assert!(tokens.check(addr, token));
+        assert!(!tokens.check(addr, token.wrapping_add(1)));
+        assert!(!tokens.check("127.0.0.1:1235".parse().unwrap(), token));
+    }
+}
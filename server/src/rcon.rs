@@ -0,0 +1,262 @@
+//! The Source RCON protocol, for running commands remotely.
+//!
+//! Every packet is a little-endian `i32` length, followed by the request id, the packet type
+//! and a null-terminated body, with one more null byte at the end.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use bevy::prelude::*;
+use oxcr_protocol::{
+    executor::{TaskContext, TokioTasksRuntime},
+    nsfr::when_the_miette,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    command::{dispatch, CommandSender},
+    config::Config,
+    error::Error,
+    shutdown::Shutdown,
+    Result,
+};
+
+const AUTH: i32 = 3;
+const AUTH_RESPONSE: i32 = 2;
+const EXEC_COMMAND: i32 = 2;
+const RESPONSE_VALUE: i32 = 0;
+
+/// The largest packet clients are allowed to send, same as vanilla.
+const MAX_REQUEST_LENGTH: i32 = 1460;
+/// Longer responses are split into several packets.
+const MAX_RESPONSE_BODY: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RconPacket {
+    id: i32,
+    kind: i32,
+    body: String,
+}
+
+impl RconPacket {
+    /// Reads a packet, or returns `None` if the connection was closed before the next one.
+    async fn read(read: &mut (impl AsyncRead + Unpin)) -> Result<Option<Self>> {
+        let length = match read.read_i32_le().await {
+            Ok(length) => length,
+            Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+
+        // the id, the type and the two null bytes
+        if !(10..=MAX_REQUEST_LENGTH).contains(&length) {
+            return Err(Error::InvalidRconPacket(length));
+        }
+
+        let mut packet = vec![0; length as usize];
+        read.read_exact(&mut packet).await?;
+
+        let int = |at: usize| i32::from_le_bytes(packet[at..at + 4].try_into().unwrap());
+        let body = &packet[8..packet.len() - 2];
+
+        Ok(Some(Self {
+            id: int(0),
+            kind: int(4),
+            body: String::from_utf8_lossy(body).into_owned(),
+        }))
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend((self.body.len() as i32 + 10).to_le_bytes());
+        out.extend(self.id.to_le_bytes());
+        out.extend(self.kind.to_le_bytes());
+        out.extend(self.body.as_bytes());
+        out.extend([0, 0]);
+    }
+}
+
+/// Splits `text` into pieces of at most `max` bytes, without splitting any characters.
+fn split(mut text: &str, max: usize) -> impl Iterator<Item = &str> {
+    std::iter::from_fn(move || {
+        if text.is_empty() {
+            return None;
+        }
+
+        let mut at = text.len().min(max);
+        while !text.is_char_boundary(at) {
+            at -= 1;
+        }
+
+        let (piece, rest) = text.split_at(at);
+        text = rest;
+        Some(piece)
+    })
+}
+
+/// Encodes the output of a command, split into as many packets as it takes.
+fn response(id: i32, output: &str) -> Vec<u8> {
+    let mut out = vec![];
+
+    if output.is_empty() {
+        RconPacket {
+            id,
+            kind: RESPONSE_VALUE,
+            body: String::new(),
+        }
+        .encode(&mut out);
+    }
+
+    for piece in split(output, MAX_RESPONSE_BODY) {
+        RconPacket {
+            id,
+            kind: RESPONSE_VALUE,
+            body: piece.to_owned(),
+        }
+        .encode(&mut out);
+    }
+
+    out
+}
+
+/// Starts the RCON listener, if it's enabled in the config.
+pub fn listen_rcon(config: Res<Config>, rt: Res<TokioTasksRuntime>, shutdown: Res<Shutdown>) {
+    if !config.rcon.enabled {
+        return;
+    }
+
+    let addr = SocketAddr::new(config.network.bind, config.rcon.port);
+    let shutdown = shutdown.0.clone();
+
+    rt.spawn_background_task(move |cx| async move {
+        let cx = Arc::new(cx);
+
+        let result: Result<()> = try {
+            let tcp = TcpListener::bind(addr).await?;
+            info!(%addr, "listening for RCON connections");
+
+            loop {
+                let (tcp, addr) = tokio::select! {
+                    accepted = tcp.accept() => accepted?,
+                    () = shutdown.cancelled() => break,
+                };
+                let cx = cx.clone();
+
+                tokio::spawn(async move {
+                    match when_the_miette(connection(tcp, addr, cx).await) {
+                        Ok(()) => debug!(%addr, "RCON connection closed"),
+                        Err(error) => info!(%addr, ?error, "RCON connection closed"),
+                    }
+                });
+            }
+        };
+
+        if let Err(error) = result {
+            error!(error=?when_the_miette(Err::<(), _>(error)), "the RCON listener stopped");
+        }
+    });
+}
+
+async fn connection(mut tcp: TcpStream, addr: SocketAddr, cx: Arc<TaskContext>) -> Result<()> {
+    let mut authenticated = false;
+
+    while let Some(request) = RconPacket::read(&mut tcp).await? {
+        // not logging the body, it can be the password
+        trace!(%addr, request.id, request.kind, "RCON packet");
+
+        let out = match request.kind {
+            AUTH => {
+                let password = cx
+                    .run_on_main_thread(|w| w.world.resource::<Config>().rcon.password.clone())
+                    .await;
+                authenticated = !password.is_empty() && request.body == password;
+
+                if authenticated {
+                    info!(%addr, "RCON client logged in");
+                } else {
+                    warn!(%addr, "RCON login with a wrong password");
+                }
+
+                let mut out = vec![];
+                RconPacket {
+                    id: if authenticated { request.id } else { -1 },
+                    kind: AUTH_RESPONSE,
+                    body: String::new(),
+                }
+                .encode(&mut out);
+                out
+            }
+            EXEC_COMMAND if authenticated => {
+                let line = request.body.clone();
+                let output = cx
+                    .run_on_main_thread(move |w| {
+                        match dispatch(w.world, CommandSender::Rcon(addr), &line) {
+                            Ok(output) => output
+                                .iter()
+                                .map(|line| line.to_plain())
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                            Err(error) => error.to_string(),
+                        }
+                    })
+                    .await;
+
+                response(request.id, &output)
+            }
+            // clients send an empty response value after a command to find where a split
+            // response ends: it's mirrored back, followed by a packet with a fixed body
+            RESPONSE_VALUE if authenticated => {
+                let mut out = response(request.id, "");
+                RconPacket {
+                    id: request.id,
+                    kind: RESPONSE_VALUE,
+                    body: String::from("\0\u{1}\0\0"),
+                }
+                .encode(&mut out);
+                out
+            }
+            _ => {
+                warn!(%addr, request.kind, "unexpected RCON packet, closing the connection");
+                break;
+            }
+        };
+
+        tcp.write_all(&out).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn packets() {
+        let packet = RconPacket {
+            id: 7,
+            kind: EXEC_COMMAND,
+            body: String::from("list"),
+        };
+
+        let mut bytes = vec![];
+        packet.encode(&mut bytes);
+        assert_eq!(bytes, b"\x0E\0\0\0\x07\0\0\0\x02\0\0\0list\0\0");
+
+        assert_eq!(
+            RconPacket::read(&mut &bytes[..]).await.unwrap(),
+            Some(packet)
+        );
+        assert_eq!(RconPacket::read(&mut &b""[..]).await.unwrap(), None);
+    }
+
+    #[test]
+    fn splits_long_responses() {
+        let text = "é".repeat(MAX_RESPONSE_BODY);
+        let pieces = split(&text, MAX_RESPONSE_BODY).collect::<Vec<_>>();
+
+        assert_eq!(pieces.len(), 2);
+        assert!(pieces.iter().all(|piece| piece.len() <= MAX_RESPONSE_BODY));
+        assert_eq!(pieces.concat(), text);
+    }
+}
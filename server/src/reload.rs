@@ -0,0 +1,275 @@
+//! Reloading `server.toml` while the server is running.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use bevy::prelude::*;
+use oxcr_protocol::{
+    executor::TokioTasksRuntime,
+    miette::Report,
+    model::{
+        packets::play::{ChangeDifficulty, SetRenderDistance, SetSimulationDistance},
+        VarInt,
+    },
+    PlayerN,
+};
+
+use crate::{
+    broadcast,
+    cli::Cli,
+    config::Config,
+    model::{DifficultySetting, Player},
+    status::ServerStatus,
+};
+
+/// How often the config file is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Where the config was loaded from, so that it can be read again with the same command line overrides.
+#[derive(Resource, Debug, Clone)]
+pub struct ConfigSource {
+    pub path: PathBuf,
+    pub cli: Cli,
+}
+
+impl ConfigSource {
+    fn read(&self) -> crate::Result<Config> {
+        let mut config = Config::read(&self.path)?;
+        self.cli.apply(&mut config);
+        config.validate(None)?;
+        Ok(config)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Polls the config file for changes, and applies them with [`apply_config`].
+pub fn watch_config(source: Res<ConfigSource>, rt: Res<TokioTasksRuntime>) {
+    let source = source.clone();
+
+    rt.spawn_background_task(move |cx| async move {
+        let mut last_modified = modified(&source.path);
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let modified = modified(&source.path);
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            if modified.is_none() {
+                warn!(path = %source.path.display(), "the config file is gone, keeping the current config");
+                continue;
+            }
+
+            match source.read() {
+                Ok(config) => {
+                    cx.run_on_main_thread(move |w| apply_config(w.world, config))
+                        .await
+                }
+                Err(error) => {
+                    error!(error = ?Report::new(error), "failed to reload the config, keeping the current one")
+                }
+            }
+        }
+    });
+}
+
+/// Applies the parts of `new` that can change at runtime, and logs the ones that need a restart.
+pub fn apply_config(world: &mut World, mut new: Config) {
+    let old = world.resource::<Config>().clone();
+
+    if old == new {
+        return;
+    }
+
+    let restart_required = [
+        ("network.bind", old.network.bind != new.network.bind),
+        ("network.port", old.network.port != new.network.port),
+        (
+            "network.compression_threshold",
+            old.network.compression_threshold != new.network.compression_threshold,
+        ),
+        (
+            "network.compression_level",
+            old.network.compression_level != new.network.compression_level,
+        ),
+        (
+            "network.online_mode",
+            old.network.online_mode != new.network.online_mode,
+        ),
+        (
+            "network.send_queue",
+            old.network.send_queue != new.network.send_queue,
+        ),
+        (
+            "network.recv_queue",
+            old.network.recv_queue != new.network.recv_queue,
+        ),
+        (
+            "network.send_policy",
+            old.network.send_policy != new.network.send_policy,
+        ),
+        (
+            "network.recv_policy",
+            old.network.recv_policy != new.network.recv_policy,
+        ),
+        (
+            "network.flush_on_tick",
+            old.network.flush_on_tick != new.network.flush_on_tick,
+        ),
+        (
+            "network.max_batch_bytes",
+            old.network.max_batch_bytes != new.network.max_batch_bytes,
+        ),
+        ("query.enabled", old.query.enabled != new.query.enabled),
+        ("query.port", old.query.port != new.query.port),
+        ("rcon.enabled", old.rcon.enabled != new.rcon.enabled),
+        ("rcon.port", old.rcon.port != new.rcon.port),
+        (
+            "world.generator",
+            old.world.generator != new.world.generator,
+        ),
+        (
+            "world.flat_preset",
+            old.world.flat_preset != new.world.flat_preset,
+        ),
+        ("world.seed", old.world.seed != new.world.seed),
+        (
+            "world.directory",
+            old.world.directory != new.world.directory,
+        ),
+    ];
+
+    for (key, _) in restart_required.iter().filter(|(_, changed)| *changed) {
+        warn!(%key, "changing this setting requires a restart");
+    }
+    // keep the values the server is actually running with
+    new.network = old.network.clone();
+    new.query = old.query.clone();
+    new.world.generator = old.world.generator;
+    new.world.flat_preset = old.world.flat_preset.clone();
+    new.world.seed = old.world.seed.clone();
+    new.world.directory = old.world.directory.clone();
+    // the password is read on every login, so it can change
+    new.rcon.enabled = old.rcon.enabled;
+    new.rcon.port = old.rcon.port;
+
+    if old.status != new.status {
+        match ServerStatus::from_config(&new.status) {
+            Ok(status) => {
+                world.insert_resource(status);
+                info!("reloaded the server status");
+            }
+            Err(error) => {
+                error!(
+                    ?error,
+                    "failed to reload the server status, keeping the current one"
+                );
+                new.status = old.status.clone();
+            }
+        }
+    }
+
+    if (old.world.difficulty, old.world.difficulty_locked)
+        != (new.world.difficulty, new.world.difficulty_locked)
+    {
+        *world.resource_mut::<DifficultySetting>() = DifficultySetting {
+            difficulty: new.world.difficulty,
+            is_locked: new.world.difficulty_locked,
+        };
+    }
+
+    if old.world.game_mode != new.world.game_mode {
+        info!(game_mode = ?new.world.game_mode, "players joining from now on will get the new game mode");
+    }
+
+    if old.whitelist != new.whitelist {
+        info!(
+            enabled = new.whitelist.enabled,
+            players = new.whitelist.players.len(),
+            "reloaded the whitelist"
+        );
+    }
+
+    if old.permissions != new.permissions {
+        info!(
+            ops = new.permissions.ops.len(),
+            "reloaded the operators, players see the commands they can run after rejoining"
+        );
+    }
+
+    world.insert_resource(new);
+    info!("reloaded the config");
+}
+
+/// Tells every player about changes to [`DifficultySetting`].
+pub fn broadcast_difficulty(
+    difficulty: Res<DifficultySetting>,
+    players: Query<&PlayerN, With<Player>>,
+    rt: Res<TokioTasksRuntime>,
+) {
+    if !difficulty.is_changed() || difficulty.is_added() {
+        return;
+    }
+
+    broadcast(
+        &rt,
+        &players,
+        ChangeDifficulty {
+            difficulty: difficulty.difficulty,
+            difficulty_locked: difficulty.is_locked,
+        },
+    );
+}
+
+/// Tells every player about changes to the view and simulation distance in [`Config`].
+pub fn broadcast_distances(
+    config: Res<Config>,
+    mut last: Local<Option<(u8, u8)>>,
+    players: Query<&PlayerN, With<Player>>,
+    rt: Res<TokioTasksRuntime>,
+) {
+    if !config.is_changed() {
+        return;
+    }
+
+    let (view_distance, simulation_distance) =
+        (config.world.view_distance, config.world.simulation_distance);
+
+    // the first time around, these are the values players got when joining
+    let Some((last_view_distance, last_simulation_distance)) =
+        last.replace((view_distance, simulation_distance))
+    else {
+        return;
+    };
+
+    if view_distance != last_view_distance {
+        broadcast(
+            &rt,
+            &players,
+            SetRenderDistance {
+                view_distance: VarInt(view_distance.into()),
+            },
+        );
+    }
+
+    if simulation_distance != last_simulation_distance {
+        broadcast(
+            &rt,
+            &players,
+            SetSimulationDistance {
+                simulation_distance: VarInt(simulation_distance.into()),
+            },
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! Stopping the server without just dropping everyone's connection.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bevy::{app::AppExit, ecs::schedule::ScheduleLabel, prelude::*};
+use oxcr_protocol::{
+    executor::{TaskContext, TokioTasksRuntime},
+    model::{
+        chat::ChatComponent,
+        packets::{login::DisconnectLogin, play::DisconnectPlay},
+        State,
+    },
+    ser::Json,
+    PlayerN, PlayerNet,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::Config;
+
+/// How often to check whether the disconnect messages were sent.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Cancelled once the server starts shutting down, at which point the listeners stop accepting connections.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Shutdown(pub CancellationToken, Arc<AtomicBool>);
+
+impl Shutdown {
+    /// Whether some players didn't get the disconnect message before the timeout, so the server should exit with an error.
+    pub fn timed_out(&self) -> bool {
+        self.1.load(Ordering::Relaxed)
+    }
+}
+
+/// Runs after every player was disconnected, right before the server exits.
+/// Anything that has to be persisted should be saved by a system in this schedule.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Save;
+
+/// Starts shutting down, unless that's already happening.
+pub fn request_shutdown(world: &mut World) {
+    let shutdown = world.resource::<Shutdown>();
+    if shutdown.0.is_cancelled() {
+        return;
+    }
+
+    info!("shutting down");
+    shutdown.0.cancel();
+
+    world
+        .resource::<TokioTasksRuntime>()
+        .spawn_background_task(shut_down);
+}
+
+async fn shut_down(cx: TaskContext) {
+    let (players, reason, timeout, shutdown) = cx
+        .run_on_main_thread(|w| {
+            let config = &w.world.resource::<Config>().shutdown;
+            // the config is validated, so the markup is too
+            let reason = ChatComponent::from_markup(&config.message)
+                .unwrap_or_else(|_| ChatComponent::text(&config.message));
+            let timeout = Duration::from_millis(config.timeout);
+
+            let players = w
+                .world
+                .query::<&PlayerN>()
+                .iter(w.world)
+                .map(|player| player.0.clone())
+                .collect::<Vec<Arc<PlayerNet>>>();
+
+            let shutdown = w.world.resource::<Shutdown>().clone();
+
+            (players, reason, timeout, shutdown)
+        })
+        .await;
+
+    info!(players = players.len(), "disconnecting everyone");
+
+    // a full send queue would make sending wait, so that counts towards the timeout too
+    let drained = tokio::time::timeout(timeout, async {
+        for player in &players {
+            let reason = Json(reason.clone());
+
+            // ignore the result, the connection is closed either way
+            let _ = match *player.state.read().await {
+                State::Login => player.send_packet(DisconnectLogin { reason }).await,
+                State::Play => player.send_packet(DisconnectPlay { reason }).await,
+                _ => Ok(()),
+            };
+            player.flush();
+        }
+
+        while players.iter().any(|player| {
+            let metrics = player.metrics();
+            !player.send.is_disconnected()
+                && (metrics.send_queue_depth > 0 || metrics.bytes_in_flight > 0)
+        }) {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .is_ok();
+
+    if !drained {
+        warn!(
+            ?timeout,
+            "not every player got the disconnect message in time"
+        );
+        shutdown.1.store(true, Ordering::Relaxed);
+    }
+
+    cx.run_on_main_thread(move |w| {
+        // before the connections are closed, so that the players are still around to be saved
+        info!("saving");
+        // there's nothing to do if nothing was added to the schedule
+        let _ = w.world.try_run_schedule(Save);
+
+        for player in &players {
+            player.cancellator.cancel();
+        }
+
+        w.world.send_event(AppExit);
+    })
+    .await;
+}
+
+/// Shuts down on Ctrl-C or SIGTERM. If the signal comes again while shutting down, the server exits immediately.
+pub fn handle_signals(rt: Res<TokioTasksRuntime>) {
+    rt.spawn_background_task(|cx| async move {
+        signal().await;
+        cx.run_on_main_thread(|w| request_shutdown(w.world)).await;
+
+        signal().await;
+        warn!("stopping right away");
+        std::process::exit(130);
+    });
+}
+
+async fn signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("can't listen for SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
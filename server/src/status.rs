@@ -0,0 +1,192 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bevy::prelude::*;
+use oxcr_protocol::{
+    executor::TaskContext,
+    miette::Report,
+    model::{
+        chat::{legacy::SECTION_SIGN, ChatComponent},
+        packets::status::{
+            self, PingRequest, Players, PongResponse, Sample, StatusRequest, StatusResponse,
+            StatusResponseJson,
+        },
+        State, PROTOCOL_VERSION,
+    },
+    ser::Json,
+    PlayerNet,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::{
+    config::StatusConfig,
+    error::Error,
+    model::{Player, PlayerName, PlayerUuid},
+    Result,
+};
+
+/// How many players the server list shows when hovering over the player count, same as vanilla.
+pub const SAMPLE_SIZE: usize = 12;
+
+/// The first byte of the legacy (pre-1.7) server list ping, which would be a packet length otherwise.
+pub const LEGACY_PING: u8 = 0xFE;
+/// The legacy kick packet, which is what the legacy ping is answered with.
+const LEGACY_KICK: u8 = 0xFF;
+/// How long to wait for the rest of the legacy ping, since clients before 1.4 only send the first byte.
+const LEGACY_PING_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Everything in the status response that doesn't change with the players online.
+#[derive(Resource, Debug, Clone)]
+pub struct ServerStatus {
+    pub motd: ChatComponent,
+    pub max_players: u32,
+    /// The favicon, already encoded as a data URL.
+    pub favicon: Option<String>,
+}
+
+impl ServerStatus {
+    pub fn from_config(config: &StatusConfig) -> Result<Self, Report> {
+        Ok(Self {
+            motd: ChatComponent::from_markup(&config.motd)?,
+            max_players: config.max_players,
+            favicon: config.favicon.as_deref().map(load_favicon).transpose()?,
+        })
+    }
+
+    fn response(&self, world: &mut World) -> StatusResponseJson {
+        let mut players = world.query_filtered::<(&PlayerName, &PlayerUuid), With<Player>>();
+
+        StatusResponseJson {
+            version: status::Version {
+                name: String::from("Implodent"),
+                protocol: PROTOCOL_VERSION,
+            },
+            description: self.motd.clone(),
+            players: Players {
+                max: self.max_players.into(),
+                online: players.iter(world).count() as i64,
+                sample: players
+                    .iter(world)
+                    .take(SAMPLE_SIZE)
+                    .map(|(name, uuid)| Sample {
+                        name: name.0.to_string(),
+                        id: uuid.0.to_string(),
+                    })
+                    .collect(),
+            },
+            favicon: self.favicon.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Reads a 64×64 PNG image and encodes it as a `data:image/png;base64,...` URL.
+pub fn load_favicon(path: &Path) -> Result<String> {
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    let invalid = |reason: &str| Error::InvalidFavicon {
+        path: path.to_owned(),
+        reason: reason.to_owned(),
+    };
+
+    let image = std::fs::read(path).map_err(|error| invalid(&error.to_string()))?;
+
+    // the IHDR chunk always comes first, right after the signature:
+    // length (4 bytes), "IHDR", width (4 bytes), height (4 bytes), ...
+    if !image.starts_with(PNG_SIGNATURE) || image.get(12..16) != Some(&b"IHDR"[..]) {
+        return Err(invalid("not a PNG image"));
+    }
+
+    let dimension = |at: usize| u32::from_be_bytes(image[at..at + 4].try_into().unwrap());
+    let (width, height) = match image.get(16..24) {
+        Some(_) => (dimension(16), dimension(20)),
+        None => return Err(invalid("the image ends too early")),
+    };
+
+    if (width, height) != (64, 64) {
+        return Err(invalid(&format!("the image is {width}x{height}")));
+    }
+
+    Ok(format!("data:image/png;base64,{}", BASE64.encode(&image)))
+}
+
+pub async fn status(net: Arc<PlayerNet>, cx: Arc<TaskContext>) -> Result<()> {
+    net.state.set(State::Status).await;
+
+    let _: StatusRequest = net.recv_packet().await?;
+
+    let response = cx
+        .run_on_main_thread(|w| {
+            let status = w.world.resource::<ServerStatus>().clone();
+            status.response(w.world)
+        })
+        .await;
+
+    net.send_packet(StatusResponse {
+        json_response: Json(response),
+    })
+    .await?;
+
+    let PingRequest { payload } = net.recv_packet().await?;
+    net.send_packet(PongResponse { payload }).await?;
+
+    Ok(())
+}
+
+/// Answers the legacy server list ping and closes the connection.
+///
+/// Clients from 1.4 to 1.6 send `0xFE 0x01` and get the protocol version and the formatted MOTD
+/// (1.6 also sends a plugin message after that, which is ignored). Older clients only send `0xFE`
+/// and get the MOTD as plain text.
+pub async fn legacy_ping(mut tcp: TcpStream, cx: Arc<TaskContext>) -> Result<()> {
+    let mut byte = [0u8; 1];
+    tcp.read_exact(&mut byte).await?;
+
+    let extended = matches!(
+        tokio::time::timeout(LEGACY_PING_TIMEOUT, tcp.read_exact(&mut byte)).await,
+        Ok(Ok(_)) if byte[0] == 0x01
+    );
+
+    let response = cx
+        .run_on_main_thread(|w| {
+            let status = w.world.resource::<ServerStatus>().clone();
+            status.response(w.world)
+        })
+        .await;
+
+    let reply = if extended {
+        format!(
+            "{SECTION_SIGN}1\0{}\0{}\0{}\0{}\0{}",
+            response.version.protocol,
+            response.version.name,
+            response.description.to_legacy(),
+            response.players.online,
+            response.players.max,
+        )
+    } else {
+        // the fields are separated with section signs here, so the MOTD can't have any formatting
+        format!(
+            "{}{SECTION_SIGN}{}{SECTION_SIGN}{}",
+            response.description.to_plain(),
+            response.players.online,
+            response.players.max,
+        )
+    };
+
+    // the string is UTF-16, prefixed with its length in code units
+    let mut reply = reply.encode_utf16().collect::<Vec<u16>>();
+    reply.truncate(u16::MAX.into());
+
+    let mut packet = Vec::with_capacity(3 + reply.len() * 2);
+    packet.push(LEGACY_KICK);
+    packet.extend((reply.len() as u16).to_be_bytes());
+    packet.extend(reply.iter().flat_map(|unit| unit.to_be_bytes()));
+
+    tcp.write_all(&packet).await?;
+    tcp.shutdown().await?;
+
+    Ok(())
+}
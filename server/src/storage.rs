@@ -0,0 +1,326 @@
+//! Saving the chunks that changed, so they come back the same instead of being generated again.
+//!
+//! Each chunk is a file in the `chunks` directory of the world, named after its position. It holds the block states
+//! of every block and the biomes of every 4×4×4 cell from the bottom of the world up, compressed with zstd.
+//! Block state ids change between versions, so the file starts with the protocol version it was saved with,
+//! and chunks saved by other versions are generated again.
+//!
+//! The items in containers like chests are all in the `containers` file, with their positions and the protocol version too.
+//!
+//! The seed the world was generated with is kept next to them in the `seed` file, so the chunks that weren't saved
+//! are generated the same way the next time, even if the seed was picked at random.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use oxcr_protocol::{
+    aott::prelude::Parser,
+    model::{
+        block::BlockState,
+        item::{ItemStack, Slot},
+        PROTOCOL_VERSION,
+    },
+    ser::{Compression, Deserialize, Serialize, Zstd},
+};
+
+use crate::{
+    generator::WorldSeed,
+    world::{Chunk, HEIGHT, MIN_Y},
+};
+
+const MAGIC: &[u8; 4] = b"OXCH";
+const CONTAINERS_MAGIC: &[u8; 4] = b"OXCO";
+const BLOCKS: usize = (HEIGHT * 16 * 16) as usize;
+const BIOMES: usize = BLOCKS / 64;
+
+/// Where chunks are saved to and loaded from.
+#[derive(Debug, Clone)]
+pub struct ChunkStorage {
+    directory: PathBuf,
+}
+
+impl ChunkStorage {
+    /// `world` is the world directory, and the `chunks` directory in it is created once the first chunk is saved.
+    pub fn new(world: &Path) -> Self {
+        Self {
+            directory: world.join("chunks"),
+        }
+    }
+
+    fn path(&self, (x, z): (i32, i32)) -> PathBuf {
+        self.directory.join(format!("{x}.{z}.chunk"))
+    }
+
+    /// `None` if the chunk was never saved or can't be read, in which case it should be generated.
+    pub fn load(&self, position: (i32, i32)) -> Option<Chunk> {
+        let path = self.path(position);
+        let file = match fs::read(&path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return None,
+            Err(error) => {
+                warn!(%error, path = %path.display(), "failed to read a chunk");
+                return None;
+            }
+        };
+
+        let chunk = decode(&file);
+        if chunk.is_none() {
+            warn!(path = %path.display(), "generating a chunk again that's invalid or from another version");
+        }
+        chunk
+    }
+
+    /// Writes to a temporary file that replaces the old one, so a chunk is never half saved.
+    pub fn save(&self, position: (i32, i32), chunk: &Chunk) -> io::Result<()> {
+        fs::create_dir_all(&self.directory)?;
+        let path = self.path(position);
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, encode(chunk)?)?;
+        fs::rename(temporary, path)
+    }
+}
+
+/// The seed the world in `world` was generated with, or `None` if it's new.
+pub fn load_seed(world: &Path) -> io::Result<Option<WorldSeed>> {
+    let seed = match fs::read_to_string(world.join("seed")) {
+        Ok(seed) => seed,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    seed.trim()
+        .parse()
+        .map(|seed| Some(WorldSeed(seed)))
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Keeps the seed for the next time the world is loaded, creating the world directory if needed.
+pub fn save_seed(world: &Path, seed: WorldSeed) -> io::Result<()> {
+    fs::create_dir_all(world)?;
+    fs::write(world.join("seed"), format!("{}\n", seed.0))
+}
+
+/// The slots of every container by the position of its block. Empty if there's no `containers` file yet,
+/// or it's from another version, since item ids change between versions too.
+pub fn load_containers(world: &Path) -> io::Result<Vec<(IVec3, Vec<Slot>)>> {
+    let path = world.join("containers");
+    let file = match fs::read(&path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(error),
+    };
+
+    let containers = decode_containers(&file);
+    if containers.is_none() {
+        warn!(path = %path.display(), "ignoring containers that are invalid or from another version");
+    }
+    Ok(containers.unwrap_or_default())
+}
+
+/// Replaces the `containers` file the same way chunks are saved, creating the world directory if needed.
+pub fn save_containers(world: &Path, containers: &[(IVec3, &[Slot])]) -> io::Result<()> {
+    fs::create_dir_all(world)?;
+    let path = world.join("containers");
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, encode_containers(containers)?)?;
+    fs::rename(temporary, path)
+}
+
+fn blocks() -> impl Iterator<Item = (i32, i32, i32)> {
+    (MIN_Y..MIN_Y + HEIGHT).flat_map(|y| (0..16).flat_map(move |z| (0..16).map(move |x| (x, y, z))))
+}
+
+fn biome_cells() -> impl Iterator<Item = (i32, i32, i32)> {
+    blocks().filter(|(x, y, z)| (x | y | z) & 3 == 0)
+}
+
+fn encode(chunk: &Chunk) -> io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity((BLOCKS + BIOMES) * 2);
+    for (x, y, z) in blocks() {
+        data.extend(chunk.block(x, y, z).0.to_le_bytes());
+    }
+    for (x, y, z) in biome_cells() {
+        data.extend(chunk.biome(x, y, z).to_le_bytes());
+    }
+
+    let compressed = Zstd::default()
+        .encode(&data)
+        .map_err(|error| io::Error::new(io::ErrorKind::Other, error.to_string()))?;
+
+    let mut file = MAGIC.to_vec();
+    file.extend(PROTOCOL_VERSION.to_le_bytes());
+    file.extend_from_slice(&compressed);
+    Ok(file)
+}
+
+fn invalid_data(error: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if data.len() < len {
+        return None;
+    }
+    let (taken, rest) = data.split_at(len);
+    *data = rest;
+    Some(taken)
+}
+
+fn take_array<const N: usize>(data: &mut &[u8]) -> Option<[u8; N]> {
+    take(data, N)?.try_into().ok()
+}
+
+/// Each container is its position, how many slots it has and how many of them have items,
+/// then the index and length of each of those items followed by the item as it's sent in packets.
+fn encode_containers(containers: &[(IVec3, &[Slot])]) -> io::Result<Vec<u8>> {
+    let mut data = vec![];
+    for (position, slots) in containers {
+        for coordinate in position.to_array() {
+            data.extend(coordinate.to_le_bytes());
+        }
+        let items = slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| Some((index, slot.as_ref()?)))
+            .collect::<Vec<_>>();
+        data.extend((slots.len() as u16).to_le_bytes());
+        data.extend((items.len() as u16).to_le_bytes());
+        for (index, item) in items {
+            let item = item.serialize().map_err(invalid_data)?;
+            data.extend((index as u16).to_le_bytes());
+            data.extend((item.len() as u32).to_le_bytes());
+            data.extend_from_slice(&item);
+        }
+    }
+
+    let compressed = Zstd::default().encode(&data).map_err(invalid_data)?;
+    let mut file = CONTAINERS_MAGIC.to_vec();
+    file.extend(PROTOCOL_VERSION.to_le_bytes());
+    file.extend_from_slice(&compressed);
+    Ok(file)
+}
+
+fn decode_containers(file: &[u8]) -> Option<Vec<(IVec3, Vec<Slot>)>> {
+    let file = file.strip_prefix(CONTAINERS_MAGIC)?;
+    if file.len() < 4 {
+        return None;
+    }
+    let (version, compressed) = file.split_at(4);
+    if i32::from_le_bytes(version.try_into().ok()?) != PROTOCOL_VERSION {
+        return None;
+    }
+
+    let data = Zstd::decode(compressed).ok()?;
+    let mut data = &data[..];
+
+    let mut containers = vec![];
+    while !data.is_empty() {
+        let mut position = [0; 3];
+        for coordinate in &mut position {
+            *coordinate = i32::from_le_bytes(take_array(&mut data)?);
+        }
+        let len = u16::from_le_bytes(take_array(&mut data)?);
+        let items = u16::from_le_bytes(take_array(&mut data)?);
+
+        let mut slots = vec![None; usize::from(len)];
+        for _ in 0..items {
+            let index = u16::from_le_bytes(take_array(&mut data)?);
+            let item_len = u32::from_le_bytes(take_array(&mut data)?);
+            let item = take(&mut data, item_len as usize)?;
+            *slots.get_mut(usize::from(index))? = Some(ItemStack::deserialize.parse(item).ok()?);
+        }
+        containers.push((IVec3::from_array(position), slots));
+    }
+    Some(containers)
+}
+
+fn decode(file: &[u8]) -> Option<Chunk> {
+    let file = file.strip_prefix(MAGIC)?;
+    if file.len() < 4 {
+        return None;
+    }
+    let (version, compressed) = file.split_at(4);
+    if i32::from_le_bytes(version.try_into().ok()?) != PROTOCOL_VERSION {
+        return None;
+    }
+
+    let data = Zstd::decode(compressed).ok()?;
+    if data.len() != (BLOCKS + BIOMES) * 2 {
+        return None;
+    }
+    let mut ids = data
+        .chunks_exact(2)
+        .map(|id| u16::from_le_bytes([id[0], id[1]]));
+
+    let mut chunk = Chunk::empty();
+    for (x, y, z) in blocks() {
+        let state = BlockState(ids.next()?);
+        if usize::from(state.0) >= BlockState::count() {
+            return None;
+        }
+        chunk.set_block(x, y, z, state);
+    }
+    for (x, y, z) in biome_cells() {
+        chunk.set_biome(x, y, z, ids.next()?);
+    }
+    Some(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut chunk = Chunk::empty();
+        chunk.set_block(3, MIN_Y, 7, BlockState::BEDROCK);
+        chunk.set_block(15, 100, 0, BlockState::STONE);
+        chunk.set_biome(8, 64, 12, 5);
+
+        let file = encode(&chunk).unwrap();
+        let decoded = decode(&file).unwrap();
+        for (x, y, z) in blocks() {
+            assert_eq!(decoded.block(x, y, z), chunk.block(x, y, z));
+        }
+        assert_eq!(decoded.biome(9, 65, 13), 5);
+        assert_eq!(decoded.biome(0, 64, 0), 0);
+
+        let mut outdated = file.clone();
+        outdated[4..8].copy_from_slice(&(PROTOCOL_VERSION - 1).to_le_bytes());
+        assert!(decode(&outdated).is_none());
+        assert!(decode(&file[..20]).is_none());
+    }
+
+    #[test]
+    fn containers() {
+        let mut slots = vec![None; 27];
+        slots[3] = ItemStack::new("stone", 12);
+        slots[26] = ItemStack::new("diamond_sword", 1);
+        let position = IVec3::new(-5, MIN_Y, 300);
+
+        let file = encode_containers(&[(position, &slots[..]), (IVec3::ZERO, &[])]).unwrap();
+        let decoded = decode_containers(&file).unwrap();
+        assert_eq!(decoded, [(position, slots), (IVec3::ZERO, vec![])]);
+
+        let mut outdated = file.clone();
+        outdated[4..8].copy_from_slice(&(PROTOCOL_VERSION - 1).to_le_bytes());
+        assert!(decode_containers(&outdated).is_none());
+    }
+
+    #[test]
+    fn seed() {
+        let world = std::env::temp_dir().join(format!("oxcr-seed-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&world);
+
+        assert!(load_seed(&world).unwrap().is_none());
+        save_seed(&world, WorldSeed(-1234567890123)).unwrap();
+        assert_eq!(load_seed(&world).unwrap(), Some(WorldSeed(-1234567890123)));
+
+        fs::write(world.join("seed"), "not a seed").unwrap();
+        assert!(load_seed(&world).is_err());
+
+        fs::remove_dir_all(&world).unwrap();
+    }
+}
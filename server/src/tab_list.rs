@@ -0,0 +1,217 @@
+//! Keeping the tab list of every player in sync with who is online.
+//!
+//! Everything is queued on the players' connections in the same update as entity spawns, so it arrives in order:
+//! the client ignores players spawned before they're in its tab list.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use oxcr_protocol::{
+    model::{
+        chat::ChatComponent,
+        packets::play::{
+            PlayerInfoActions, PlayerInfoEntry, PlayerInfoRemove, PlayerInfoUpdate,
+            SetTabListHeaderAndFooter,
+        },
+        VarInt,
+    },
+    ser::{Array, Json},
+    uuid::Uuid,
+    PlayerN,
+};
+
+use crate::{
+    config::{Config, TabListConfig},
+    model::{Joined, PlayerGameMode, PlayerLatency, PlayerName, PlayerUuid},
+};
+
+/// Everything a player's entry in the tab list is made of.
+type Listed = (
+    Entity,
+    &'static PlayerUuid,
+    &'static PlayerName,
+    &'static PlayerGameMode,
+    &'static PlayerLatency,
+    &'static PlayerN,
+);
+
+/// What's sent about players who weren't in the tab list yet.
+const ADD: PlayerInfoActions = PlayerInfoActions::ADD_PLAYER
+    .union(PlayerInfoActions::UPDATE_GAME_MODE)
+    .union(PlayerInfoActions::UPDATE_LISTED)
+    .union(PlayerInfoActions::UPDATE_LATENCY);
+
+/// The players in everyone's tab list, to know who to remove once their entity is gone.
+#[derive(Resource, Debug, Default)]
+pub struct TabList {
+    listed: HashMap<Entity, Uuid>,
+}
+
+fn entry(
+    uuid: &PlayerUuid,
+    name: &PlayerName,
+    game_mode: &PlayerGameMode,
+    latency: &PlayerLatency,
+) -> PlayerInfoEntry {
+    PlayerInfoEntry {
+        uuid: uuid.0,
+        name: name.0.clone(),
+        // there are no skins without online mode
+        properties: Array::empty(),
+        game_mode: game_mode.0,
+        listed: true,
+        latency: VarInt(latency.0),
+        display_name: None,
+    }
+}
+
+fn header_and_footer(config: &TabListConfig) -> SetTabListHeaderAndFooter {
+    // the config is validated, so the markup is too
+    let text = |markup: &str| {
+        ChatComponent::from_markup(markup).unwrap_or_else(|_| ChatComponent::text(markup))
+    };
+
+    SetTabListHeaderAndFooter {
+        header: Json(text(&config.header)),
+        footer: Json(text(&config.footer)),
+    }
+}
+
+/// Sends players who just joined everyone in the tab list, and everyone else the players who joined.
+pub fn add_to_tab_list(
+    config: Res<Config>,
+    mut tab_list: ResMut<TabList>,
+    joined: Query<Entity, Added<Joined>>,
+    players: Query<Listed, With<Joined>>,
+) {
+    if joined.is_empty() {
+        return;
+    }
+
+    let mut everyone = vec![];
+    let mut new = vec![];
+
+    for (entity, uuid, name, game_mode, latency, _) in &players {
+        let entry = entry(uuid, name, game_mode, latency);
+
+        if joined.contains(entity) {
+            tab_list.listed.insert(entity, uuid.0);
+            new.push(entry.clone());
+        }
+        everyone.push(entry);
+    }
+
+    let has_header = !config.tab_list.header.is_empty() || !config.tab_list.footer.is_empty();
+    for (entity, .., net) in &players {
+        if joined.contains(entity) {
+            net.0.queue(PlayerInfoUpdate {
+                actions: ADD,
+                players: everyone.clone(),
+            });
+            if has_header {
+                net.0.queue(header_and_footer(&config.tab_list));
+            }
+        } else {
+            net.0.queue(PlayerInfoUpdate {
+                actions: ADD,
+                players: new.clone(),
+            });
+        }
+    }
+}
+
+/// Removes players who left from everyone's tab list.
+pub fn remove_from_tab_list(
+    mut tab_list: ResMut<TabList>,
+    mut left: RemovedComponents<Joined>,
+    players: Query<&PlayerN, With<Joined>>,
+) {
+    let left = left
+        .iter()
+        .filter_map(|entity| tab_list.listed.remove(&entity))
+        .collect::<Vec<_>>();
+
+    if left.is_empty() {
+        return;
+    }
+
+    for net in &players {
+        net.0.queue(PlayerInfoRemove {
+            players: Array::new(&left),
+        });
+    }
+}
+
+/// Tells everyone about game mode and latency changes.
+pub fn update_tab_list(
+    changed: Query<(Listed, Ref<Joined>), Or<(Changed<PlayerGameMode>, Changed<PlayerLatency>)>>,
+    players: Query<&PlayerN, With<Joined>>,
+) {
+    let updated = changed
+        .iter()
+        // players who just joined were sent with everything already
+        .filter(|(_, joined)| !joined.is_added())
+        .map(|((_, uuid, name, game_mode, latency, _), _)| entry(uuid, name, game_mode, latency))
+        .collect::<Vec<_>>();
+
+    if updated.is_empty() {
+        return;
+    }
+
+    for net in &players {
+        net.0.queue(PlayerInfoUpdate {
+            actions: PlayerInfoActions::UPDATE_GAME_MODE | PlayerInfoActions::UPDATE_LATENCY,
+            players: updated.clone(),
+        });
+    }
+}
+
+/// Sends the new header and footer to everyone when they're changed in the config.
+pub fn broadcast_tab_list_header(
+    config: Res<Config>,
+    mut last: Local<Option<TabListConfig>>,
+    players: Query<&PlayerN, With<Joined>>,
+) {
+    if !config.is_changed() {
+        return;
+    }
+
+    // the first time around, these are what players get when joining
+    let Some(last) = last.replace(config.tab_list.clone()) else {
+        return;
+    };
+
+    if last != config.tab_list {
+        for net in &players {
+            net.0.queue(header_and_footer(&config.tab_list));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oxcr_protocol::{
+        model::packets::play::GameMode,
+        ser::{FixedStr, Serialize},
+    };
+
+    use super::*;
+
+    #[test]
+    fn only_sends_the_actions() {
+        let update = PlayerInfoUpdate {
+            actions: PlayerInfoActions::UPDATE_LATENCY,
+            players: vec![entry(
+                &PlayerUuid(Uuid::nil()),
+                &PlayerName(FixedStr::from_string("Steve").unwrap()),
+                &PlayerGameMode(GameMode::Creative),
+                &PlayerLatency(300),
+            )],
+        };
+
+        let mut expected = vec![0x10, 1];
+        expected.extend([0; 16]);
+        expected.extend([0xAC, 0x02]);
+        assert_eq!(&update.serialize().unwrap()[..], expected);
+    }
+}
@@ -0,0 +1,358 @@
+//! Which entities each player knows about. An entity is spawned for a player once it's within the player's
+//! view distance, moved while it stays there, and removed once it leaves or is despawned.
+//!
+//! Entities are sent with the index of their [`Entity`] as their network id, like the id players get in [`LoginPlay`](oxcr_protocol::model::packets::play::LoginPlay).
+
+use std::collections::HashSet;
+
+use bevy::{math::DVec3, prelude::*};
+use oxcr_protocol::{
+    model::{
+        packets::play::{
+            RemoveEntities, SetEntityMetadata, SetHeadRotation, SpawnEntity, SpawnPlayer,
+            TeleportEntity, UpdateEntityPosition, UpdateEntityPositionAndRotation,
+            UpdateEntityRotation,
+        },
+        VarInt,
+    },
+    ser::{Angle, Array},
+    uuid::Uuid,
+    PlayerN,
+};
+
+use crate::{
+    config::Config,
+    metadata::MetadataQuery,
+    model::{EntityKind, Joined, OnGround, PlayerUuid, Position, Rotation},
+    view::{self, ViewDistance},
+};
+
+/// How many steps of a relative move make up a block.
+const MOVE_STEPS: f64 = 4096.0;
+
+/// The entities a player was sent.
+#[derive(Component, Debug, Default)]
+pub struct TrackedEntities(HashSet<Entity>);
+
+impl TrackedEntities {
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.0.contains(&entity)
+    }
+}
+
+/// What the players tracking an entity were last sent about where it is, so that only changes are sent.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct SentPosition {
+    /// In steps of relative moves, so that rounding errors don't add up.
+    steps: [i64; 3],
+    yaw: Angle,
+    pitch: Angle,
+    on_ground: bool,
+}
+
+impl SentPosition {
+    fn new(position: DVec3, rotation: Rotation, on_ground: bool) -> Self {
+        Self {
+            steps: [position.x, position.y, position.z]
+                .map(|axis| (axis * MOVE_STEPS).floor() as i64),
+            yaw: Angle::from_degrees(rotation.yaw),
+            pitch: Angle::from_degrees(rotation.pitch),
+            on_ground,
+        }
+    }
+}
+
+pub fn network_id(entity: Entity) -> VarInt {
+    VarInt(entity.index() as i32)
+}
+
+/// The chunk an entity is in.
+fn chunk(position: DVec3) -> (i32, i32) {
+    (
+        (position.x / 16.0).floor() as i32,
+        (position.z / 16.0).floor() as i32,
+    )
+}
+
+fn in_view(center: (i32, i32), chunk: (i32, i32), view_distance: i32) -> bool {
+    (center.0 - chunk.0).abs() <= view_distance && (center.1 - chunk.1).abs() <= view_distance
+}
+
+/// Spawns and removes entities for players as they come into and go out of view.
+///
+/// Runs after [`add_to_tab_list`](crate::tab_list::add_to_tab_list), since the client only spawns players
+/// it has in its tab list.
+pub fn track_entities(
+    config: Res<Config>,
+    mut viewers: Query<(
+        Entity,
+        &Position,
+        &PlayerN,
+        &ViewDistance,
+        &mut TrackedEntities,
+    )>,
+    entities: Query<
+        (
+            Entity,
+            &Position,
+            &Rotation,
+            Option<&PlayerUuid>,
+            Option<&EntityKind>,
+            MetadataQuery,
+        ),
+        Or<(With<Joined>, With<EntityKind>)>,
+    >,
+) {
+    for (viewer, position, net, requested, mut tracked) in &mut viewers {
+        let view_distance = view::view_distance(*requested, &config);
+        let center = chunk(position.0);
+
+        let mut removed = vec![];
+        tracked.0.retain(|entity| {
+            let visible = entities
+                .get(*entity)
+                .is_ok_and(|(_, position, ..)| in_view(center, chunk(position.0), view_distance));
+            if !visible {
+                removed.push(network_id(*entity));
+            }
+            visible
+        });
+        if !removed.is_empty() {
+            net.0.queue(RemoveEntities {
+                entity_ids: Array::new(&removed),
+            });
+        }
+
+        for (entity, position, rotation, uuid, kind, components) in &entities {
+            if entity == viewer
+                || tracked.0.contains(&entity)
+                || !in_view(center, chunk(position.0), view_distance)
+            {
+                continue;
+            }
+
+            let DVec3 { x, y, z } = position.0;
+            let (yaw, pitch) = (
+                Angle::from_degrees(rotation.yaw),
+                Angle::from_degrees(rotation.pitch),
+            );
+
+            if let Some(kind) = kind {
+                net.0.queue(SpawnEntity {
+                    entity_id: network_id(entity),
+                    uuid: Uuid::from_u64_pair(0, entity.to_bits()),
+                    kind: VarInt(kind.0),
+                    x,
+                    y,
+                    z,
+                    pitch,
+                    yaw,
+                    head_yaw: yaw,
+                    data: VarInt(0),
+                    velocity_x: 0,
+                    velocity_y: 0,
+                    velocity_z: 0,
+                });
+            } else {
+                // a player that hasn't got its uuid yet
+                let Some(uuid) = uuid else {
+                    continue;
+                };
+                net.0.queue(SpawnPlayer {
+                    entity_id: network_id(entity),
+                    uuid: uuid.0,
+                    x,
+                    y,
+                    z,
+                    yaw,
+                    pitch,
+                });
+                net.0.queue(SetHeadRotation {
+                    entity_id: network_id(entity),
+                    head_yaw: yaw,
+                });
+            }
+
+            let metadata = components.metadata(true);
+            if !metadata.is_empty() {
+                net.0.queue(SetEntityMetadata {
+                    entity_id: network_id(entity),
+                    metadata,
+                });
+            }
+
+            tracked.0.insert(entity);
+        }
+    }
+}
+
+/// Sends how entities moved to the players tracking them.
+///
+/// Runs before [`track_entities`], so that players who are sent an entity for the first time
+/// get its current position and aren't sent the move to there again.
+pub fn broadcast_movement(
+    mut commands: Commands,
+    mut entities: Query<
+        (
+            Entity,
+            &Position,
+            &Rotation,
+            Option<&OnGround>,
+            Option<&mut SentPosition>,
+        ),
+        Or<(Changed<Position>, Changed<Rotation>, Changed<OnGround>)>,
+    >,
+    viewers: Query<(&PlayerN, &TrackedEntities)>,
+) {
+    for (entity, position, rotation, on_ground, sent) in &mut entities {
+        let on_ground = on_ground.is_some_and(|on_ground| on_ground.0);
+        let new = SentPosition::new(position.0, *rotation, on_ground);
+
+        let Some(mut sent) = sent else {
+            commands.entity(entity).insert(new);
+            continue;
+        };
+        if *sent == new {
+            continue;
+        }
+
+        let delta = [0, 1, 2].map(|axis| new.steps[axis] - sent.steps[axis]);
+        let relative = delta.iter().all(|delta| i16::try_from(*delta).is_ok());
+        let [delta_x, delta_y, delta_z] = delta.map(|delta| delta as i16);
+        let (moved, turned) = (
+            delta != [0; 3],
+            (new.yaw, new.pitch) != (sent.yaw, sent.pitch),
+        );
+
+        let entity_id = network_id(entity);
+        for (net, tracked) in &viewers {
+            if !tracked.0.contains(&entity) {
+                continue;
+            }
+
+            if !relative {
+                let DVec3 { x, y, z } = position.0;
+                net.0.queue(TeleportEntity {
+                    entity_id,
+                    x,
+                    y,
+                    z,
+                    yaw: new.yaw,
+                    pitch: new.pitch,
+                    on_ground,
+                });
+            } else if moved && turned {
+                net.0.queue(UpdateEntityPositionAndRotation {
+                    entity_id,
+                    delta_x,
+                    delta_y,
+                    delta_z,
+                    yaw: new.yaw,
+                    pitch: new.pitch,
+                    on_ground,
+                });
+            } else if turned {
+                net.0.queue(UpdateEntityRotation {
+                    entity_id,
+                    yaw: new.yaw,
+                    pitch: new.pitch,
+                    on_ground,
+                });
+            } else {
+                // also covers only landing or jumping
+                net.0.queue(UpdateEntityPosition {
+                    entity_id,
+                    delta_x,
+                    delta_y,
+                    delta_z,
+                    on_ground,
+                });
+            }
+
+            if new.yaw != sent.yaw {
+                net.0.queue(SetHeadRotation {
+                    entity_id,
+                    head_yaw: new.yaw,
+                });
+            }
+        }
+
+        *sent = new;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bevy::ecs::schedule::ExecutorKind;
+    use oxcr_protocol::{
+        model::packets::{
+            play::{GameMode, PlayerInfoUpdate},
+            Packet,
+        },
+        net::NetConfig,
+        ser::FixedStr,
+        testing::{connect, received},
+        uuid::Uuid,
+    };
+
+    use super::*;
+    use crate::{
+        model::{PlayerGameMode, PlayerLatency, PlayerName},
+        tab_list::{add_to_tab_list, TabList},
+    };
+
+    #[tokio::test]
+    async fn players_are_listed_before_they_spawn() {
+        let mut world = World::new();
+        world.insert_resource(Config::default());
+        world.init_resource::<TabList>();
+        let mut schedule = Schedule::default();
+        schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        schedule.add_systems((add_to_tab_list, track_entities.after(add_to_tab_list)));
+
+        let mut clients = vec![];
+        for (index, name) in ["Alex", "Steve"].into_iter().enumerate() {
+            let (net, client) = connect(NetConfig::default()).await;
+            world.spawn((
+                PlayerN(Arc::new(net)),
+                Joined,
+                Position(DVec3::ZERO),
+                Rotation::default(),
+                PlayerUuid(Uuid::from_u128(index as u128)),
+                PlayerName(FixedStr::from_string(name).unwrap()),
+                PlayerGameMode(GameMode::Creative),
+                PlayerLatency::default(),
+                ViewDistance(2),
+                TrackedEntities::default(),
+            ));
+            schedule.run(&mut world);
+            clients.push(client);
+        }
+
+        for mut client in clients {
+            let ids: Vec<_> = received(&mut client)
+                .await
+                .iter()
+                .map(|packet| packet.id.0)
+                .collect();
+            let listed = ids.iter().rposition(|id| *id == PlayerInfoUpdate::ID.0);
+            let spawned = ids.iter().position(|id| *id == SpawnPlayer::ID.0);
+            assert!(listed.is_some() && listed < spawned, "{ids:#x?}");
+        }
+    }
+
+    #[test]
+    fn view_distance() {
+        assert_eq!(chunk(DVec3::new(-0.5, 64.0, 15.9)), (-1, 0));
+        assert!(in_view((0, 0), (2, -2), 2));
+        assert!(!in_view((0, 0), (3, 0), 2));
+    }
+
+    #[test]
+    fn steps() {
+        let sent = SentPosition::new(DVec3::new(1.0, -0.5, 0.0), Rotation::default(), true);
+        assert_eq!(sent.steps, [4096, -2048, 0]);
+    }
+}
@@ -0,0 +1,175 @@
+//! Which chunks each player has, following them as they move around.
+//!
+//! Players get the chunks within their view distance around the chunk they're in, ring by ring from the middle out,
+//! and are told to forget the ones that fall out of it. The view distance is the one they asked for in their settings,
+//! up to the one in the config.
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use oxcr_protocol::{
+    model::{
+        packets::play::{SetCenterChunk, UnloadChunk},
+        VarInt,
+    },
+    PlayerN,
+};
+
+use crate::{
+    config::{Config, DISTANCES},
+    model::{Joined, Position},
+    world::{chunk_of, Chunks, LoadedChunks, PendingChunks},
+};
+
+/// The view distance the player asked for in their settings.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ViewDistance(pub u8);
+
+/// The chunk the client was told the player is in, which their view is around.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CenterChunk(pub (i32, i32));
+
+/// How many chunks the player gets, and sees entities in, in every direction.
+pub fn view_distance(requested: ViewDistance, config: &Config) -> i32 {
+    let distance = requested
+        .0
+        .min(config.world.view_distance)
+        .max(*DISTANCES.start());
+    i32::from(distance)
+}
+
+/// How many rings of chunks away from `center` the chunk is.
+fn ring(center: (i32, i32), (x, z): (i32, i32)) -> i32 {
+    (x - center.0).abs().max((z - center.1).abs())
+}
+
+/// The chunks within `radius` of `center`, ring by ring from the middle out.
+pub fn spiral(center: (i32, i32), radius: i32) -> impl Iterator<Item = (i32, i32)> {
+    std::iter::once((0, 0))
+        .chain((1..=radius).flat_map(|ring| {
+            // a side of the ring each, without the corner they end in
+            (-ring..ring).flat_map(move |offset| {
+                [
+                    (offset, -ring),
+                    (ring, offset),
+                    (-offset, ring),
+                    (-ring, -offset),
+                ]
+            })
+        }))
+        .map(move |(x, z)| (center.0 + x, center.1 + z))
+}
+
+/// Starts loading the chunks within the view distance of `center` for a player who just joined,
+/// who gets each of them as soon as it's ready.
+pub fn load_spawn_chunks(world: &mut World, player: Entity, center: (i32, i32)) {
+    let requested = world
+        .get::<ViewDistance>(player)
+        .copied()
+        .unwrap_or(ViewDistance(u8::MAX));
+    let distance = view_distance(requested, world.resource::<Config>());
+    let positions = spiral(center, distance).collect::<Vec<_>>();
+
+    let mut chunks = world.resource_mut::<Chunks>();
+    for position in &positions {
+        chunks.request(*position);
+    }
+
+    world.entity_mut(player).insert((
+        CenterChunk(center),
+        LoadedChunks::default(),
+        PendingChunks(positions),
+    ));
+}
+
+/// Sets the view distance a player asked for, which they send again whenever their settings change.
+pub fn set_view_distance(world: &mut World, player: Entity, requested: i8) {
+    let requested = ViewDistance(u8::try_from(requested).unwrap_or(0));
+    let Some(mut entity) = world.get_entity_mut(player) else {
+        return;
+    };
+    // only when it changed, so the view isn't updated for nothing
+    if entity.get::<ViewDistance>() != Some(&requested) {
+        entity.insert(requested);
+    }
+}
+
+/// Sends players the chunks that came into their view and unloads the ones that left it,
+/// when they move into another chunk or their view distance changes.
+pub fn update_views(
+    config: Res<Config>,
+    mut chunks: ResMut<Chunks>,
+    mut players: Query<
+        (
+            &PlayerN,
+            &Position,
+            Ref<ViewDistance>,
+            &mut CenterChunk,
+            &mut LoadedChunks,
+            &mut PendingChunks,
+        ),
+        With<Joined>,
+    >,
+) {
+    for (net, position, requested, mut center, mut loaded, mut pending) in &mut players {
+        let current = chunk_of(position.0.floor().as_ivec3());
+        if current == center.0 && !requested.is_changed() && !config.is_changed() {
+            continue;
+        }
+
+        if current != center.0 {
+            center.0 = current;
+            let packet = SetCenterChunk {
+                x: VarInt(current.0),
+                z: VarInt(current.1),
+            };
+            net.0.queue(packet);
+        }
+
+        let distance = view_distance(*requested, &config);
+        let left = loaded
+            .0
+            .iter()
+            .copied()
+            .filter(|position| ring(current, *position) > distance)
+            .collect::<Vec<_>>();
+        for (x, z) in left {
+            loaded.0.remove(&(x, z));
+            net.0.queue(UnloadChunk { x, z });
+        }
+        pending
+            .0
+            .retain(|position| ring(current, *position) <= distance);
+
+        let queued = pending.0.iter().copied().collect::<HashSet<_>>();
+        for position in spiral(current, distance) {
+            if !loaded.0.contains(&position) && !queued.contains(&position) {
+                chunks.request(position);
+                pending.0.push(position);
+            }
+        }
+        // the ones that were already waiting might not be the nearest anymore
+        pending.0.sort_by_key(|position| ring(current, *position));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spiral_order() {
+        let center = (-3, 7);
+        let positions = spiral(center, 3).collect::<Vec<_>>();
+
+        assert_eq!(positions.len(), 7 * 7);
+        assert_eq!(positions.iter().collect::<HashSet<_>>().len(), 7 * 7);
+        assert_eq!(positions[0], center);
+        assert!(positions
+            .windows(2)
+            .all(|pair| ring(center, pair[0]) <= ring(center, pair[1])));
+        assert!(positions
+            .iter()
+            .all(|position| ring(center, *position) <= 3));
+    }
+}
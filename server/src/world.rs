@@ -0,0 +1,806 @@
+//! The blocks of the world, kept in chunks of 16×16 block columns as high as the world.
+//!
+//! Chunks are made by the [generator](crate::generator) the first time they're needed,
+//! on the async compute pool so the server keeps ticking while they're generated.
+//! Changed blocks and light are collected and sent to everyone who has the chunk once per update.
+//! Chunks nobody has for a while are unloaded, and [saved](crate::storage) first if their blocks changed.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    math::DVec3,
+    prelude::*,
+    tasks::{
+        futures_lite::future::{block_on, poll_once},
+        AsyncComputeTaskPool, Task,
+    },
+};
+use oxcr_protocol::{
+    indexmap::IndexMap,
+    model::{
+        block::BlockState,
+        chunk::{
+            heightmap, ChunkData, ChunkSection, PalettedContainer, SECTION_BIOMES, SECTION_BLOCKS,
+        },
+        packets::{
+            play::{BlockUpdate, ChunkDataAndUpdateLight, UpdateLight, UpdateSectionBlocks},
+            Packet,
+        },
+        registry::Registry,
+        DimensionType, VarInt, WorldgenBiome,
+    },
+    nbt::Nbt,
+    ser::{self, Serialize},
+    PlayerN,
+};
+
+use crate::{
+    config::Config,
+    generator::{self, ChunkGenerator, WorldSeed},
+    light::{self, ChunkLight, Loaded, ALL_SECTIONS},
+    model::Joined,
+    storage::{self, ChunkStorage},
+};
+
+pub const MIN_Y: i32 = DimensionType::OVERWORLD.min_y;
+pub const HEIGHT: i32 = DimensionType::OVERWORLD.height;
+const SECTIONS: usize = (HEIGHT / 16) as usize;
+
+/// Where players spawn when there's no ground at the spawn, the same as vanilla's sea level.
+const SPAWN_HEIGHT: i32 = 63;
+/// The chunk players spawn in, which is loaded with the world and stays loaded.
+const SPAWN_CHUNK: (i32, i32) = (0, 0);
+/// How long a chunk stays loaded after the last player who had it left, in case they come back.
+const UNLOAD_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+enum Blocks {
+    Uniform(BlockState),
+    Mixed(Box<[BlockState; SECTION_BLOCKS]>),
+}
+
+/// 16×16×16 blocks.
+#[derive(Debug, Clone)]
+struct Section {
+    blocks: Blocks,
+    /// Kept up to date for the client, which skips sections without any blocks.
+    non_air: u16,
+    /// By their id in the biome registry, in cells of 4×4×4 blocks.
+    biomes: [u16; SECTION_BIOMES],
+}
+
+impl Section {
+    fn filled(state: BlockState) -> Self {
+        Self {
+            blocks: Blocks::Uniform(state),
+            non_air: if state.is_air() {
+                0
+            } else {
+                SECTION_BLOCKS as u16
+            },
+            biomes: [0; SECTION_BIOMES],
+        }
+    }
+
+    fn get(&self, index: usize) -> BlockState {
+        match &self.blocks {
+            Blocks::Uniform(state) => *state,
+            Blocks::Mixed(blocks) => blocks[index],
+        }
+    }
+
+    /// Returns the block that was there before.
+    fn set(&mut self, index: usize, state: BlockState) -> BlockState {
+        let old = self.get(index);
+        if old == state {
+            return old;
+        }
+
+        if let Blocks::Uniform(uniform) = self.blocks {
+            self.blocks = Blocks::Mixed(Box::new([uniform; SECTION_BLOCKS]));
+        }
+        if let Blocks::Mixed(blocks) = &mut self.blocks {
+            blocks[index] = state;
+        }
+
+        match (old.is_air(), state.is_air()) {
+            (true, false) => self.non_air += 1,
+            (false, true) => self.non_air -= 1,
+            _ => {}
+        }
+        old
+    }
+
+    /// `biome_count` is how many biomes are in the registry.
+    fn to_network(&self, biome_count: usize) -> ChunkSection {
+        let blocks = match &self.blocks {
+            Blocks::Uniform(state) => PalettedContainer::single(state.0.into()),
+            Blocks::Mixed(blocks) => {
+                PalettedContainer::blocks(&blocks.iter().map(|state| state.0).collect::<Vec<_>>())
+            }
+        };
+
+        ChunkSection {
+            block_count: self.non_air as i16,
+            blocks,
+            biomes: PalettedContainer::biomes(&self.biomes, biome_count),
+        }
+    }
+}
+
+/// The index of a block in its section.
+pub fn section_index(x: i32, y: i32, z: i32) -> usize {
+    ((y & 15) << 8 | (z & 15) << 4 | (x & 15)) as usize
+}
+
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    /// From the bottom of the world up.
+    sections: Vec<Section>,
+    light: ChunkLight,
+}
+
+impl Chunk {
+    pub fn empty() -> Self {
+        Self {
+            sections: vec![Section::filled(BlockState::AIR); SECTIONS],
+            light: ChunkLight::default(),
+        }
+    }
+
+    pub fn light(&self) -> &ChunkLight {
+        &self.light
+    }
+
+    pub fn light_mut(&mut self) -> &mut ChunkLight {
+        &mut self.light
+    }
+
+    fn section(y: i32) -> Option<usize> {
+        usize::try_from((y - MIN_Y) >> 4)
+            .ok()
+            .filter(|section| *section < SECTIONS)
+    }
+
+    /// The block at `x` and `z` within the chunk, and at height `y`. Everything above and below the world is air.
+    pub fn block(&self, x: i32, y: i32, z: i32) -> BlockState {
+        Self::section(y).map_or(BlockState::AIR, |section| {
+            self.sections[section].get(section_index(x, y, z))
+        })
+    }
+
+    /// Returns the block that was there before, or `None` if it's outside of the world.
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, state: BlockState) -> Option<BlockState> {
+        let section = Self::section(y)?;
+        Some(self.sections[section].set(section_index(x, y, z), state))
+    }
+
+    pub fn fill_biome(&mut self, biome: u16) {
+        for section in &mut self.sections {
+            section.biomes = [biome; SECTION_BIOMES];
+        }
+    }
+
+    fn biome_cell(x: i32, y: i32, z: i32) -> usize {
+        (((y & 15) >> 2) << 4 | ((z & 15) >> 2) << 2 | ((x & 15) >> 2)) as usize
+    }
+
+    /// The biome of the 4×4×4 cell the block is in, by its id in the biome registry.
+    pub fn biome(&self, x: i32, y: i32, z: i32) -> u16 {
+        Self::section(y).map_or(0, |section| {
+            self.sections[section].biomes[Self::biome_cell(x, y, z)]
+        })
+    }
+
+    /// Sets the biome of the 4×4×4 cell the block is in.
+    pub fn set_biome(&mut self, x: i32, y: i32, z: i32, biome: u16) {
+        if let Some(section) = Self::section(y) {
+            self.sections[section].biomes[Self::biome_cell(x, y, z)] = biome;
+        }
+    }
+
+    /// The height of the highest block in the column that isn't air.
+    pub fn top(&self, x: i32, z: i32) -> Option<i32> {
+        (MIN_Y..MIN_Y + HEIGHT)
+            .rev()
+            .find(|y| !self.block(x, *y, z).is_air())
+    }
+
+    /// How high each column is, from the bottom of the world to above the highest block that isn't air.
+    fn heights(&self) -> Vec<u16> {
+        let mut heights = vec![0; 256];
+        for (column, height) in heights.iter_mut().enumerate() {
+            let (x, z) = ((column & 15) as i32, (column >> 4) as i32);
+            for (index, section) in self.sections.iter().enumerate().rev() {
+                if section.non_air == 0 {
+                    continue;
+                }
+                let top = (0..16)
+                    .rev()
+                    .find(|y| !section.get(section_index(x, *y, z)).is_air());
+                if let Some(y) = top {
+                    *height = (index * 16) as u16 + y as u16 + 1;
+                    break;
+                }
+            }
+        }
+        heights
+    }
+
+    /// `biome_count` is how many biomes are in the registry.
+    pub fn data(&self, biome_count: usize) -> ChunkData {
+        let heights = heightmap(&self.heights(), HEIGHT as usize);
+
+        ChunkData {
+            heightmaps: Nbt::Compound(IndexMap::from([
+                (
+                    "MOTION_BLOCKING".to_owned(),
+                    Nbt::LongArray(heights.clone()),
+                ),
+                ("WORLD_SURFACE".to_owned(), Nbt::LongArray(heights)),
+            ])),
+            sections: self
+                .sections
+                .iter()
+                .map(|section| section.to_network(biome_count))
+                .collect(),
+        }
+    }
+}
+
+/// The chunk a block is in.
+pub fn chunk_of(position: IVec3) -> (i32, i32) {
+    (position.x >> 4, position.z >> 4)
+}
+
+pub fn block_pos(location: &ser::Position) -> IVec3 {
+    IVec3::new(location.x.into(), location.y.into(), location.z.into())
+}
+
+/// `None` for positions too far out to be sent.
+pub fn location(position: IVec3) -> Option<ser::Position> {
+    Some(ser::Position {
+        x: position.x.try_into().ok()?,
+        z: position.z.try_into().ok()?,
+        y: position.y.try_into().ok()?,
+    })
+}
+
+/// Loads the chunk if it was saved, or generates it otherwise.
+fn load_or_generate(
+    generator: &dyn ChunkGenerator,
+    storage: Option<&ChunkStorage>,
+    position: (i32, i32),
+) -> Chunk {
+    let mut chunk = storage
+        .and_then(|storage| storage.load(position))
+        .unwrap_or_else(|| {
+            let mut chunk = Chunk::empty();
+            generator.generate(position, &mut chunk);
+            chunk
+        });
+    light::light_chunk(&mut chunk);
+    chunk
+}
+
+/// Every chunk that's loaded, and what changed in them.
+#[derive(Resource, Debug)]
+pub struct Chunks {
+    generator: Arc<dyn ChunkGenerator>,
+    /// `None` keeps the chunks in memory only.
+    storage: Option<ChunkStorage>,
+    chunks: HashMap<(i32, i32), Chunk>,
+    /// Chunks being loaded or generated in the background.
+    generating: HashMap<(i32, i32), Task<Chunk>>,
+    /// Chunks whose blocks changed since they were last saved.
+    dirty: HashSet<(i32, i32)>,
+    /// Chunks being saved in the background, which stay loaded until that worked.
+    saving: HashMap<(i32, i32), Task<io::Result<()>>>,
+    /// When the last player who had the chunk stopped having it.
+    unseen_since: HashMap<(i32, i32), Instant>,
+    /// Blocks changed since the changes were last sent, by section and then position in the section.
+    changes: HashMap<[i32; 3], HashMap<[u8; 3], BlockState>>,
+    /// Sections whose light changed since the changes were last sent, as bits by chunk.
+    light_changes: HashMap<(i32, i32), u32>,
+}
+
+impl Chunks {
+    pub fn new(generator: Arc<dyn ChunkGenerator>, storage: Option<ChunkStorage>) -> Self {
+        Self {
+            generator,
+            storage,
+            chunks: HashMap::new(),
+            generating: HashMap::new(),
+            dirty: HashSet::new(),
+            saving: HashMap::new(),
+            unseen_since: HashMap::new(),
+            changes: HashMap::new(),
+            light_changes: HashMap::new(),
+        }
+    }
+
+    fn loaded(&mut self) -> Loaded<'_> {
+        Loaded {
+            chunks: &mut self.chunks,
+            changed: &mut self.light_changes,
+        }
+    }
+
+    /// Adds a chunk that finished loading or generating, and lets the light of the chunks around it into it.
+    fn insert(&mut self, position: (i32, i32), chunk: Chunk) {
+        self.chunks.insert(position, chunk);
+        light::join(&mut self.loaded(), position);
+    }
+
+    /// `None` if the chunk isn't loaded.
+    pub fn get(&self, position: (i32, i32)) -> Option<&Chunk> {
+        self.chunks.get(&position)
+    }
+
+    /// Loads or generates the chunk right away if it isn't loaded, blocking until it's done.
+    pub fn get_or_generate(&mut self, position: (i32, i32)) -> &Chunk {
+        if !self.chunks.contains_key(&position) {
+            let chunk = match self.generating.remove(&position) {
+                Some(task) => block_on(task),
+                None => load_or_generate(&*self.generator, self.storage.as_ref(), position),
+            };
+            self.insert(position, chunk);
+        }
+        &self.chunks[&position]
+    }
+
+    /// Starts loading or generating the chunk in the background, unless it's loaded or that already started.
+    pub fn request(&mut self, position: (i32, i32)) {
+        if self.chunks.contains_key(&position) || self.generating.contains_key(&position) {
+            return;
+        }
+
+        let generator = self.generator.clone();
+        let storage = self.storage.clone();
+        let task = AsyncComputeTaskPool::get()
+            .spawn(async move { load_or_generate(&*generator, storage.as_ref(), position) });
+        self.generating.insert(position, task);
+    }
+
+    pub fn is_flat(&self) -> bool {
+        self.generator.is_flat()
+    }
+
+    /// On top of the ground in the middle of the block at 0, 0.
+    pub fn spawn(&self) -> DVec3 {
+        let top = self.get(SPAWN_CHUNK).and_then(|chunk| chunk.top(0, 0));
+        let y = top.map_or(SPAWN_HEIGHT, |top| top + 1);
+        DVec3::new(0.5, f64::from(y), 0.5)
+    }
+
+    /// `None` if the chunk isn't loaded.
+    pub fn block(&self, position: IVec3) -> Option<BlockState> {
+        let chunk = self.chunks.get(&chunk_of(position))?;
+        Some(chunk.block(position.x & 15, position.y, position.z & 15))
+    }
+
+    /// Returns the block that was there before, or `None` if the chunk isn't loaded or it's outside of the world.
+    pub fn set_block(&mut self, position: IVec3, state: BlockState) -> Option<BlockState> {
+        let chunk = self.chunks.get_mut(&chunk_of(position))?;
+        let IVec3 { x, y, z } = position;
+        let old = chunk.set_block(x & 15, y, z & 15, state)?;
+
+        if old != state {
+            self.dirty.insert(chunk_of(position));
+            self.changes
+                .entry([x >> 4, y >> 4, z >> 4])
+                .or_default()
+                .insert([x & 15, y & 15, z & 15].map(|axis| axis as u8), state);
+            light::block_changed(&mut self.loaded(), position, old, state);
+        }
+        Some(old)
+    }
+
+    /// Starts saving the chunk in the background if its blocks changed and it isn't being saved already.
+    fn save(&mut self, position: (i32, i32)) {
+        if self.saving.contains_key(&position) || !self.dirty.remove(&position) {
+            return;
+        }
+        // without storage, there's nowhere to keep the changes
+        let (Some(storage), Some(chunk)) = (self.storage.clone(), self.chunks.get(&position))
+        else {
+            return;
+        };
+
+        let chunk = chunk.clone();
+        let task = AsyncComputeTaskPool::get().spawn(async move { storage.save(position, &chunk) });
+        self.saving.insert(position, task);
+    }
+
+    /// Takes the results of the saves that are done, or waits for all of them with `wait`.
+    /// Chunks that failed to save are marked as changed again, to be saved once they'd be unloaded again.
+    fn finish_saving(&mut self, wait: bool) {
+        for (position, mut task) in std::mem::take(&mut self.saving) {
+            let result = if wait {
+                Some(block_on(&mut task))
+            } else {
+                block_on(poll_once(&mut task))
+            };
+
+            match result {
+                None => {
+                    self.saving.insert(position, task);
+                }
+                Some(Ok(())) => {}
+                Some(Err(error)) => {
+                    error!(%error, ?position, "failed to save a chunk");
+                    self.dirty.insert(position);
+                    self.unseen_since.insert(position, Instant::now());
+                }
+            }
+        }
+    }
+
+    /// Saves every chunk whose blocks changed, blocking until it's done.
+    /// Returns how many chunks couldn't be saved.
+    pub fn save_all(&mut self) -> usize {
+        let dirty = self.dirty.iter().copied().collect::<Vec<_>>();
+        for position in dirty {
+            self.save(position);
+        }
+        self.finish_saving(true);
+        self.dirty.len()
+    }
+
+    /// Unloads the chunks that weren't in `viewed` for a while, once they're saved if they changed.
+    fn unload_unviewed(&mut self, viewed: &HashSet<(i32, i32)>, now: Instant) {
+        let Self {
+            chunks,
+            unseen_since,
+            ..
+        } = self;
+        unseen_since.retain(|position, _| chunks.contains_key(position));
+        for position in chunks.keys() {
+            if viewed.contains(position) {
+                unseen_since.remove(position);
+            } else {
+                unseen_since.entry(*position).or_insert(now);
+            }
+        }
+
+        let expired = unseen_since
+            .iter()
+            .filter(|(_, since)| now.duration_since(**since) >= UNLOAD_DELAY)
+            .map(|(position, _)| *position)
+            .collect::<Vec<_>>();
+        for position in expired {
+            self.save(position);
+            if self.dirty.contains(&position) || self.saving.contains_key(&position) {
+                continue;
+            }
+            self.unseen_since.remove(&position);
+            self.chunks.remove(&position);
+            self.light_changes.remove(&position);
+        }
+    }
+}
+
+/// Makes the generator from the config, once the registries it looks biomes up in are filled in.
+pub fn init_world(
+    mut commands: Commands,
+    config: Res<Config>,
+    biomes: Res<Registry<WorldgenBiome>>,
+) {
+    let directory = &config.world.directory;
+    let seed = match storage::load_seed(directory) {
+        Ok(Some(seed)) => {
+            // like vanilla, the seed in the config only counts for new worlds
+            if !config.world.seed.trim().is_empty()
+                && WorldSeed::from_config(&config.world.seed) != seed
+            {
+                warn!(
+                    seed = seed.0,
+                    "the world already has a seed, ignoring the one in the config"
+                );
+            }
+            seed
+        }
+        Ok(None) => {
+            let seed = WorldSeed::from_config(&config.world.seed);
+            if let Err(error) = storage::save_seed(directory, seed) {
+                error!(%error, "failed to save the world seed");
+            }
+            seed
+        }
+        Err(error) => {
+            // not overwritten, it might still be fixed by hand
+            error!(%error, "failed to read the world seed, using the one in the config");
+            WorldSeed::from_config(&config.world.seed)
+        }
+    };
+    let generator = generator::from_config(&config.world, seed, &biomes);
+    info!(seed = seed.0, generator = ?config.world.generator, "generating chunks");
+    commands.insert_resource(seed);
+
+    // before anyone joins, so that joining doesn't wait for it
+    let mut chunks = Chunks::new(generator, Some(ChunkStorage::new(directory)));
+    chunks.get_or_generate(SPAWN_CHUNK);
+    commands.insert_resource(chunks);
+}
+
+/// Moves the chunks that finished loading or generating in with the loaded ones.
+pub fn finish_generating(mut chunks: ResMut<Chunks>) {
+    if chunks.generating.is_empty() {
+        return;
+    }
+
+    let mut finished = vec![];
+    chunks
+        .generating
+        .retain(|position, task| match block_on(poll_once(task)) {
+            Some(chunk) => {
+                finished.push((*position, chunk));
+                false
+            }
+            None => true,
+        });
+    for (position, chunk) in finished {
+        chunks.insert(position, chunk);
+    }
+}
+
+/// The chunks a player was sent.
+#[derive(Component, Debug, Default)]
+pub struct LoadedChunks(pub HashSet<(i32, i32)>);
+
+/// The chunks a player should be sent once they're generated, nearest first.
+#[derive(Component, Debug, Default)]
+pub struct PendingChunks(pub Vec<(i32, i32)>);
+
+/// Sends players the chunks they're waiting for that are done generating,
+/// as many as fit into their send queue. The rest waits for the next tick.
+pub fn send_chunks(
+    chunks: Res<Chunks>,
+    biomes: Res<Registry<WorldgenBiome>>,
+    mut players: Query<(&PlayerN, &mut LoadedChunks, &mut PendingChunks), With<Joined>>,
+) {
+    for (net, mut loaded, mut pending) in &mut players {
+        if pending
+            .0
+            .iter()
+            .all(|position| chunks.get(*position).is_none())
+        {
+            continue;
+        }
+
+        let mut room = net
+            .0
+            .config
+            .send_capacity
+            .saturating_sub(net.0.metrics().send_queue_depth);
+        pending.0.retain(|position| {
+            let Some(chunk) = chunks.get(*position).filter(|_| room > 0) else {
+                return true;
+            };
+            room -= 1;
+            let packet = ChunkDataAndUpdateLight {
+                x: position.0,
+                z: position.1,
+                data: chunk.data(biomes.0.len()),
+                light: chunk.light().data(ALL_SECTIONS),
+            };
+            net.0.queue(packet);
+            loaded.0.insert(*position);
+            false
+        });
+    }
+}
+
+/// Unloads the chunks no player has or is waiting for once they weren't needed for a while, except the spawn chunk.
+pub fn unload_chunks(
+    mut chunks: ResMut<Chunks>,
+    players: Query<(&LoadedChunks, &PendingChunks), With<Joined>>,
+) {
+    let viewed = players
+        .iter()
+        .flat_map(|(loaded, pending)| loaded.0.iter().chain(&pending.0))
+        .copied()
+        .chain([SPAWN_CHUNK])
+        .collect();
+    chunks.finish_saving(false);
+    chunks.unload_unviewed(&viewed, Instant::now());
+}
+
+/// Saves the chunks that changed before the server exits.
+pub fn save_chunks(mut chunks: ResMut<Chunks>) {
+    let count = chunks.dirty.len() + chunks.saving.len();
+    match chunks.save_all() {
+        0 => info!(count, "saved chunks"),
+        failed => error!(count, failed, "failed to save some chunks"),
+    }
+}
+
+fn send_to_viewers<T: Packet + Serialize + std::fmt::Debug + Clone>(
+    viewers: &Query<(&PlayerN, &LoadedChunks), With<Joined>>,
+    chunk: (i32, i32),
+    packet: T,
+) {
+    for (net, loaded) in viewers {
+        if !loaded.0.contains(&chunk) {
+            continue;
+        }
+
+        net.0.queue(packet.clone());
+    }
+}
+
+/// Sends the blocks that changed to the players who have their chunks,
+/// as a single block update if only one changed in a section.
+pub fn broadcast_block_changes(
+    mut chunks: ResMut<Chunks>,
+    viewers: Query<(&PlayerN, &LoadedChunks), With<Joined>>,
+) {
+    if chunks.changes.is_empty() {
+        return;
+    }
+
+    for (section, blocks) in std::mem::take(&mut chunks.changes) {
+        let chunk = (section[0], section[2]);
+
+        if blocks.len() == 1 {
+            let Some((offset, state)) = blocks.into_iter().next() else {
+                continue;
+            };
+            let position = IVec3::from(section) * 16 + IVec3::from(offset.map(i32::from));
+            let Some(location) = location(position) else {
+                continue;
+            };
+            send_to_viewers(
+                &viewers,
+                chunk,
+                BlockUpdate {
+                    location,
+                    block_id: state.into(),
+                },
+            );
+        } else {
+            send_to_viewers(
+                &viewers,
+                chunk,
+                UpdateSectionBlocks {
+                    section,
+                    blocks: blocks
+                        .into_iter()
+                        .map(|(offset, state)| (offset, state.into()))
+                        .collect(),
+                },
+            );
+        }
+    }
+}
+
+/// Sends the light that changed to the players who have the chunks.
+pub fn broadcast_light_changes(
+    mut chunks: ResMut<Chunks>,
+    viewers: Query<(&PlayerN, &LoadedChunks), With<Joined>>,
+) {
+    if chunks.light_changes.is_empty() {
+        return;
+    }
+
+    for (position, sections) in std::mem::take(&mut chunks.light_changes) {
+        let Some(chunk) = chunks.get(position) else {
+            continue;
+        };
+        send_to_viewers(
+            &viewers,
+            position,
+            UpdateLight {
+                x: VarInt(position.0),
+                z: VarInt(position.1),
+                light: chunk.light().data(sections),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::tasks::TaskPool;
+
+    use super::*;
+
+    #[test]
+    fn blocks() {
+        let generator = generator::FlatGenerator::new(&Default::default(), &Registry::default());
+        let mut chunks = Chunks::new(Arc::new(generator), None);
+        chunks.get_or_generate((-1, 0));
+
+        // bedrock, two dirt and grass from the bottom of the world
+        let grass = MIN_Y + 3;
+        let position = IVec3::new(-3, grass, 5);
+        assert_eq!(chunks.block(position), Some(BlockState::GRASS_BLOCK));
+        assert_eq!(chunks.block(IVec3::new(20, grass, 5)), None);
+        assert_eq!(
+            chunks.set_block(position, BlockState::AIR),
+            Some(BlockState::GRASS_BLOCK)
+        );
+        assert_eq!(
+            chunks.set_block(IVec3::new(-3, HEIGHT + MIN_Y, 5), BlockState::STONE),
+            None
+        );
+        assert_eq!(
+            chunks.changes[&[-1, -4, 0]],
+            HashMap::from([([13, 3, 5], BlockState::AIR)])
+        );
+
+        let chunk = &chunks.chunks[&(-1, 0)];
+        let heights = chunk.heights();
+        assert_eq!(heights[5 << 4 | 13], 3);
+        assert_eq!(heights[0], 4);
+        assert_eq!(chunk.data(1).sections[0].block_count, 1023);
+
+        assert_eq!(
+            chunks.spawn(),
+            DVec3::new(0.5, f64::from(SPAWN_HEIGHT), 0.5)
+        );
+        chunks.get_or_generate(SPAWN_CHUNK);
+        assert_eq!(chunks.spawn(), DVec3::new(0.5, f64::from(grass + 1), 0.5));
+    }
+
+    #[test]
+    fn unloading() {
+        let generator = generator::FlatGenerator::new(&Default::default(), &Registry::default());
+        let mut chunks = Chunks::new(Arc::new(generator), None);
+        chunks.get_or_generate((0, 0));
+        chunks.get_or_generate((1, 0));
+        chunks.set_block(IVec3::new(0, 0, 0), BlockState::STONE);
+
+        let now = Instant::now();
+        let viewed = HashSet::from([(1, 0)]);
+        chunks.unload_unviewed(&viewed, now);
+        assert!(chunks.get((0, 0)).is_some());
+
+        chunks.unload_unviewed(&viewed, now + UNLOAD_DELAY);
+        assert!(chunks.get((0, 0)).is_none());
+        assert!(chunks.get((1, 0)).is_some());
+        assert!(chunks.dirty.is_empty());
+    }
+
+    #[test]
+    fn saving_before_unloading() {
+        AsyncComputeTaskPool::init(TaskPool::default);
+        let directory = std::env::temp_dir().join(format!("oxcr-world-{}", std::process::id()));
+        // nothing can be saved in a directory inside of a file
+        let file = directory.join("file");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(&file, []).unwrap();
+
+        let position = IVec3::new(3, 0, 5);
+        for (storage, saved) in [(&file, false), (&directory, true)] {
+            let generator =
+                generator::FlatGenerator::new(&Default::default(), &Registry::default());
+            let storage = ChunkStorage::new(storage);
+            let mut chunks = Chunks::new(Arc::new(generator), Some(storage.clone()));
+            chunks.get_or_generate((0, 0));
+            chunks.set_block(position, BlockState::STONE);
+
+            let now = Instant::now();
+            chunks.unload_unviewed(&HashSet::new(), now);
+            chunks.unload_unviewed(&HashSet::new(), now + UNLOAD_DELAY);
+            // still being saved
+            assert!(chunks.get((0, 0)).is_some());
+
+            chunks.finish_saving(true);
+            chunks.unload_unviewed(&HashSet::new(), now + UNLOAD_DELAY);
+            assert_eq!(chunks.get((0, 0)).is_none(), saved);
+            assert_eq!(chunks.dirty.contains(&(0, 0)), !saved);
+            assert_eq!(
+                storage.load((0, 0)).map(|chunk| chunk.block(3, 0, 5)),
+                saved.then_some(BlockState::STONE)
+            );
+        }
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}